@@ -0,0 +1,79 @@
+/// Placeholder of the bot templates replaced with the name of the profile.
+pub const PROFILE_PLACEHOLDER: &str = "%PROFILE%";
+
+/// A Stoat bot that answers a command with what the profiles are listening
+/// to.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct Options {
+    #[serde(default = "crate::stoat::default_lure_stoat_api_url")]
+    pub api_url: String,
+    /// Token of the bot. Also needed in dry run, where the replies are only
+    /// logged.
+    #[serde(default)]
+    pub bot_token: String,
+    /// Command the bot answers, optionally followed by the name of a
+    /// profile.
+    #[serde(default = "default_command")]
+    pub command: String,
+    /// Names of the profiles the bot answers for. Every profile by default.
+    #[serde(default)]
+    pub profiles: Vec<String>,
+    /// Reply for a profile that is listening to something.
+    #[serde(default = "default_template")]
+    pub template: String,
+    /// Reply for a profile that isn't listening to anything.
+    #[serde(default = "default_idle")]
+    pub idle: String,
+}
+
+fn default_command() -> String {
+    String::from("!np")
+}
+
+fn default_template() -> String {
+    String::from("🎵 %PROFILE% is listening to %NAME% by %ARTIST%")
+}
+
+fn default_idle() -> String {
+    String::from("%PROFILE% isn't listening to anything.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options_minimal() {
+        let options: Options = serde_yaml::from_str("bot_token: meow").unwrap();
+
+        assert_eq!(options.api_url, "https://api.stoat.chat");
+        assert_eq!(options.bot_token, "meow");
+        assert_eq!(options.command, "!np");
+        assert!(options.profiles.is_empty());
+        assert_eq!(
+            options.template,
+            "🎵 %PROFILE% is listening to %NAME% by %ARTIST%"
+        );
+        assert_eq!(options.idle, "%PROFILE% isn't listening to anything.");
+    }
+
+    #[test]
+    fn test_options_full() {
+        let yaml = r"
+            api_url: https://stoat.example.com/api
+            bot_token: meow
+            command: '!meow'
+            profiles: [kitty, puppy]
+            template: '%PROFILE%: %NAME% - %ARTIST%'
+            idle: '%PROFILE%: nothing'
+        ";
+
+        let options: Options = serde_yaml::from_str(yaml).unwrap();
+
+        assert_eq!(options.api_url, "https://stoat.example.com/api");
+        assert_eq!(options.command, "!meow");
+        assert_eq!(options.profiles, ["kitty", "puppy"]);
+        assert_eq!(options.template, "%PROFILE%: %NAME% - %ARTIST%");
+        assert_eq!(options.idle, "%PROFILE%: nothing");
+    }
+}
@@ -0,0 +1,82 @@
+/// A Stoat channel a message is posted to for every track.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct Options {
+    /// ID of the channel.
+    pub channel_id: String,
+    #[serde(default = "crate::stoat::default_lure_stoat_api_url")]
+    pub api_url: String,
+    /// Session token of the account posting the messages. Only optional in
+    /// dry run.
+    #[serde(default)]
+    pub session_token: String,
+    #[serde(default = "default_template")]
+    pub template: String,
+    /// Attach an embed with the cover art and a link to the track.
+    #[serde(default = "default_embed")]
+    pub embed: bool,
+    /// Minimum number of seconds between two messages. Tracks played in
+    /// between are skipped, except the latest one.
+    #[serde(default)]
+    pub throttle: u64,
+    /// Edit the previous message instead of posting a new one.
+    #[serde(default)]
+    pub edit: bool,
+    /// Delete the message once the track stops.
+    #[serde(default)]
+    pub delete_on_stop: bool,
+}
+
+fn default_template() -> String {
+    String::from("🎵 Now playing: %NAME% by %ARTIST%")
+}
+
+const fn default_embed() -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options_minimal() {
+        let yaml = r"
+            channel_id: 01J0000000000000000000000
+            session_token: meow
+        ";
+
+        let options: Options = serde_yaml::from_str(yaml).unwrap();
+
+        assert_eq!(options.channel_id, "01J0000000000000000000000");
+        assert_eq!(options.api_url, "https://api.stoat.chat");
+        assert_eq!(options.session_token, "meow");
+        assert_eq!(options.template, "🎵 Now playing: %NAME% by %ARTIST%");
+        assert!(options.embed);
+        assert_eq!(options.throttle, 0);
+        assert!(!options.edit);
+        assert!(!options.delete_on_stop);
+    }
+
+    #[test]
+    fn test_options_full() {
+        let yaml = r"
+            channel_id: 01J0000000000000000000000
+            api_url: https://stoat.example.com/api
+            session_token: meow
+            template: '%NAME% - %ARTIST%'
+            embed: false
+            throttle: 30
+            edit: true
+            delete_on_stop: true
+        ";
+
+        let options: Options = serde_yaml::from_str(yaml).unwrap();
+
+        assert_eq!(options.api_url, "https://stoat.example.com/api");
+        assert_eq!(options.template, "%NAME% - %ARTIST%");
+        assert!(!options.embed);
+        assert_eq!(options.throttle, 30);
+        assert!(options.edit);
+        assert!(options.delete_on_stop);
+    }
+}
@@ -0,0 +1,43 @@
+use std::path::PathBuf;
+
+/// The local Discord client the listening activity is shown on, as Rich
+/// Presence.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct Options {
+    /// ID of the Discord application the activity is shown as, whose name
+    /// appears after "Listening to".
+    pub client_id: String,
+    /// Path of the IPC socket. Found in the runtime and temporary
+    /// directories by default.
+    #[serde(default)]
+    pub socket: Option<PathBuf>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options_minimal() {
+        let options: Options = serde_yaml::from_str(r#"client_id: "1234""#).unwrap();
+
+        assert_eq!(options.client_id, "1234");
+        assert_eq!(options.socket, None);
+    }
+
+    #[test]
+    fn test_options_full() {
+        let yaml = r#"
+            client_id: "1234"
+            socket: /run/user/1000/discord-ipc-0
+        "#;
+
+        let options: Options = serde_yaml::from_str(yaml).unwrap();
+
+        assert_eq!(options.client_id, "1234");
+        assert_eq!(
+            options.socket,
+            Some(PathBuf::from("/run/user/1000/discord-ipc-0"))
+        );
+    }
+}
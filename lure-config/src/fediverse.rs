@@ -0,0 +1,104 @@
+use secrecy::{ExposeSecret as _, SecretString};
+
+/// A Fediverse account, on Mastodon or a server implementing its API like
+/// GoToSocial, with a profile field kept up to date with the current track.
+///
+/// Misskey isn't supported, since its profile is edited through its own
+/// API.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Options {
+    /// Base URL of the instance.
+    pub instance: String,
+    /// Access token of the account, with the `read:accounts` and
+    /// `write:accounts` scopes. Only optional in dry run.
+    #[serde(default)]
+    pub access_token: Option<SecretString>,
+    /// Name of the profile field.
+    #[serde(default = "default_field")]
+    pub field: String,
+    #[serde(default = "default_template")]
+    pub template: String,
+    /// Value of the field when nothing is playing. If not set, the field is
+    /// set back to its previous value, or removed if it didn't exist.
+    #[serde(default)]
+    pub idle: Option<String>,
+    /// Minimum number of seconds between two updates, since every update
+    /// is sent to the followers of the account.
+    #[serde(default = "default_min_interval")]
+    pub min_interval: u64,
+}
+
+impl PartialEq for Options {
+    fn eq(&self, other: &Self) -> bool {
+        self.instance == other.instance
+            && self
+                .access_token
+                .as_ref()
+                .map(|access_token| access_token.expose_secret())
+                == other
+                    .access_token
+                    .as_ref()
+                    .map(|access_token| access_token.expose_secret())
+            && self.field == other.field
+            && self.template == other.template
+            && self.idle == other.idle
+            && self.min_interval == other.min_interval
+    }
+}
+
+impl Eq for Options {}
+
+fn default_field() -> String {
+    String::from("Now playing")
+}
+
+fn default_template() -> String {
+    String::from("%NAME% by %ARTIST%")
+}
+
+const fn default_min_interval() -> u64 {
+    60
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options_minimal() {
+        let options: Options = serde_yaml::from_str("instance: https://example.social").unwrap();
+
+        assert_eq!(options.instance, "https://example.social");
+        assert!(options.access_token.is_none());
+        assert_eq!(options.field, "Now playing");
+        assert_eq!(options.template, "%NAME% by %ARTIST%");
+        assert_eq!(options.idle, None);
+        assert_eq!(options.min_interval, 60);
+    }
+
+    #[test]
+    fn test_options_full() {
+        let yaml = r"
+            instance: https://example.social
+            access_token: meow
+            field: Listening to
+            template: '%NAME% - %ARTIST%'
+            idle: Nothing
+            min_interval: 300
+        ";
+
+        let options: Options = serde_yaml::from_str(yaml).unwrap();
+
+        assert_eq!(
+            options
+                .access_token
+                .as_ref()
+                .map(|access_token| access_token.expose_secret()),
+            Some("meow")
+        );
+        assert_eq!(options.field, "Listening to");
+        assert_eq!(options.template, "%NAME% - %ARTIST%");
+        assert_eq!(options.idle.as_deref(), Some("Nothing"));
+        assert_eq!(options.min_interval, 300);
+    }
+}
@@ -0,0 +1,62 @@
+use std::path::PathBuf;
+
+/// A file kept up to date with the current track, for stream overlays.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct Options {
+    pub path: PathBuf,
+    #[serde(default)]
+    pub format: Format,
+    /// Template of the text written while playing.
+    #[serde(default = "default_file_template")]
+    pub template: String,
+    /// Text written when nothing is playing. If not set, the file is
+    /// cleared.
+    #[serde(default)]
+    pub idle: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Format {
+    /// The rendered template, for text sources.
+    #[default]
+    Text,
+    /// The track and the rendered template as JSON, for browser sources.
+    Json,
+}
+
+fn default_file_template() -> String {
+    "%NAME% by %ARTIST%".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options_minimal() {
+        let options: Options = serde_yaml::from_str("path: now-playing.txt").unwrap();
+
+        assert_eq!(options.path, PathBuf::from("now-playing.txt"));
+        assert_eq!(options.format, Format::Text);
+        assert_eq!(options.template, "%NAME% by %ARTIST%");
+        assert_eq!(options.idle, None);
+    }
+
+    #[test]
+    fn test_options_full() {
+        let yaml = r"
+            path: /tmp/now-playing.json
+            format: json
+            template: '%ARTIST% - %NAME%'
+            idle: Nothing playing
+        ";
+
+        let options: Options = serde_yaml::from_str(yaml).unwrap();
+
+        assert_eq!(options.path, PathBuf::from("/tmp/now-playing.json"));
+        assert_eq!(options.format, Format::Json);
+        assert_eq!(options.template, "%ARTIST% - %NAME%");
+        assert_eq!(options.idle.as_deref(), Some("Nothing playing"));
+    }
+}
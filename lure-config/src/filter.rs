@@ -0,0 +1,257 @@
+use lure_types::TrackInfo;
+use regex::Regex;
+
+pub use regex::Error;
+
+/// A privacy filter, hiding or masking the tracks it matches.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct Filter {
+    /// The field of the track that is matched.
+    pub field: Field,
+    /// How `pattern` is matched against the field.
+    #[serde(default = "default_match", rename = "match")]
+    pub kind: Match,
+    pub pattern: String,
+    /// The status template to show instead. If not set, matching tracks
+    /// are treated as if nothing was playing.
+    #[serde(default)]
+    pub replacement: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Field {
+    Artist,
+    Title,
+    Album,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Match {
+    /// The field is equal to the pattern.
+    Exact,
+    /// The field is equal to the pattern, ignoring the case.
+    IgnoreCase,
+    /// The field contains a match of the pattern as a regular expression.
+    Regex,
+}
+
+/// Privacy filters, ready to be matched against tracks.
+#[derive(Debug, Clone, Default)]
+pub struct Filters(Vec<(Filter, Option<Regex>)>);
+
+impl Filter {
+    /// Compiles the pattern if it's a regular expression.
+    pub fn regex(&self) -> Result<Option<Regex>, Error> {
+        match self.kind {
+            Match::Regex => Regex::new(&self.pattern).map(Some),
+            Match::Exact | Match::IgnoreCase => Ok(None),
+        }
+    }
+
+    fn matches(&self, regex: Option<&Regex>, track: &TrackInfo) -> bool {
+        match self.field {
+            Field::Artist => self.matches_value(regex, &track.artist),
+            Field::Title => self.matches_value(regex, &track.title),
+            Field::Album => track
+                .album
+                .as_deref()
+                .is_some_and(|album| self.matches_value(regex, album)),
+        }
+    }
+
+    fn matches_value(&self, regex: Option<&Regex>, value: &str) -> bool {
+        match (self.kind, regex) {
+            (Match::Exact, _) => value == self.pattern,
+            (Match::IgnoreCase, _) => value.to_lowercase() == self.pattern.to_lowercase(),
+            (Match::Regex, Some(regex)) => regex.is_match(value),
+            (Match::Regex, None) => false,
+        }
+    }
+}
+
+impl Filters {
+    pub fn new(filters: &[Filter]) -> Result<Self, Error> {
+        filters
+            .iter()
+            .map(|filter| Ok((filter.clone(), filter.regex()?)))
+            .collect::<Result<_, _>>()
+            .map(Self)
+    }
+
+    /// Returns the first filter matching `track`, if any.
+    pub fn find(&self, track: &TrackInfo) -> Option<&Filter> {
+        self.0
+            .iter()
+            .find(|(filter, regex)| filter.matches(regex.as_ref(), track))
+            .map(|(filter, _)| filter)
+    }
+
+    /// Returns the first filter on the artist matching `artist`, if any, for
+    /// when only the artist is known.
+    pub fn find_artist(&self, artist: &str) -> Option<&Filter> {
+        self.0
+            .iter()
+            .find(|(filter, regex)| {
+                filter.field == Field::Artist && filter.matches_value(regex.as_ref(), artist)
+            })
+            .map(|(filter, _)| filter)
+    }
+}
+
+const fn default_match() -> Match {
+    Match::Exact
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track() -> TrackInfo {
+        TrackInfo {
+            artist: "Rain Sounds".to_string(),
+            title: "Heavy Rain".to_string(),
+            album: Some("Sleep".to_string()),
+            cover_url: None,
+            url: None,
+        }
+    }
+
+    fn filters(yaml: &str) -> Filters {
+        let filters: Vec<Filter> = serde_yaml::from_str(yaml).unwrap();
+
+        Filters::new(&filters).unwrap()
+    }
+
+    #[test]
+    fn test_options() {
+        let yaml = r"
+            - field: artist
+              pattern: Rain Sounds
+            - field: album
+              match: regex
+              pattern: ^Sleep
+              replacement: 🎵 Listening to something
+        ";
+
+        let filters: Vec<Filter> = serde_yaml::from_str(yaml).unwrap();
+
+        assert_eq!(filters[0].field, Field::Artist);
+        assert_eq!(filters[0].kind, Match::Exact);
+        assert_eq!(filters[0].replacement, None);
+        assert_eq!(filters[1].field, Field::Album);
+        assert_eq!(filters[1].kind, Match::Regex);
+        assert_eq!(
+            filters[1].replacement.as_deref(),
+            Some("🎵 Listening to something")
+        );
+    }
+
+    #[test]
+    fn test_exact() {
+        assert!(
+            filters("[{ field: artist, pattern: Rain Sounds }]")
+                .find(&track())
+                .is_some()
+        );
+        assert!(
+            filters("[{ field: artist, pattern: rain sounds }]")
+                .find(&track())
+                .is_none()
+        );
+        assert!(
+            filters("[{ field: title, pattern: Heavy }]")
+                .find(&track())
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_ignore_case() {
+        assert!(
+            filters("[{ field: title, match: ignore_case, pattern: HEAVY rain }]")
+                .find(&track())
+                .is_some()
+        );
+        assert!(
+            filters("[{ field: title, match: ignore_case, pattern: heavy }]")
+                .find(&track())
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_regex() {
+        assert!(
+            filters("[{ field: album, match: regex, pattern: '^Sl(ee|u)p$' }]")
+                .find(&track())
+                .is_some()
+        );
+        assert!(
+            filters("[{ field: artist, match: regex, pattern: '(?i)SOUNDS' }]")
+                .find(&track())
+                .is_some()
+        );
+        assert!(
+            filters("[{ field: artist, match: regex, pattern: '^Sounds' }]")
+                .find(&track())
+                .is_none()
+        );
+
+        let filters: Vec<Filter> =
+            serde_yaml::from_str("[{ field: artist, match: regex, pattern: '(' }]").unwrap();
+        assert!(Filters::new(&filters).is_err());
+    }
+
+    #[test]
+    fn test_unknown_album() {
+        let track = TrackInfo {
+            album: None,
+            ..track()
+        };
+
+        assert!(
+            filters("[{ field: album, match: regex, pattern: '' }]")
+                .find(&track)
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_find_artist() {
+        assert!(
+            filters("[{ field: artist, match: ignore_case, pattern: rain sounds }]")
+                .find_artist("Rain Sounds")
+                .is_some()
+        );
+        assert!(
+            filters("[{ field: title, match: regex, pattern: '' }]")
+                .find_artist("Rain Sounds")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn test_first_match_wins() {
+        let filters = filters(
+            r"
+            - field: title
+              pattern: Quiet Rain
+              replacement: first
+            - field: artist
+              pattern: Rain Sounds
+              replacement: second
+            - field: album
+              pattern: Sleep
+            ",
+        );
+
+        assert_eq!(
+            filters
+                .find(&track())
+                .and_then(|filter| filter.replacement.as_deref()),
+            Some("second")
+        );
+    }
+}
@@ -0,0 +1,75 @@
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct Options {
+    /// Record every track played in the local listening history.
+    #[serde(default = "default_enable")]
+    pub enable: bool,
+    /// Path to the history database. Defaults to `history.sqlite3` in the
+    /// state directory.
+    #[serde(default)]
+    pub path: Option<PathBuf>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            enable: default_enable(),
+            path: None,
+        }
+    }
+}
+
+impl Options {
+    /// Returns the configured path, or the default one in the state
+    /// directory: `$XDG_STATE_HOME/lure`, falling back to
+    /// `$HOME/.local/state/lure`, and then to the working directory.
+    pub fn path(&self) -> PathBuf {
+        self.path.clone().unwrap_or_else(|| {
+            std::env::var_os("XDG_STATE_HOME")
+                .map(PathBuf::from)
+                .or_else(|| {
+                    std::env::var_os("HOME")
+                        .map(|home| PathBuf::from(home).join(".local").join("state"))
+                })
+                .map_or_else(PathBuf::new, |state| state.join("lure"))
+                .join("history.sqlite3")
+        })
+    }
+}
+
+const fn default_enable() -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options_minimal() {
+        let options: Options = serde_yaml::from_str("enable: true").unwrap();
+
+        assert!(options.enable);
+
+        assert_eq!(options.path, None);
+        assert!(options.path().ends_with("history.sqlite3"));
+    }
+
+    #[test]
+    fn test_options_full() {
+        let yaml = r"
+            enable: true
+            path: /var/lib/lure/history.sqlite3
+        ";
+
+        let options: Options = serde_yaml::from_str(yaml).unwrap();
+
+        assert!(options.enable);
+
+        assert_eq!(
+            options.path(),
+            PathBuf::from("/var/lib/lure/history.sqlite3")
+        );
+    }
+}
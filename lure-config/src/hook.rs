@@ -0,0 +1,88 @@
+/// An external command run on the playback events.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct Options {
+    /// The program followed by its arguments. It's run directly, not
+    /// through a shell.
+    pub command: Vec<String>,
+    /// Events the command is run on. Every event by default.
+    #[serde(default = "default_events")]
+    pub events: Vec<Event>,
+    /// How many seconds a run can take before it's killed.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    /// How many runs can happen at once. Events happening while the limit
+    /// is reached wait for a run to finish, in order.
+    #[serde(default = "default_max_concurrent")]
+    pub max_concurrent: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Event {
+    /// A track started playing after nothing was.
+    Started,
+    /// Another track started playing.
+    Changed,
+    /// Nothing is playing anymore.
+    Stopped,
+    /// Polling the listening service started failing.
+    Error,
+}
+
+impl Options {
+    /// The program of the command, used in logs.
+    pub fn program(&self) -> &str {
+        self.command.first().map_or("", String::as_str)
+    }
+}
+
+fn default_events() -> Vec<Event> {
+    vec![Event::Started, Event::Changed, Event::Stopped, Event::Error]
+}
+
+const fn default_timeout() -> u64 {
+    10
+}
+
+const fn default_max_concurrent() -> usize {
+    1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options_minimal() {
+        let options: Options = serde_yaml::from_str("command: [notify-send, lure]").unwrap();
+
+        assert_eq!(options.command, ["notify-send", "lure"]);
+        assert_eq!(options.program(), "notify-send");
+        assert_eq!(
+            options.events,
+            [Event::Started, Event::Changed, Event::Stopped, Event::Error]
+        );
+        assert_eq!(options.timeout, 10);
+        assert_eq!(options.max_concurrent, 1);
+    }
+
+    #[test]
+    fn test_options_full() {
+        let yaml = r#"
+            command: [sh, -c, 'tmux set -g status-right "$LURE_TITLE"']
+            events: [started, changed]
+            timeout: 2
+            max_concurrent: 4
+        "#;
+
+        let options: Options = serde_yaml::from_str(yaml).unwrap();
+
+        assert_eq!(
+            options.command,
+            ["sh", "-c", r#"tmux set -g status-right "$LURE_TITLE""#]
+        );
+        assert_eq!(options.events, [Event::Started, Event::Changed]);
+        assert_eq!(options.timeout, 2);
+        assert_eq!(options.max_concurrent, 4);
+    }
+}
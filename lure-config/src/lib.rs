@@ -1,13 +1,146 @@
+pub mod bot;
+pub mod channel;
+pub mod discord;
+pub mod fediverse;
+pub mod file;
+pub mod filter;
+pub mod history;
+pub mod hook;
+pub mod loader;
+pub mod matrix;
+pub mod mqtt;
+pub mod profile;
+pub mod schedule;
+pub mod server;
 pub mod stoat;
+pub mod validate;
+pub mod webhook;
 
-#[derive(Debug, serde::Deserialize)]
+pub use loader::load;
+pub use validate::{Diagnostic, Diagnostics};
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
 pub struct Config {
-    pub service: ServiceOptions,
-    pub stoat: stoat::Options,
+    /// Service of the default profile.
+    #[serde(default)]
+    pub service: Option<ServiceOptions>,
+    /// Stoat accounts of the default profile.
+    #[serde(default)]
+    pub stoat: Option<stoat::Targets>,
+    /// Stoat channels of the default profile.
+    #[serde(default)]
+    pub channels: Vec<channel::Options>,
+    /// Now-playing files of the default profile.
+    #[serde(default)]
+    pub files: Vec<file::Options>,
+    /// Webhooks of the default profile.
+    #[serde(default)]
+    pub webhooks: Vec<webhook::Options>,
+    /// Hooks of the default profile.
+    #[serde(default)]
+    pub hooks: Vec<hook::Options>,
+    /// MQTT broker of the default profile.
+    #[serde(default)]
+    pub mqtt: Option<mqtt::Options>,
+    /// Discord client of the default profile.
+    #[serde(default)]
+    pub discord: Option<discord::Options>,
+    /// Matrix account of the default profile.
+    #[serde(default)]
+    pub matrix: Option<matrix::Options>,
+    /// Fediverse account of the default profile.
+    #[serde(default)]
+    pub fediverse: Option<fediverse::Options>,
+    /// Privacy filters of the default profile.
+    #[serde(default)]
+    pub filters: Vec<filter::Filter>,
+    /// Schedule of the default profile.
+    #[serde(default)]
+    pub schedule: schedule::Schedule,
+    /// Additional profiles, for serving more than one person from one
+    /// process.
+    #[serde(default)]
+    pub profiles: Vec<profile::Profile>,
+    #[serde(default)]
+    pub server: server::Options,
+    #[serde(default)]
+    pub history: history::Options,
+    /// Stoat bot answering what the profiles are listening to.
+    #[serde(default)]
+    pub bot: Option<bot::Options>,
+    /// Log the statuses of every profile instead of setting them.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+impl Config {
+    /// Returns every profile, starting with the default profile made of the
+    /// top-level `service` and `stoat` options, if they're set. Every
+    /// profile is in dry run if the configuration is.
+    pub fn profiles(&self) -> Vec<profile::Profile> {
+        let default_profile = match (&self.service, &self.stoat) {
+            (Some(service), Some(stoat)) => Some(profile::Profile {
+                name: profile::DEFAULT_PROFILE_NAME.to_string(),
+                service: service.clone(),
+                stoat: stoat.clone(),
+                channels: self.channels.clone(),
+                files: self.files.clone(),
+                webhooks: self.webhooks.clone(),
+                hooks: self.hooks.clone(),
+                mqtt: self.mqtt.clone(),
+                discord: self.discord.clone(),
+                matrix: self.matrix.clone(),
+                fediverse: self.fediverse.clone(),
+                filters: self.filters.clone(),
+                schedule: self.schedule.clone(),
+                dry_run: false,
+            }),
+            _ => None,
+        };
+
+        default_profile
+            .into_iter()
+            .chain(self.profiles.iter().cloned())
+            .map(|mut profile| {
+                profile.dry_run |= self.dry_run;
+                profile
+            })
+            .collect()
+    }
 }
 
-#[derive(Debug, Default, serde::Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize)]
 pub struct ServiceOptions {
     pub lastfm: Option<lure_lastfm_service::config::Options>,
     pub listenbrainz: Option<lure_listenbrainz_service::config::Options>,
+    pub exec: Option<lure_exec_service::config::Options>,
+}
+
+impl ServiceOptions {
+    /// Name of the enabled service, if any.
+    pub const fn name(&self) -> Option<&'static str> {
+        match self {
+            Self {
+                lastfm: Some(_), ..
+            } => Some("lastfm"),
+            Self {
+                listenbrainz: Some(_),
+                ..
+            } => Some("listenbrainz"),
+            Self { exec: Some(_), .. } => Some("exec"),
+            _ => None,
+        }
+    }
+
+    /// Names of every enabled service.
+    pub fn enabled(&self) -> Vec<&'static str> {
+        [
+            self.lastfm.as_ref().map(|_| "lastfm"),
+            self.listenbrainz.as_ref().map(|_| "listenbrainz"),
+            self.exec.as_ref().map(|_| "exec"),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
 }
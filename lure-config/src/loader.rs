@@ -0,0 +1,147 @@
+use std::path::Path;
+
+use figment::{
+    Figment, Metadata, Profile, Provider,
+    error::Kind,
+    providers::{Env, Format as _, Yaml},
+    value::{Dict, Map, Value},
+};
+use figment_file_provider_adapter::FileAdapter;
+
+use crate::{
+    Config,
+    validate::{Diagnostic, Diagnostics},
+};
+
+/// Keys that can also be read from a file by adding a `_file` suffix.
+pub const SECURE_CONFIG_KEYS: &[&str; 6] = &[
+    "session_token",
+    "bot_token",
+    "api_key",
+    "secret",
+    "password",
+    "access_token",
+];
+
+/// Builds the figment that merges the configuration file and the
+/// environment variables, each followed by their `_file` suffixed secure
+/// keys.
+pub fn figment(config_path: &Path) -> Figment {
+    let config_path = config_path.to_path_buf();
+    let yaml = move || Yaml::file(&config_path);
+    let env = || Env::prefixed("LURE_").split("__");
+
+    Figment::new()
+        .merge(yaml())
+        .merge(SecureFile(yaml))
+        .merge(env())
+        .merge(SecureFile(env))
+}
+
+/// Loads and validates the configuration from the file at `config_path` and
+/// the environment variables.
+pub fn load(config_path: &Path) -> Result<Config, Diagnostics> {
+    extract(&figment(config_path))
+}
+
+/// Extracts and validates the configuration, returning every problem
+/// found along with where the problematic value came from.
+pub fn extract(figment: &Figment) -> Result<Config, Diagnostics> {
+    let config: Config = figment.extract().map_err(|error| {
+        Diagnostics(
+            error
+                .into_iter()
+                .map(|error| {
+                    let mut path = error.path.clone();
+                    let source = if let Kind::MissingField(field) = &error.kind {
+                        path.push(field.to_string());
+                        None
+                    } else {
+                        error.metadata.as_ref().map(describe_source)
+                    };
+
+                    Diagnostic {
+                        path: path.join("."),
+                        message: error.kind.to_string(),
+                        source,
+                    }
+                })
+                .collect(),
+        )
+    })?;
+
+    let diagnostics: Vec<_> = config
+        .validate()
+        .into_iter()
+        .map(|mut diagnostic| {
+            diagnostic.source = figment.find_metadata(&diagnostic.path).map(describe_source);
+            diagnostic
+        })
+        .collect();
+
+    if diagnostics.is_empty() {
+        Ok(config)
+    } else {
+        Err(Diagnostics(diagnostics))
+    }
+}
+
+fn describe_source(metadata: &Metadata) -> String {
+    metadata.source.as_ref().map_or_else(
+        || metadata.name.to_string(),
+        |source| format!("{} `{source}`", metadata.name),
+    )
+}
+
+/// Reads the [`SECURE_CONFIG_KEYS`] from the files pointed by their `_file`
+/// suffixed keys, and provides only those, so that diagnostics can tell them
+/// apart from the values set directly.
+///
+/// Wraps a function that creates the underlying provider, since it's needed
+/// twice: once as is, and once wrapped in a [`FileAdapter`].
+struct SecureFile<F>(F);
+
+impl<F, P> Provider for SecureFile<F>
+where
+    F: Fn() -> P,
+    P: Provider + 'static,
+{
+    fn metadata(&self) -> Metadata {
+        let mut metadata = self.0().metadata();
+        metadata.name = format!("{} (`_file` suffixed)", metadata.name).into();
+
+        metadata
+    }
+
+    fn data(&self) -> Result<Map<Profile, Dict>, figment::Error> {
+        // Errors are already reported by the unwrapped provider.
+        let Ok(raw) = self.0().data() else {
+            return Ok(Map::new());
+        };
+
+        Ok(FileAdapter::wrap(self.0())
+            .only(SECURE_CONFIG_KEYS)
+            .data()?
+            .into_iter()
+            .map(|(profile, dict)| {
+                let dict = raw
+                    .get(&profile)
+                    .map_or_else(Dict::new, |raw| only_file_keys(dict, raw));
+
+                (profile, dict)
+            })
+            .collect())
+    }
+}
+
+fn only_file_keys(dict: Dict, raw: &Dict) -> Dict {
+    dict.into_iter()
+        .filter_map(|(key, value)| match (value, raw.get(&key)) {
+            (Value::Dict(tag, dict), Some(Value::Dict(_, raw))) => {
+                Some((key, Value::Dict(tag, only_file_keys(dict, raw))))
+            }
+            (value, _) if raw.contains_key(&format!("{key}_file")) => Some((key, value)),
+            _ => None,
+        })
+        .collect()
+}
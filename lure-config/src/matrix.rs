@@ -0,0 +1,94 @@
+use secrecy::{ExposeSecret as _, SecretString};
+
+/// A Matrix account whose presence status message is kept in sync with the
+/// listening activity.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Options {
+    /// Base URL of the homeserver's client-server API.
+    pub homeserver: String,
+    /// Access token of the account. Only optional in dry run.
+    #[serde(default)]
+    pub access_token: Option<SecretString>,
+    #[serde(default)]
+    pub status: StatusOptions,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct StatusOptions {
+    #[serde(default = "default_status_template")]
+    pub template: String,
+    #[serde(default)]
+    pub idle: Option<String>,
+}
+
+impl Default for StatusOptions {
+    fn default() -> Self {
+        Self {
+            template: default_status_template(),
+            idle: None,
+        }
+    }
+}
+
+impl PartialEq for Options {
+    fn eq(&self, other: &Self) -> bool {
+        self.homeserver == other.homeserver
+            && self
+                .access_token
+                .as_ref()
+                .map(|access_token| access_token.expose_secret())
+                == other
+                    .access_token
+                    .as_ref()
+                    .map(|access_token| access_token.expose_secret())
+            && self.status == other.status
+    }
+}
+
+impl Eq for Options {}
+
+fn default_status_template() -> String {
+    String::from("🎵 Listening to %NAME% by %ARTIST%")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options_minimal() {
+        let options: Options =
+            serde_yaml::from_str("homeserver: https://matrix.example.com").unwrap();
+
+        assert_eq!(options.homeserver, "https://matrix.example.com");
+        assert!(options.access_token.is_none());
+        assert_eq!(
+            options.status.template,
+            "🎵 Listening to %NAME% by %ARTIST%"
+        );
+        assert_eq!(options.status.idle, None);
+    }
+
+    #[test]
+    fn test_options_full() {
+        let yaml = r"
+            homeserver: https://matrix.example.com
+            access_token: meow
+            status:
+                template: '%NAME% // %ARTIST%'
+                idle: Sleeping
+        ";
+
+        let options: Options = serde_yaml::from_str(yaml).unwrap();
+
+        assert_eq!(
+            options
+                .access_token
+                .as_ref()
+                .map(|access_token| access_token.expose_secret()),
+            Some("meow")
+        );
+        assert_eq!(options.status.template, "%NAME% // %ARTIST%");
+        assert_eq!(options.status.idle.as_deref(), Some("Sleeping"));
+    }
+}
@@ -0,0 +1,149 @@
+use secrecy::{ExposeSecret as _, SecretString};
+
+/// An MQTT broker the current track and the playback state are published
+/// to.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Options {
+    pub host: String,
+    #[serde(default = "default_port")]
+    pub port: u16,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<SecretString>,
+    /// Client ID of the connection. Defaults to `lure-<profile>-<hostname>`,
+    /// so that lure running on several machines doesn't kick itself off the
+    /// broker.
+    #[serde(default)]
+    pub client_id: Option<String>,
+    /// Prefix of the topics. Defaults to `lure/<profile>`.
+    #[serde(default)]
+    pub topic: Option<String>,
+    #[serde(default)]
+    pub discovery: DiscoveryOptions,
+}
+
+/// Home Assistant MQTT discovery.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct DiscoveryOptions {
+    /// Announce a "now playing" sensor to Home Assistant.
+    #[serde(default)]
+    pub enable: bool,
+    /// The discovery prefix configured in Home Assistant.
+    #[serde(default = "default_discovery_prefix")]
+    pub prefix: String,
+}
+
+impl Options {
+    /// Returns the client ID, or the default one for `profile` on this
+    /// machine.
+    pub fn client_id(&self, profile: &str) -> String {
+        self.client_id.clone().unwrap_or_else(|| {
+            format!(
+                "lure-{profile}-{}",
+                gethostname::gethostname().to_string_lossy()
+            )
+        })
+    }
+
+    /// Returns the prefix of the topics, or the default one for `profile`.
+    pub fn topic(&self, profile: &str) -> String {
+        self.topic
+            .clone()
+            .unwrap_or_else(|| format!("lure/{profile}"))
+    }
+}
+
+impl Default for DiscoveryOptions {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            prefix: default_discovery_prefix(),
+        }
+    }
+}
+
+impl PartialEq for Options {
+    fn eq(&self, other: &Self) -> bool {
+        self.host == other.host
+            && self.port == other.port
+            && self.username == other.username
+            && self
+                .password
+                .as_ref()
+                .map(|password| password.expose_secret())
+                == other
+                    .password
+                    .as_ref()
+                    .map(|password| password.expose_secret())
+            && self.client_id == other.client_id
+            && self.topic == other.topic
+            && self.discovery == other.discovery
+    }
+}
+
+impl Eq for Options {}
+
+const fn default_port() -> u16 {
+    1883
+}
+
+fn default_discovery_prefix() -> String {
+    "homeassistant".to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options_minimal() {
+        let options: Options = serde_yaml::from_str("host: localhost").unwrap();
+
+        assert_eq!(options.host, "localhost");
+        assert_eq!(options.port, 1883);
+        assert_eq!(options.username, None);
+        assert!(options.password.is_none());
+        assert_eq!(
+            options.client_id("kitty"),
+            format!(
+                "lure-kitty-{}",
+                gethostname::gethostname().to_string_lossy()
+            )
+        );
+        assert_eq!(options.topic("kitty"), "lure/kitty");
+        assert!(!options.discovery.enable);
+        assert_eq!(options.discovery.prefix, "homeassistant");
+    }
+
+    #[test]
+    fn test_options_full() {
+        let yaml = r"
+            host: mqtt.example.com
+            port: 1884
+            username: kitty
+            password: meow
+            client_id: lure
+            topic: home/music
+            discovery:
+                enable: true
+                prefix: ha
+        ";
+
+        let options: Options = serde_yaml::from_str(yaml).unwrap();
+
+        assert_eq!(options.port, 1884);
+        assert_eq!(options.username.as_deref(), Some("kitty"));
+        assert_eq!(
+            options
+                .password
+                .as_ref()
+                .map(|password| password.expose_secret()),
+            Some("meow")
+        );
+        assert_eq!(options.client_id("kitty"), "lure");
+        assert_eq!(options.topic("kitty"), "home/music");
+        assert!(options.discovery.enable);
+        assert_eq!(options.discovery.prefix, "ha");
+    }
+}
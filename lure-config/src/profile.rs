@@ -0,0 +1,156 @@
+use crate::{
+    ServiceOptions, channel, discord, fediverse, file, filter, hook, matrix, mqtt, schedule, stoat,
+    webhook,
+};
+
+/// Name of the profile made of the top-level `service` and `stoat` options.
+pub const DEFAULT_PROFILE_NAME: &str = "default";
+
+/// A listening service paired with the Stoat accounts it's mirrored to.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct Profile {
+    /// Name of the profile, used in logs, metrics and the state endpoint.
+    pub name: String,
+    pub service: ServiceOptions,
+    pub stoat: stoat::Targets,
+    /// Stoat channels a message is posted to for every track.
+    #[serde(default)]
+    pub channels: Vec<channel::Options>,
+    /// Files kept up to date with the current track.
+    #[serde(default)]
+    pub files: Vec<file::Options>,
+    /// URLs the playback events are posted to.
+    #[serde(default)]
+    pub webhooks: Vec<webhook::Options>,
+    /// Commands run on the playback events.
+    #[serde(default)]
+    pub hooks: Vec<hook::Options>,
+    /// MQTT broker the current track is published to.
+    #[serde(default)]
+    pub mqtt: Option<mqtt::Options>,
+    /// Discord client the current track is shown on.
+    #[serde(default)]
+    pub discord: Option<discord::Options>,
+    /// Matrix account whose presence status message is kept in sync.
+    #[serde(default)]
+    pub matrix: Option<matrix::Options>,
+    /// Fediverse account with a profile field kept up to date.
+    #[serde(default)]
+    pub fediverse: Option<fediverse::Options>,
+    /// Privacy filters, applied in order. The first matching filter wins.
+    #[serde(default)]
+    pub filters: Vec<filter::Filter>,
+    /// When the profile is active. Always active by default.
+    #[serde(default)]
+    pub schedule: schedule::Schedule,
+    /// Log the statuses instead of setting them.
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+impl Profile {
+    /// Returns every template of the profile, idle ones included.
+    pub fn templates(&self) -> impl Iterator<Item = &str> {
+        let stoat = self.stoat.0.iter().flat_map(|options| {
+            std::iter::once(options.status.template.as_str()).chain(options.status.idle.as_deref())
+        });
+        let channels = self
+            .channels
+            .iter()
+            .map(|options| options.template.as_str());
+        let files = self.files.iter().flat_map(|options| {
+            std::iter::once(options.template.as_str()).chain(options.idle.as_deref())
+        });
+
+        let matrix = self.matrix.iter().flat_map(|options| {
+            std::iter::once(options.status.template.as_str()).chain(options.status.idle.as_deref())
+        });
+
+        let fediverse = self.fediverse.iter().flat_map(|options| {
+            std::iter::once(options.template.as_str()).chain(options.idle.as_deref())
+        });
+
+        stoat
+            .chain(channels)
+            .chain(files)
+            .chain(matrix)
+            .chain(fediverse)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_profile() {
+        let yaml = r"
+            name: kitty
+            service:
+                listenbrainz:
+                    username: kitty
+            stoat:
+                session_token: meow
+        ";
+
+        let profile: Profile = serde_yaml::from_str(yaml).unwrap();
+
+        assert_eq!(profile.name, "kitty");
+        assert_eq!(
+            profile.service.listenbrainz.map(|options| options.username),
+            Some("kitty".to_string())
+        );
+        assert_eq!(profile.stoat.0.len(), 1);
+        assert!(profile.files.is_empty());
+        assert!(profile.filters.is_empty());
+    }
+
+    /// The top-level options are copied into the default profile, so they
+    /// must read the same as the options of a profile.
+    #[test]
+    fn test_default_profile_matches_profile() {
+        let options = r"
+            service:
+                listenbrainz:
+                    username: kitty
+            stoat:
+                session_token: meow
+            channels:
+                - channel_id: 01J0000000000000000000000
+                  session_token: meow
+            files:
+                - path: now-playing.txt
+            webhooks:
+                - url: https://example.com/hook
+            hooks:
+                - command: [notify-send]
+            mqtt:
+                host: localhost
+            discord:
+                client_id: '1234'
+            matrix:
+                homeserver: https://matrix.example.com
+            fediverse:
+                instance: https://example.social
+            filters:
+                - field: artist
+                  match: exact
+                  pattern: Kitty
+            schedule:
+                outside: idle
+        ";
+
+        let config: crate::Config = serde_yaml::from_str(options).unwrap();
+        let mut profile: serde_yaml::Mapping = serde_yaml::from_str(options).unwrap();
+        profile.insert("name".into(), DEFAULT_PROFILE_NAME.into());
+        let profile: Profile = serde_yaml::from_value(profile.into()).unwrap();
+
+        assert_eq!(config.profiles(), [profile]);
+    }
+
+    #[test]
+    #[should_panic(expected = "missing field `name`")]
+    fn test_missing_name() {
+        let _: Profile = serde_yaml::from_str("service: {}").unwrap();
+    }
+}
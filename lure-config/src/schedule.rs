@@ -0,0 +1,260 @@
+use jiff::civil::{Date, Time, Weekday};
+use jiff::tz::TimeZone;
+use jiff::{ToSpan as _, Zoned};
+
+/// When a profile is active. Outside the windows, the statuses aren't
+/// updated.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize)]
+pub struct Schedule {
+    /// Name of the IANA time zone of the windows, e.g. `Europe/Berlin`.
+    /// Defaults to the local time zone.
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// What the statuses show outside the windows.
+    #[serde(default)]
+    pub outside: Outside,
+    /// When the profile is active. If empty, it's always active.
+    #[serde(default)]
+    pub windows: Vec<Window>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outside {
+    /// The status the account had before lure started.
+    #[default]
+    Restore,
+    /// The idle status, or the status the account had before lure started
+    /// if no idle status is configured.
+    Idle,
+}
+
+/// A daily window. Windows ending at or before their start end on the next
+/// day, so `22:00` to `02:00` covers the night.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct Window {
+    /// The days the window starts on. Defaults to every day.
+    #[serde(default = "default_days")]
+    pub days: Vec<Day>,
+    pub from: Time,
+    pub to: Time,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Day {
+    #[serde(alias = "monday")]
+    Mon,
+    #[serde(alias = "tuesday")]
+    Tue,
+    #[serde(alias = "wednesday")]
+    Wed,
+    #[serde(alias = "thursday")]
+    Thu,
+    #[serde(alias = "friday")]
+    Fri,
+    #[serde(alias = "saturday")]
+    Sat,
+    #[serde(alias = "sunday")]
+    Sun,
+}
+
+impl Schedule {
+    /// Returns the time zone of the windows.
+    pub fn time_zone(&self) -> Result<TimeZone, jiff::Error> {
+        self.timezone
+            .as_deref()
+            .map_or_else(|| Ok(TimeZone::system()), TimeZone::get)
+    }
+
+    /// Whether the profile is active at `now`, which must be in the time
+    /// zone of the schedule.
+    pub fn is_active(&self, now: &Zoned) -> bool {
+        self.windows.is_empty()
+            || self
+                .windows
+                .iter()
+                .any(|window| window.contains(now.date(), now.time()))
+    }
+
+    /// Returns when the profile becomes active or inactive next, or `None`
+    /// if it never does.
+    pub fn next_change(&self, now: &Zoned) -> Option<Zoned> {
+        let active = self.is_active(now);
+        let today = now.date();
+
+        // A week and a day ahead covers every window, including the ones
+        // ending on the next day.
+        let mut boundaries: Vec<Zoned> = (0..=8)
+            .filter_map(|days| today.checked_add(days.days()).ok())
+            .flat_map(|date| {
+                self.windows.iter().flat_map(move |window| {
+                    [date.to_datetime(window.from), date.to_datetime(window.to)]
+                })
+            })
+            .filter_map(|datetime| datetime.to_zoned(now.time_zone().clone()).ok())
+            .filter(|boundary| boundary > now)
+            .collect();
+        boundaries.sort();
+
+        boundaries
+            .into_iter()
+            .find(|boundary| self.is_active(boundary) != active)
+    }
+}
+
+impl Window {
+    fn contains(&self, date: Date, time: Time) -> bool {
+        let starts_on = |date: Date| self.days.iter().any(|day| day.weekday() == date.weekday());
+
+        if self.from < self.to {
+            return starts_on(date) && self.from <= time && time < self.to;
+        }
+
+        (starts_on(date) && self.from <= time)
+            || (time < self.to && date.yesterday().is_ok_and(starts_on))
+    }
+}
+
+impl Day {
+    const fn weekday(self) -> Weekday {
+        match self {
+            Self::Mon => Weekday::Monday,
+            Self::Tue => Weekday::Tuesday,
+            Self::Wed => Weekday::Wednesday,
+            Self::Thu => Weekday::Thursday,
+            Self::Fri => Weekday::Friday,
+            Self::Sat => Weekday::Saturday,
+            Self::Sun => Weekday::Sunday,
+        }
+    }
+}
+
+fn default_days() -> Vec<Day> {
+    vec![
+        Day::Mon,
+        Day::Tue,
+        Day::Wed,
+        Day::Thu,
+        Day::Fri,
+        Day::Sat,
+        Day::Sun,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(yaml: &str) -> Schedule {
+        serde_yaml::from_str(yaml).unwrap()
+    }
+
+    fn at(datetime: &str) -> Zoned {
+        // 2026-10-19 is a Monday.
+        datetime
+            .parse::<jiff::civil::DateTime>()
+            .unwrap()
+            .to_zoned(TimeZone::get("Europe/Berlin").unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_options_minimal() {
+        let schedule = schedule("{}");
+
+        assert_eq!(schedule.timezone, None);
+        assert_eq!(schedule.outside, Outside::Restore);
+        assert!(schedule.windows.is_empty());
+        assert!(schedule.is_active(&at("2026-10-19T03:00")));
+        assert_eq!(schedule.next_change(&at("2026-10-19T03:00")), None);
+    }
+
+    #[test]
+    fn test_options_full() {
+        let schedule = schedule(
+            r"
+            timezone: Europe/Berlin
+            outside: idle
+            windows:
+                - days: [mon, tuesday]
+                  from: '18:00'
+                  to: '23:30'
+                - from: '08:00:30'
+                  to: '09:00'
+            ",
+        );
+
+        assert_eq!(schedule.timezone.as_deref(), Some("Europe/Berlin"));
+        assert_eq!(schedule.outside, Outside::Idle);
+        assert_eq!(schedule.windows[0].days, [Day::Mon, Day::Tue]);
+        assert_eq!(schedule.windows[0].from, Time::constant(18, 0, 0, 0));
+        assert_eq!(schedule.windows[1].days.len(), 7);
+        assert_eq!(schedule.windows[1].from, Time::constant(8, 0, 30, 0));
+        assert!(schedule.time_zone().is_ok());
+    }
+
+    #[test]
+    fn test_is_active() {
+        let schedule = schedule(
+            r"
+            windows:
+                - days: [mon, tue, wed, thu, fri]
+                  from: '18:00'
+                  to: '23:00'
+            ",
+        );
+
+        assert!(schedule.is_active(&at("2026-10-19T18:00")));
+        assert!(schedule.is_active(&at("2026-10-23T22:59")));
+        assert!(!schedule.is_active(&at("2026-10-19T23:00")));
+        assert!(!schedule.is_active(&at("2026-10-19T12:00")));
+        assert!(!schedule.is_active(&at("2026-10-24T19:00")));
+    }
+
+    #[test]
+    fn test_overnight_window() {
+        let schedule = schedule(
+            r"
+            windows:
+                - days: [fri]
+                  from: '22:00'
+                  to: '02:00'
+            ",
+        );
+
+        assert!(schedule.is_active(&at("2026-10-23T23:00")));
+        assert!(schedule.is_active(&at("2026-10-24T01:59")));
+        assert!(!schedule.is_active(&at("2026-10-24T02:00")));
+        assert!(!schedule.is_active(&at("2026-10-23T01:00")));
+    }
+
+    #[test]
+    fn test_next_change() {
+        let schedule = schedule(
+            r"
+            windows:
+                - days: [mon]
+                  from: '09:00'
+                  to: '12:00'
+                - days: [mon]
+                  from: '12:00'
+                  to: '17:00'
+            ",
+        );
+
+        assert_eq!(
+            schedule.next_change(&at("2026-10-19T08:00")),
+            Some(at("2026-10-19T09:00"))
+        );
+        // Adjacent windows don't make a change.
+        assert_eq!(
+            schedule.next_change(&at("2026-10-19T10:00")),
+            Some(at("2026-10-19T17:00"))
+        );
+        assert_eq!(
+            schedule.next_change(&at("2026-10-19T18:00")),
+            Some(at("2026-10-26T09:00"))
+        );
+    }
+}
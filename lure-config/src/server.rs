@@ -0,0 +1,85 @@
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct Options {
+    /// Enable the HTTP server.
+    #[serde(default = "default_enable")]
+    pub enable: bool,
+    /// Address to bind the HTTP server to.
+    #[serde(default = "default_address")]
+    pub address: SocketAddr,
+    /// Number of check intervals without a successful poll after which
+    /// lure is no longer reported as ready.
+    #[serde(default = "default_max_missed_polls")]
+    pub max_missed_polls: u32,
+    /// Expose Prometheus metrics on the `/metrics` endpoint.
+    #[serde(default = "default_metrics")]
+    pub metrics: bool,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            enable: default_enable(),
+            address: default_address(),
+            max_missed_polls: default_max_missed_polls(),
+            metrics: default_metrics(),
+        }
+    }
+}
+
+const fn default_enable() -> bool {
+    false
+}
+
+const fn default_address() -> SocketAddr {
+    SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 8080)
+}
+
+const fn default_max_missed_polls() -> u32 {
+    3
+}
+
+const fn default_metrics() -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options_minimal() {
+        let options: Options = serde_yaml::from_str("enable: true").unwrap();
+
+        assert!(options.enable);
+
+        assert_eq!(options.address, "127.0.0.1:8080".parse().unwrap());
+        assert_eq!(options.max_missed_polls, 3);
+        assert!(!options.metrics);
+    }
+
+    #[test]
+    fn test_options_full() {
+        let yaml = r"
+            enable: true
+            address: 0.0.0.0:9090
+            max_missed_polls: 5
+            metrics: true
+        ";
+
+        let options: Options = serde_yaml::from_str(yaml).unwrap();
+
+        assert!(options.enable);
+
+        assert_eq!(options.address, "0.0.0.0:9090".parse().unwrap());
+        assert_eq!(options.max_missed_polls, 5);
+        assert!(options.metrics);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid socket address syntax")]
+    fn test_invalid_address() {
+        let _: Options = serde_yaml::from_str("address: kitty").unwrap();
+    }
+}
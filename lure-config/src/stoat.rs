@@ -1,13 +1,30 @@
-#[derive(Debug, serde::Deserialize)]
+use std::fmt;
+
+use serde::de::{self, Deserialize as _};
+
+/// One or more Stoat accounts to update, written either as a single
+/// account or as a list of accounts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Targets(pub Vec<Options>);
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
 pub struct Options {
+    /// Name of the target, used in logs.
+    #[serde(default)]
+    pub name: Option<String>,
     #[serde(default = "default_stoat_status")]
     pub status: StatusOptions,
     #[serde(default = "default_lure_stoat_api_url")]
     pub api_url: String,
+    /// Session token of the account. Only optional in dry run.
+    #[serde(default)]
     pub session_token: String,
+    /// Parts of the profile kept up to date with the current track.
+    #[serde(default)]
+    pub profile: ProfileOptions,
 }
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
 pub struct StatusOptions {
     #[serde(default = "default_stoat_status_template")]
     pub template: String,
@@ -15,6 +32,91 @@ pub struct StatusOptions {
     pub idle: Option<String>,
 }
 
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Deserialize)]
+pub struct ProfileOptions {
+    /// Section of the bio replaced with the current track.
+    #[serde(default)]
+    pub bio: Option<BioOptions>,
+    /// Set the cover art of the current track as the profile background.
+    #[serde(default)]
+    pub background: bool,
+}
+
+/// A section of the bio, between two markers, replaced with the current
+/// track. The rest of the bio is left untouched.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct BioOptions {
+    #[serde(default = "default_bio_template")]
+    pub template: String,
+    /// The section when nothing is playing. If not set, the section the bio
+    /// had before lure started is used.
+    #[serde(default)]
+    pub idle: Option<String>,
+    #[serde(default = "default_bio_start_marker")]
+    pub start_marker: String,
+    #[serde(default = "default_bio_end_marker")]
+    pub end_marker: String,
+}
+
+impl ProfileOptions {
+    /// Whether any part of the profile is kept up to date.
+    pub const fn is_enabled(&self) -> bool {
+        self.bio.is_some() || self.background
+    }
+}
+
+impl Targets {
+    /// Returns the targets along with their key paths, `path` being the
+    /// key path of the targets themselves.
+    pub fn with_paths<'a>(&'a self, path: &'a str) -> impl Iterator<Item = (String, &'a Options)> {
+        let single = self.0.len() == 1;
+
+        self.0.iter().enumerate().map(move |(index, options)| {
+            let path = if single {
+                path.to_string()
+            } else {
+                format!("{path}.{index}")
+            };
+
+            (path, options)
+        })
+    }
+}
+
+impl<'de> serde::Deserialize<'de> for Targets {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct TargetsVisitor;
+
+        impl<'de> de::Visitor<'de> for TargetsVisitor {
+            type Value = Targets;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+                formatter.write_str("a Stoat account or a list of Stoat accounts")
+            }
+
+            fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+            where
+                A: de::MapAccess<'de>,
+            {
+                Options::deserialize(de::value::MapAccessDeserializer::new(map))
+                    .map(|options| Targets(vec![options]))
+            }
+
+            fn visit_seq<A>(self, seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: de::SeqAccess<'de>,
+            {
+                Vec::<Options>::deserialize(de::value::SeqAccessDeserializer::new(seq)).map(Targets)
+            }
+        }
+
+        deserializer.deserialize_any(TargetsVisitor)
+    }
+}
+
 fn default_stoat_status() -> StatusOptions {
     StatusOptions {
         template: default_stoat_status_template(),
@@ -26,7 +128,19 @@ fn default_stoat_status_template() -> String {
     String::from("🎵 Listening to %NAME% by %ARTIST%")
 }
 
-fn default_lure_stoat_api_url() -> String {
+fn default_bio_template() -> String {
+    String::from("🎵 %NAME% by %ARTIST%")
+}
+
+fn default_bio_start_marker() -> String {
+    String::from("<!-- lure -->")
+}
+
+fn default_bio_end_marker() -> String {
+    String::from("<!-- /lure -->")
+}
+
+pub(crate) fn default_lure_stoat_api_url() -> String {
     String::from("https://api.stoat.chat")
 }
 
@@ -49,6 +163,8 @@ mod tests {
             "🎵 Listening to %NAME% by %ARTIST%"
         );
         assert_eq!(options.status.idle, None);
+        assert_eq!(options.profile, ProfileOptions::default());
+        assert!(!options.profile.is_enabled());
     }
 
     #[test]
@@ -59,6 +175,12 @@ mod tests {
             status:
                 template: "%NAME% by %ARTIST%"
                 idle: Not listening to anything!
+            profile:
+                bio:
+                    idle: Napping
+                    start_marker: "[np]"
+                    end_marker: "[/np]"
+                background: true
         "#;
 
         let options: Options = serde_yaml::from_str(yaml).unwrap();
@@ -70,11 +192,80 @@ mod tests {
             options.status.idle,
             Some("Not listening to anything!".to_string())
         );
+        assert_eq!(
+            options.profile.bio,
+            Some(BioOptions {
+                template: "🎵 %NAME% by %ARTIST%".to_string(),
+                idle: Some("Napping".to_string()),
+                start_marker: "[np]".to_string(),
+                end_marker: "[/np]".to_string(),
+            })
+        );
+        assert!(options.profile.background);
+        assert!(options.profile.is_enabled());
     }
 
     #[test]
-    #[should_panic(expected = "missing field `session_token`")]
     fn test_missing_session_token() {
-        let _: Options = serde_yaml::from_str("").unwrap();
+        let options: Options = serde_yaml::from_str("{}").unwrap();
+
+        assert_eq!(options.session_token, "");
+    }
+
+    #[test]
+    fn test_targets_single() {
+        let yaml = r"
+            session_token: meow
+        ";
+
+        let targets: Targets = serde_yaml::from_str(yaml).unwrap();
+
+        assert_eq!(targets.0.len(), 1);
+        assert_eq!(targets.0[0].name, None);
+        assert_eq!(targets.0[0].session_token, "meow");
+        assert_eq!(
+            targets
+                .with_paths("stoat")
+                .map(|(path, _)| path)
+                .collect::<Vec<_>>(),
+            ["stoat"]
+        );
+    }
+
+    #[test]
+    fn test_targets_list() {
+        let yaml = r#"
+            - name: public
+              session_token: meow
+            - name: self-hosted
+              api_url: https://api.kittenvolt.cat
+              session_token: mrrp
+              status:
+                template: "%NAME% by %ARTIST%"
+                idle: Napping
+        "#;
+
+        let targets: Targets = serde_yaml::from_str(yaml).unwrap();
+
+        assert_eq!(targets.0.len(), 2);
+        assert_eq!(targets.0[0].name.as_deref(), Some("public"));
+        assert_eq!(targets.0[0].api_url, "https://api.stoat.chat");
+        assert_eq!(targets.0[1].name.as_deref(), Some("self-hosted"));
+        assert_eq!(targets.0[1].api_url, "https://api.kittenvolt.cat");
+        assert_eq!(targets.0[1].status.idle.as_deref(), Some("Napping"));
+        assert_eq!(
+            targets
+                .with_paths("stoat")
+                .map(|(path, _)| path)
+                .collect::<Vec<_>>(),
+            ["stoat.0", "stoat.1"]
+        );
+    }
+
+    #[test]
+    fn test_targets_list_missing_session_token() {
+        let targets: Targets = serde_yaml::from_str("- name: kitty").unwrap();
+
+        assert_eq!(targets.0[0].session_token, "");
     }
 }
@@ -0,0 +1,1311 @@
+use std::collections::HashSet;
+use std::fmt;
+
+use lure_types::template;
+use secrecy::ExposeSecret as _;
+
+use crate::{
+    Config, ServiceOptions, bot, channel, discord, fediverse, file, filter, hook, matrix, mqtt,
+    profile, schedule, stoat, webhook,
+};
+
+/// A problem found in the configuration.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// Dotted key path of the problematic option, e.g. `stoat.api_url`.
+    pub path: String,
+    pub message: String,
+    /// Where the value came from, e.g. the configuration file or an
+    /// environment variable. `None` if the value wasn't set anywhere.
+    pub source: Option<String>,
+}
+
+/// All problems found in the configuration.
+#[derive(Debug)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+impl Diagnostic {
+    pub fn new(path: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            message: message.into(),
+            source: None,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            write!(f, "{}", self.message)?;
+        } else {
+            write!(f, "{}: {}", self.path, self.message)?;
+        }
+
+        if let Some(source) = &self.source {
+            write!(f, " (from {source})")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid configuration:")?;
+        for diagnostic in &self.0 {
+            write!(f, "\n  - {diagnostic}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+impl Config {
+    /// Checks the configuration for semantic problems that can't be caught
+    /// while deserialising it, and returns all of them at once.
+    pub fn validate(&self) -> Vec<Diagnostic> {
+        let mut diagnostics = Vec::new();
+
+        match (&self.service, &self.stoat) {
+            (Some(_), None) => diagnostics.push(Diagnostic::new(
+                "stoat",
+                "must be set together with `service`",
+            )),
+            (None, Some(_)) => diagnostics.push(Diagnostic::new(
+                "service",
+                "must be set together with `stoat`",
+            )),
+            (None, None) if self.profiles.is_empty() => diagnostics.push(Diagnostic::new(
+                "",
+                "Nothing to do. Either `service` and `stoat`, or `profiles` must be set.",
+            )),
+            _ => {}
+        }
+
+        // The default profile is validated like the others, with the paths
+        // of the top-level options.
+        let has_default = self.service.is_some() && self.stoat.is_some();
+        let profiles = self.profiles();
+        let mut names: HashSet<&str> = HashSet::new();
+
+        for (index, profile) in profiles.iter().enumerate() {
+            let path = index
+                .checked_sub(usize::from(has_default))
+                .map_or_else(String::new, |index| format!("profiles.{index}."));
+
+            validate_not_empty(&format!("{path}name"), &profile.name, &mut diagnostics);
+            if !names.insert(&profile.name) {
+                diagnostics.push(Diagnostic::new(
+                    format!("{path}name"),
+                    format!("the profile name `{}` is used more than once", profile.name),
+                ));
+            }
+
+            validate_profile(&path, profile, &mut diagnostics);
+        }
+
+        if let Some(bot) = &self.bot {
+            validate_bot("bot", bot, &names, &mut diagnostics);
+        }
+
+        if self.server.enable && self.server.max_missed_polls == 0 {
+            diagnostics.push(Diagnostic::new(
+                "server.max_missed_polls",
+                "must be greater than 0",
+            ));
+        }
+
+        diagnostics
+    }
+}
+
+/// Validates the options of `profile`, whose paths start with `path`.
+fn validate_profile(path: &str, profile: &profile::Profile, diagnostics: &mut Vec<Diagnostic>) {
+    validate_service(&format!("{path}service"), &profile.service, diagnostics);
+    validate_stoat(
+        &format!("{path}stoat"),
+        &profile.stoat,
+        profile.dry_run,
+        diagnostics,
+    );
+    validate_channels(
+        &format!("{path}channels"),
+        &profile.channels,
+        profile.dry_run,
+        diagnostics,
+    );
+    validate_files(&format!("{path}files"), &profile.files, diagnostics);
+    validate_webhooks(&format!("{path}webhooks"), &profile.webhooks, diagnostics);
+    validate_hooks(&format!("{path}hooks"), &profile.hooks, diagnostics);
+    if let Some(mqtt) = &profile.mqtt {
+        validate_mqtt(&format!("{path}mqtt"), mqtt, diagnostics);
+    }
+    if let Some(discord) = &profile.discord {
+        validate_discord(&format!("{path}discord"), discord, diagnostics);
+    }
+    if let Some(matrix) = &profile.matrix {
+        validate_matrix(
+            &format!("{path}matrix"),
+            matrix,
+            profile.dry_run,
+            diagnostics,
+        );
+    }
+    if let Some(fediverse) = &profile.fediverse {
+        validate_fediverse(
+            &format!("{path}fediverse"),
+            fediverse,
+            profile.dry_run,
+            diagnostics,
+        );
+    }
+    validate_filters(&format!("{path}filters"), &profile.filters, diagnostics);
+    validate_schedule(&format!("{path}schedule"), &profile.schedule, diagnostics);
+}
+
+fn validate_service(path: &str, service: &ServiceOptions, diagnostics: &mut Vec<Diagnostic>) {
+    match service.enabled().as_slice() {
+        [] => diagnostics.push(Diagnostic::new(
+            path,
+            "No services are enabled. One service must be enabled.",
+        )),
+        [_] => {}
+        enabled => diagnostics.push(Diagnostic::new(
+            path,
+            format!(
+                "More than one service ({}) is enabled. Only one service can be enabled at a time.",
+                enabled.join(" and ")
+            ),
+        )),
+    }
+
+    if let Some(lastfm) = &service.lastfm {
+        validate_not_empty(
+            &format!("{path}.lastfm.username"),
+            &lastfm.username,
+            diagnostics,
+        );
+        validate_not_empty(
+            &format!("{path}.lastfm.api_key"),
+            lastfm.api_key.expose_secret(),
+            diagnostics,
+        );
+        validate_check_interval(
+            &format!("{path}.lastfm.check_interval"),
+            lastfm.check_interval,
+            diagnostics,
+        );
+    }
+
+    if let Some(listenbrainz) = &service.listenbrainz {
+        validate_not_empty(
+            &format!("{path}.listenbrainz.username"),
+            &listenbrainz.username,
+            diagnostics,
+        );
+        validate_url(
+            &format!("{path}.listenbrainz.api_url"),
+            &listenbrainz.api_url,
+            diagnostics,
+        );
+        validate_check_interval(
+            &format!("{path}.listenbrainz.check_interval"),
+            listenbrainz.check_interval,
+            diagnostics,
+        );
+    }
+
+    if let Some(exec) = &service.exec {
+        validate_not_empty(&format!("{path}.exec.command"), exec.program(), diagnostics);
+        validate_check_interval(
+            &format!("{path}.exec.check_interval"),
+            exec.check_interval,
+            diagnostics,
+        );
+        if exec.timeout == 0 {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.exec.timeout"),
+                "must be greater than 0",
+            ));
+        }
+        if exec.fatal_after == Some(0) {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.exec.fatal_after"),
+                "must be greater than 0",
+            ));
+        }
+    }
+}
+
+/// In dry run, the accounts are never used, so they don't need a session
+/// token, and may be the same.
+fn validate_stoat(
+    path: &str,
+    targets: &stoat::Targets,
+    dry_run: bool,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    if targets.0.is_empty() {
+        diagnostics.push(Diagnostic::new(
+            path,
+            "No Stoat accounts are configured. At least one account must be configured.",
+        ));
+    }
+
+    let mut seen = HashSet::new();
+
+    for (path, options) in targets.with_paths(path) {
+        validate_url(&format!("{path}.api_url"), &options.api_url, diagnostics);
+        if !dry_run {
+            validate_not_empty(
+                &format!("{path}.session_token"),
+                &options.session_token,
+                diagnostics,
+            );
+        }
+        validate_template(
+            &format!("{path}.status.template"),
+            &options.status.template,
+            diagnostics,
+        );
+        if let Some(idle) = &options.status.idle {
+            validate_idle_template(&format!("{path}.status.idle"), idle, diagnostics);
+        }
+        if let Some(bio) = &options.profile.bio {
+            validate_bio(&format!("{path}.profile.bio"), bio, diagnostics);
+        }
+
+        if !dry_run && !seen.insert((&options.api_url, &options.session_token)) {
+            diagnostics.push(Diagnostic::new(
+                path,
+                "the same Stoat account is configured more than once",
+            ));
+        }
+    }
+}
+
+fn validate_bio(path: &str, options: &stoat::BioOptions, diagnostics: &mut Vec<Diagnostic>) {
+    validate_template(&format!("{path}.template"), &options.template, diagnostics);
+    if let Some(idle) = &options.idle {
+        validate_idle_template(&format!("{path}.idle"), idle, diagnostics);
+    }
+    validate_not_empty(
+        &format!("{path}.start_marker"),
+        &options.start_marker,
+        diagnostics,
+    );
+    validate_not_empty(
+        &format!("{path}.end_marker"),
+        &options.end_marker,
+        diagnostics,
+    );
+    if options.start_marker == options.end_marker {
+        diagnostics.push(Diagnostic::new(
+            format!("{path}.end_marker"),
+            "must be different from `start_marker`",
+        ));
+    }
+}
+
+/// In dry run, the channels are never posted to, so they don't need a
+/// session token.
+fn validate_channels(
+    path: &str,
+    channels: &[channel::Options],
+    dry_run: bool,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    let mut seen = HashSet::new();
+
+    for (index, options) in channels.iter().enumerate() {
+        let path = format!("{path}.{index}");
+
+        validate_not_empty(
+            &format!("{path}.channel_id"),
+            &options.channel_id,
+            diagnostics,
+        );
+        if !seen.insert((&options.api_url, &options.channel_id)) {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.channel_id"),
+                "the same channel is configured more than once",
+            ));
+        }
+        validate_url(&format!("{path}.api_url"), &options.api_url, diagnostics);
+        if !dry_run {
+            validate_not_empty(
+                &format!("{path}.session_token"),
+                &options.session_token,
+                diagnostics,
+            );
+        }
+        validate_template(&format!("{path}.template"), &options.template, diagnostics);
+    }
+}
+
+fn validate_bot(
+    path: &str,
+    options: &bot::Options,
+    profiles: &HashSet<&str>,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    validate_url(&format!("{path}.api_url"), &options.api_url, diagnostics);
+    // The bot has to listen for the command, even in dry run.
+    validate_not_empty(
+        &format!("{path}.bot_token"),
+        &options.bot_token,
+        diagnostics,
+    );
+    if options.command.is_empty() || options.command.contains(char::is_whitespace) {
+        diagnostics.push(Diagnostic::new(
+            format!("{path}.command"),
+            "must be a single word",
+        ));
+    }
+    for (index, name) in options.profiles.iter().enumerate() {
+        if !profiles.contains(name.as_str()) {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.profiles.{index}"),
+                format!("there is no profile named `{name}`"),
+            ));
+        }
+    }
+
+    // `%PROFILE%` is only known to the bot, so it's taken out before
+    // checking the rest of the placeholders.
+    let without_profile = |template: &str| template.replace(bot::PROFILE_PLACEHOLDER, "PROFILE");
+    validate_template(
+        &format!("{path}.template"),
+        &without_profile(&options.template),
+        diagnostics,
+    );
+    validate_idle_template(
+        &format!("{path}.idle"),
+        &without_profile(&options.idle),
+        diagnostics,
+    );
+}
+
+fn validate_files(path: &str, files: &[file::Options], diagnostics: &mut Vec<Diagnostic>) {
+    let mut seen = HashSet::new();
+
+    for (index, options) in files.iter().enumerate() {
+        let path = format!("{path}.{index}");
+
+        if options.path.as_os_str().is_empty() {
+            diagnostics.push(Diagnostic::new(format!("{path}.path"), "must not be empty"));
+        } else if !seen.insert(&options.path) {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.path"),
+                "the same file is configured more than once",
+            ));
+        }
+        validate_template(&format!("{path}.template"), &options.template, diagnostics);
+        if let Some(idle) = &options.idle {
+            validate_idle_template(&format!("{path}.idle"), idle, diagnostics);
+        }
+    }
+}
+
+fn validate_webhooks(path: &str, webhooks: &[webhook::Options], diagnostics: &mut Vec<Diagnostic>) {
+    let mut seen = HashSet::new();
+
+    for (index, options) in webhooks.iter().enumerate() {
+        let path = format!("{path}.{index}");
+
+        validate_url(&format!("{path}.url"), &options.url, diagnostics);
+        if !seen.insert(&options.url) {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.url"),
+                "the same webhook is configured more than once",
+            ));
+        }
+        if let Some(secret) = &options.secret {
+            validate_not_empty(
+                &format!("{path}.secret"),
+                secret.expose_secret(),
+                diagnostics,
+            );
+        }
+        if options.queue_size == 0 {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.queue_size"),
+                "must be greater than 0",
+            ));
+        }
+    }
+}
+
+fn validate_hooks(path: &str, hooks: &[hook::Options], diagnostics: &mut Vec<Diagnostic>) {
+    for (index, options) in hooks.iter().enumerate() {
+        let path = format!("{path}.{index}");
+
+        validate_not_empty(&format!("{path}.command"), options.program(), diagnostics);
+        if options.events.is_empty() {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.events"),
+                "must not be empty",
+            ));
+        }
+        if options.timeout == 0 {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.timeout"),
+                "must be greater than 0",
+            ));
+        }
+        if options.max_concurrent == 0 {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.max_concurrent"),
+                "must be greater than 0",
+            ));
+        }
+    }
+}
+
+fn validate_mqtt(path: &str, options: &mqtt::Options, diagnostics: &mut Vec<Diagnostic>) {
+    validate_not_empty(&format!("{path}.host"), &options.host, diagnostics);
+    if options.port == 0 {
+        diagnostics.push(Diagnostic::new(
+            format!("{path}.port"),
+            "must be greater than 0",
+        ));
+    }
+    if let Some(client_id) = &options.client_id {
+        validate_not_empty(&format!("{path}.client_id"), client_id, diagnostics);
+    }
+    if let Some(topic) = &options.topic {
+        validate_not_empty(&format!("{path}.topic"), topic, diagnostics);
+        if topic.contains(['+', '#']) {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.topic"),
+                "must not contain the `+` or `#` wildcards",
+            ));
+        }
+    }
+    if options.discovery.enable {
+        validate_not_empty(
+            &format!("{path}.discovery.prefix"),
+            &options.discovery.prefix,
+            diagnostics,
+        );
+    }
+}
+
+fn validate_discord(path: &str, options: &discord::Options, diagnostics: &mut Vec<Diagnostic>) {
+    if options.client_id.is_empty() || !options.client_id.bytes().all(|byte| byte.is_ascii_digit())
+    {
+        diagnostics.push(Diagnostic::new(
+            format!("{path}.client_id"),
+            "must be the numeric ID of a Discord application",
+        ));
+    }
+    if options
+        .socket
+        .as_ref()
+        .is_some_and(|socket| socket.as_os_str().is_empty())
+    {
+        diagnostics.push(Diagnostic::new(
+            format!("{path}.socket"),
+            "must not be empty",
+        ));
+    }
+}
+
+/// In dry run, the account is never used, so it doesn't need an access
+/// token.
+fn validate_matrix(
+    path: &str,
+    options: &matrix::Options,
+    dry_run: bool,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    validate_url(
+        &format!("{path}.homeserver"),
+        &options.homeserver,
+        diagnostics,
+    );
+    if !dry_run {
+        validate_not_empty(
+            &format!("{path}.access_token"),
+            options
+                .access_token
+                .as_ref()
+                .map(|access_token| access_token.expose_secret())
+                .unwrap_or_default(),
+            diagnostics,
+        );
+    }
+    validate_template(
+        &format!("{path}.status.template"),
+        &options.status.template,
+        diagnostics,
+    );
+    if let Some(idle) = &options.status.idle {
+        validate_idle_template(&format!("{path}.status.idle"), idle, diagnostics);
+    }
+}
+
+/// In dry run, the account is never used, so it doesn't need an access
+/// token.
+fn validate_fediverse(
+    path: &str,
+    options: &fediverse::Options,
+    dry_run: bool,
+    diagnostics: &mut Vec<Diagnostic>,
+) {
+    validate_url(&format!("{path}.instance"), &options.instance, diagnostics);
+    if !dry_run {
+        validate_not_empty(
+            &format!("{path}.access_token"),
+            options
+                .access_token
+                .as_ref()
+                .map(|access_token| access_token.expose_secret())
+                .unwrap_or_default(),
+            diagnostics,
+        );
+    }
+    validate_not_empty(&format!("{path}.field"), options.field.trim(), diagnostics);
+    validate_template(&format!("{path}.template"), &options.template, diagnostics);
+    if let Some(idle) = &options.idle {
+        validate_idle_template(&format!("{path}.idle"), idle, diagnostics);
+    }
+    if options.min_interval == 0 {
+        diagnostics.push(Diagnostic::new(
+            format!("{path}.min_interval"),
+            "must be greater than 0",
+        ));
+    }
+}
+
+fn validate_filters(path: &str, filters: &[filter::Filter], diagnostics: &mut Vec<Diagnostic>) {
+    for (index, filter) in filters.iter().enumerate() {
+        let path = format!("{path}.{index}");
+
+        if let Err(error) = filter.regex() {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.pattern"),
+                format!("invalid regular expression: {error}"),
+            ));
+        }
+        if let Some(replacement) = &filter.replacement {
+            validate_template(&format!("{path}.replacement"), replacement, diagnostics);
+        }
+    }
+}
+
+fn validate_schedule(path: &str, schedule: &schedule::Schedule, diagnostics: &mut Vec<Diagnostic>) {
+    if let Err(error) = schedule.time_zone() {
+        diagnostics.push(Diagnostic::new(
+            format!("{path}.timezone"),
+            format!("unknown time zone: {error}"),
+        ));
+    }
+
+    for (index, window) in schedule.windows.iter().enumerate() {
+        if window.days.is_empty() {
+            diagnostics.push(Diagnostic::new(
+                format!("{path}.windows.{index}.days"),
+                "must not be empty",
+            ));
+        }
+    }
+}
+
+fn validate_not_empty(path: &str, value: &str, diagnostics: &mut Vec<Diagnostic>) {
+    if value.trim().is_empty() {
+        diagnostics.push(Diagnostic::new(path, "must not be empty"));
+    }
+}
+
+fn validate_url(path: &str, value: &str, diagnostics: &mut Vec<Diagnostic>) {
+    match url::Url::parse(value) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") => {}
+        Ok(url) => diagnostics.push(Diagnostic::new(
+            path,
+            format!(
+                "unsupported URL scheme `{}`, expected http or https",
+                url.scheme()
+            ),
+        )),
+        Err(error) => diagnostics.push(Diagnostic::new(path, format!("invalid URL: {error}"))),
+    }
+}
+
+fn validate_check_interval(path: &str, value: u64, diagnostics: &mut Vec<Diagnostic>) {
+    if value == 0 {
+        diagnostics.push(Diagnostic::new(path, "must be greater than 0"));
+    }
+}
+
+fn validate_template(path: &str, value: &str, diagnostics: &mut Vec<Diagnostic>) {
+    validate_not_empty(path, value, diagnostics);
+
+    for placeholder in template::unknown_placeholders(value) {
+        diagnostics.push(Diagnostic::new(
+            path,
+            format!(
+                "unknown placeholder `{placeholder}`, expected one of {}",
+                template::PLACEHOLDERS.join(", ")
+            ),
+        ));
+    }
+}
+
+/// The idle status is shown when nothing is playing, so it can't use the
+/// placeholders of the current track.
+fn validate_idle_template(path: &str, value: &str, diagnostics: &mut Vec<Diagnostic>) {
+    validate_template(path, value, diagnostics);
+
+    for placeholder in template::TRACK_PLACEHOLDERS {
+        if template::uses(value, placeholder) {
+            diagnostics.push(Diagnostic::new(
+                path,
+                format!("`{placeholder}` can't be used when nothing is playing"),
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn paths(yaml: &str) -> Vec<String> {
+        let config: Config = serde_yaml::from_str(yaml).unwrap();
+
+        config
+            .validate()
+            .into_iter()
+            .map(|diagnostic| diagnostic.path)
+            .collect()
+    }
+
+    #[test]
+    fn test_valid_config() {
+        let yaml = r"
+            service:
+                listenbrainz:
+                    username: kitty
+            stoat:
+                session_token: meow
+        ";
+
+        assert!(paths(yaml).is_empty());
+    }
+
+    #[test]
+    fn test_all_problems_are_reported() {
+        let yaml = r#"
+            service:
+                lastfm:
+                    username: ""
+                    api_key: hellokitty
+                    check_interval: 0
+            stoat:
+                api_url: kitty
+                session_token: meow
+                status:
+                    template: "%TITLE% by %ARTIST%"
+        "#;
+
+        assert_eq!(
+            paths(yaml),
+            [
+                "service.lastfm.username",
+                "service.lastfm.check_interval",
+                "stoat.api_url",
+                "stoat.status.template",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_service_count() {
+        let none = r"
+            service: {}
+            stoat:
+                session_token: meow
+        ";
+        let both = r"
+            service:
+                lastfm:
+                    username: kitty
+                    api_key: hellokitty
+                listenbrainz:
+                    username: kitty
+            stoat:
+                session_token: meow
+        ";
+
+        assert_eq!(paths(none), ["service"]);
+        assert_eq!(paths(both), ["service"]);
+    }
+
+    #[test]
+    fn test_exec_service() {
+        let yaml = r#"
+            service:
+                exec:
+                    command: [""]
+                    check_interval: 0
+                    timeout: 0
+                    fatal_after: 0
+            stoat:
+                session_token: meow
+        "#;
+
+        assert_eq!(
+            paths(yaml),
+            [
+                "service.exec.command",
+                "service.exec.check_interval",
+                "service.exec.timeout",
+                "service.exec.fatal_after"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stoat_targets() {
+        let yaml = r#"
+            service:
+                listenbrainz:
+                    username: kitty
+            stoat:
+                - session_token: meow
+                - api_url: kitty
+                  session_token: mrrp
+                  status:
+                    template: "%TITLE%"
+                - session_token: meow
+        "#;
+
+        assert_eq!(
+            paths(yaml),
+            ["stoat.1.api_url", "stoat.1.status.template", "stoat.2"]
+        );
+    }
+
+    #[test]
+    fn test_profiles() {
+        let yaml = r"
+            profiles:
+                - name: kitty
+                  service:
+                      listenbrainz:
+                          username: kitty
+                  stoat:
+                      session_token: meow
+                - name: kitty
+                  service:
+                      listenbrainz:
+                          username: ''
+                  stoat:
+                      session_token: mrrp
+        ";
+
+        assert_eq!(
+            paths(yaml),
+            [
+                "profiles.1.name",
+                "profiles.1.service.listenbrainz.username"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_default_profile_is_incomplete() {
+        let yaml = r"
+            service:
+                listenbrainz:
+                    username: kitty
+        ";
+
+        assert_eq!(paths(yaml), ["stoat"]);
+        assert_eq!(paths("server: {}"), [""]);
+    }
+
+    #[test]
+    fn test_stoat_profile() {
+        let yaml = r#"
+            service:
+                listenbrainz:
+                    username: kitty
+            stoat:
+                - name: bio
+                  session_token: meow
+                  profile:
+                      bio:
+                          template: "%MEOW%"
+                          idle: "%ARTIST%"
+                          start_marker: "[np]"
+                          end_marker: "[np]"
+                - name: background
+                  session_token: mrrp
+                  profile:
+                      background: true
+                      bio:
+                          start_marker: ""
+        "#;
+
+        assert_eq!(
+            paths(yaml),
+            [
+                "stoat.0.profile.bio.template",
+                "stoat.0.profile.bio.idle",
+                "stoat.0.profile.bio.end_marker",
+                "stoat.1.profile.bio.start_marker"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_idle_template() {
+        let yaml = r#"
+            service:
+                listenbrainz:
+                    username: kitty
+            stoat:
+                - session_token: meow
+                  status:
+                      idle: "Top artist this week: %TOP_ARTIST_WEEK%"
+                - session_token: mrrp
+                  status:
+                      idle: "Last played %NAME%"
+        "#;
+
+        assert_eq!(paths(yaml), ["stoat.1.status.idle"]);
+    }
+
+    #[test]
+    fn test_files() {
+        let yaml = r#"
+            service:
+                listenbrainz:
+                    username: kitty
+            stoat:
+                session_token: meow
+            files:
+                - path: now-playing.txt
+                  idle: "Last played %ARTIST%"
+                - path: now-playing.txt
+                  format: json
+                - path: ""
+                  template: "%MOOD%"
+        "#;
+
+        assert_eq!(
+            paths(yaml),
+            [
+                "files.0.idle",
+                "files.1.path",
+                "files.2.path",
+                "files.2.template"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_webhooks() {
+        let yaml = r#"
+            service:
+                listenbrainz:
+                    username: kitty
+            stoat:
+                session_token: meow
+            webhooks:
+                - url: https://example.com/hook
+                  secret: ""
+                - url: https://example.com/hook
+                  queue_size: 0
+                - url: ftp://example.com/hook
+        "#;
+
+        assert_eq!(
+            paths(yaml),
+            [
+                "webhooks.0.secret",
+                "webhooks.1.url",
+                "webhooks.1.queue_size",
+                "webhooks.2.url"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hooks() {
+        let yaml = r#"
+            service:
+                listenbrainz:
+                    username: kitty
+            stoat:
+                session_token: meow
+            hooks:
+                - command: []
+                  events: []
+                - command: [""]
+                  timeout: 0
+                  max_concurrent: 0
+                - command: [notify-send]
+        "#;
+
+        assert_eq!(
+            paths(yaml),
+            [
+                "hooks.0.command",
+                "hooks.0.events",
+                "hooks.1.command",
+                "hooks.1.timeout",
+                "hooks.1.max_concurrent"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_mqtt() {
+        let yaml = r#"
+            service:
+                listenbrainz:
+                    username: kitty
+            stoat:
+                session_token: meow
+            mqtt:
+                host: ""
+                topic: lure/#
+            profiles:
+                - name: puppy
+                  service:
+                      listenbrainz:
+                          username: puppy
+                  stoat:
+                      session_token: woof
+                  mqtt:
+                      host: localhost
+                      port: 0
+                      discovery:
+                          enable: true
+                          prefix: ""
+        "#;
+
+        assert_eq!(
+            paths(yaml),
+            [
+                "mqtt.host",
+                "mqtt.topic",
+                "profiles.0.mqtt.port",
+                "profiles.0.mqtt.discovery.prefix"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_discord() {
+        let yaml = r#"
+            service:
+                listenbrainz:
+                    username: kitty
+            stoat:
+                session_token: meow
+            discord:
+                client_id: meow
+                socket: ""
+            profiles:
+                - name: puppy
+                  service:
+                      listenbrainz:
+                          username: puppy
+                  stoat:
+                      session_token: woof
+                  discord:
+                      client_id: ""
+                - name: bunny
+                  service:
+                      listenbrainz:
+                          username: bunny
+                  stoat:
+                      session_token: squeak
+                  discord:
+                      client_id: "1234"
+        "#;
+
+        assert_eq!(
+            paths(yaml),
+            [
+                "discord.client_id",
+                "discord.socket",
+                "profiles.0.discord.client_id"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_matrix() {
+        let yaml = r#"
+            service:
+                listenbrainz:
+                    username: kitty
+            stoat:
+                session_token: meow
+            matrix:
+                homeserver: matrix.example.com
+                access_token: meow
+                status:
+                    template: "%MEOW%"
+            profiles:
+                - name: puppy
+                  service:
+                      listenbrainz:
+                          username: puppy
+                  stoat:
+                      session_token: woof
+                  matrix:
+                      homeserver: https://matrix.example.com
+                - name: bunny
+                  service:
+                      listenbrainz:
+                          username: bunny
+                  stoat: {}
+                  matrix:
+                      homeserver: https://matrix.example.com
+                  dry_run: true
+        "#;
+
+        assert_eq!(
+            paths(yaml),
+            [
+                "matrix.homeserver",
+                "matrix.status.template",
+                "profiles.0.matrix.access_token"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fediverse() {
+        let yaml = r#"
+            service:
+                listenbrainz:
+                    username: kitty
+            stoat:
+                session_token: meow
+            fediverse:
+                instance: https://example.social
+                access_token: meow
+                field: " "
+                min_interval: 0
+            profiles:
+                - name: puppy
+                  service:
+                      listenbrainz:
+                          username: puppy
+                  stoat:
+                      session_token: woof
+                  fediverse:
+                      instance: example.social
+                      idle: "%NAME%"
+        "#;
+
+        assert_eq!(
+            paths(yaml),
+            [
+                "fediverse.field",
+                "fediverse.min_interval",
+                "profiles.0.fediverse.instance",
+                "profiles.0.fediverse.access_token",
+                "profiles.0.fediverse.idle"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_channels() {
+        let yaml = r#"
+            service:
+                listenbrainz:
+                    username: kitty
+            stoat:
+                session_token: meow
+            channels:
+                - channel_id: meow
+                  session_token: meow
+                - channel_id: meow
+                  template: "%MEOW%"
+            profiles:
+                - name: puppy
+                  service:
+                      listenbrainz:
+                          username: puppy
+                  stoat:
+                      session_token: woof
+                  channels:
+                      - channel_id: ""
+                        api_url: stoat.example.com
+                        session_token: woof
+        "#;
+
+        assert_eq!(
+            paths(yaml),
+            [
+                "channels.1.channel_id",
+                "channels.1.session_token",
+                "channels.1.template",
+                "profiles.0.channels.0.channel_id",
+                "profiles.0.channels.0.api_url"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bot() {
+        let yaml = r#"
+            service:
+                listenbrainz:
+                    username: kitty
+            stoat:
+                session_token: meow
+            profiles:
+                - name: puppy
+                  service:
+                      listenbrainz:
+                          username: puppy
+                  stoat:
+                      session_token: woof
+            bot:
+                api_url: stoat.example.com
+                command: "! np"
+                profiles: [default, puppy, bunny]
+                template: "%PROFILE% %MEOW%"
+                idle: "%PROFILE% was listening to %NAME%"
+        "#;
+
+        assert_eq!(
+            paths(yaml),
+            [
+                "bot.api_url",
+                "bot.bot_token",
+                "bot.command",
+                "bot.profiles.2",
+                "bot.template",
+                "bot.idle"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_filters() {
+        let yaml = r#"
+            service:
+                listenbrainz:
+                    username: kitty
+            stoat:
+                session_token: meow
+            filters:
+                - field: artist
+                  match: regex
+                  pattern: "(Rain"
+                - field: album
+                  pattern: Sleep
+                  replacement: "%MOOD%"
+            profiles:
+                - name: puppy
+                  service:
+                      listenbrainz:
+                          username: puppy
+                  stoat:
+                      session_token: woof
+                  filters:
+                      - field: title
+                        match: ignore_case
+                        pattern: lullaby
+                        replacement: ""
+        "#;
+
+        assert_eq!(
+            paths(yaml),
+            [
+                "filters.0.pattern",
+                "filters.1.replacement",
+                "profiles.0.filters.0.replacement"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_schedule() {
+        let yaml = r"
+            service:
+                listenbrainz:
+                    username: kitty
+            stoat:
+                session_token: meow
+            schedule:
+                timezone: Europe/Meowville
+                windows:
+                    - from: '18:00'
+                      to: '23:00'
+                    - days: []
+                      from: '10:00'
+                      to: '12:00'
+        ";
+
+        assert_eq!(
+            paths(yaml),
+            ["schedule.timezone", "schedule.windows.1.days"]
+        );
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let yaml = r"
+            service:
+                listenbrainz:
+                    username: kitty
+            stoat:
+                - name: public
+                - name: self-hosted
+            profiles:
+                - name: puppy
+                  dry_run: true
+                  service:
+                      listenbrainz:
+                          username: puppy
+                  stoat: {}
+                - name: bunny
+                  service:
+                      listenbrainz:
+                          username: bunny
+                  stoat: {}
+        ";
+
+        assert_eq!(
+            paths(yaml),
+            [
+                "stoat.0.session_token",
+                "stoat.1.session_token",
+                "stoat.1",
+                "profiles.1.stoat.session_token"
+            ]
+        );
+        assert_eq!(
+            paths(&format!("            dry_run: true{yaml}")),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_diagnostic_display() {
+        let diagnostic = Diagnostic {
+            source: Some("`LURE_` environment variable(s)".to_string()),
+            ..Diagnostic::new("stoat.session_token", "must not be empty")
+        };
+
+        assert_eq!(
+            diagnostic.to_string(),
+            "stoat.session_token: must not be empty (from `LURE_` environment variable(s))"
+        );
+    }
+}
@@ -0,0 +1,61 @@
+use secrecy::{ExposeSecret as _, SecretString};
+
+/// A URL the playback events are posted to.
+#[derive(Debug, Clone, serde::Deserialize)]
+pub struct Options {
+    pub url: String,
+    /// Shared secret the payloads are signed with. If not set, they aren't
+    /// signed.
+    #[serde(default)]
+    pub secret: Option<SecretString>,
+    /// How many events are held while the endpoint is down. The oldest
+    /// ones are dropped first.
+    #[serde(default = "default_queue_size")]
+    pub queue_size: usize,
+}
+
+impl PartialEq for Options {
+    fn eq(&self, other: &Self) -> bool {
+        self.url == other.url
+            && self.secret.as_ref().map(|secret| secret.expose_secret())
+                == other.secret.as_ref().map(|secret| secret.expose_secret())
+            && self.queue_size == other.queue_size
+    }
+}
+
+impl Eq for Options {}
+
+const fn default_queue_size() -> usize {
+    100
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options_minimal() {
+        let options: Options = serde_yaml::from_str("url: https://example.com/hook").unwrap();
+
+        assert_eq!(options.url, "https://example.com/hook");
+        assert!(options.secret.is_none());
+        assert_eq!(options.queue_size, 100);
+    }
+
+    #[test]
+    fn test_options_full() {
+        let yaml = r"
+            url: https://example.com/hook
+            secret: meow
+            queue_size: 10
+        ";
+
+        let options: Options = serde_yaml::from_str(yaml).unwrap();
+
+        assert_eq!(
+            options.secret.as_ref().map(|secret| secret.expose_secret()),
+            Some("meow")
+        );
+        assert_eq!(options.queue_size, 10);
+    }
+}
@@ -0,0 +1,446 @@
+use std::path::{Path, PathBuf};
+
+use tokio::io::{AsyncRead, AsyncReadExt as _, AsyncWrite, AsyncWriteExt as _};
+
+pub use models::{Activity, ActivityType, Assets, Timestamps};
+
+mod models;
+
+/// Largest frame accepted from Discord.
+const MAX_FRAME_LENGTH: u32 = 1024 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+enum Opcode {
+    Handshake = 0,
+    Frame = 1,
+    Close = 2,
+    Ping = 3,
+    Pong = 4,
+}
+
+trait Stream: AsyncRead + AsyncWrite + Send + Sync + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Sync + Unpin> Stream for T {}
+
+/// A connection to the local Discord client, over its IPC socket.
+pub struct Client {
+    stream: Box<dyn Stream>,
+    next_nonce: u64,
+}
+
+impl Client {
+    /// Connects to the Discord client as the application `client_id`, at
+    /// `path`, or at the first IPC socket found in the usual places.
+    pub async fn connect(client_id: &str, path: Option<&Path>) -> Result<Self, Error> {
+        let stream = match path {
+            Some(path) => open(path).await?,
+            None => {
+                let mut found = None;
+                for path in socket_paths() {
+                    if let Ok(stream) = open(&path).await {
+                        found = Some(stream);
+                        break;
+                    }
+                }
+
+                found.ok_or(Error::NotRunning)?
+            }
+        };
+
+        let mut client = Self {
+            stream,
+            next_nonce: 0,
+        };
+        client
+            .write(
+                Opcode::Handshake,
+                &serde_json::json!({ "v": 1, "client_id": client_id }),
+            )
+            .await?;
+        // Discord answers with a `READY` event, or closes the connection if
+        // the client ID is invalid.
+        client.read().await?;
+
+        Ok(client)
+    }
+
+    /// Sets the activity of the user, or clears it.
+    pub async fn set_activity(&mut self, activity: Option<&Activity>) -> Result<(), Error> {
+        self.next_nonce += 1;
+        let nonce = self.next_nonce.to_string();
+
+        self.write(
+            Opcode::Frame,
+            &models::Command {
+                cmd: "SET_ACTIVITY",
+                args: serde_json::json!({
+                    "pid": std::process::id(),
+                    "activity": activity,
+                }),
+                nonce: &nonce,
+            },
+        )
+        .await?;
+
+        loop {
+            let response = self.read().await?;
+            if response.nonce.as_deref() != Some(nonce.as_str()) {
+                continue;
+            }
+
+            return match response.evt.as_deref() {
+                Some("ERROR") => {
+                    let data = response.data.unwrap_or_default();
+
+                    Err(Error::Rpc {
+                        code: data.code,
+                        message: data.message,
+                    })
+                }
+                _ => Ok(()),
+            };
+        }
+    }
+
+    /// Closes the connection, which clears the activity.
+    pub async fn close(mut self) -> Result<(), Error> {
+        self.write(Opcode::Close, &serde_json::json!({})).await?;
+        self.stream.shutdown().await?;
+
+        Ok(())
+    }
+
+    async fn write<T: serde::Serialize + Sync>(
+        &mut self,
+        opcode: Opcode,
+        payload: &T,
+    ) -> Result<(), Error> {
+        let payload = serde_json::to_vec(payload)?;
+        let length = u32::try_from(payload.len()).map_err(|_| Error::FrameTooLarge)?;
+
+        let mut frame = Vec::with_capacity(payload.len() + 8);
+        frame.extend_from_slice(&(opcode as u32).to_le_bytes());
+        frame.extend_from_slice(&length.to_le_bytes());
+        frame.extend_from_slice(&payload);
+        self.stream.write_all(&frame).await?;
+
+        Ok(())
+    }
+
+    /// Reads the next response, answering pings on the way.
+    async fn read(&mut self) -> Result<models::Response, Error> {
+        loop {
+            let opcode = self.stream.read_u32_le().await?;
+            let length = self.stream.read_u32_le().await?;
+            if length > MAX_FRAME_LENGTH {
+                return Err(Error::FrameTooLarge);
+            }
+
+            let mut payload = vec![0; length as usize];
+            self.stream.read_exact(&mut payload).await?;
+
+            match opcode {
+                opcode if opcode == Opcode::Frame as u32 => {
+                    return Ok(serde_json::from_slice(&payload)?);
+                }
+                opcode if opcode == Opcode::Close as u32 => {
+                    let data: models::ErrorData = serde_json::from_slice(&payload)?;
+
+                    return Err(Error::Closed {
+                        code: data.code,
+                        message: data.message,
+                    });
+                }
+                opcode if opcode == Opcode::Ping as u32 => {
+                    let payload: serde_json::Value = serde_json::from_slice(&payload)?;
+                    self.write(Opcode::Pong, &payload).await?;
+                }
+                opcode => return Err(Error::UnexpectedOpcode(opcode)),
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn open(path: &Path) -> std::io::Result<Box<dyn Stream>> {
+    Ok(Box::new(tokio::net::UnixStream::connect(path).await?))
+}
+
+#[cfg(windows)]
+async fn open(path: &Path) -> std::io::Result<Box<dyn Stream>> {
+    Ok(Box::new(
+        tokio::net::windows::named_pipe::ClientOptions::new().open(path)?,
+    ))
+}
+
+/// Returns where Discord may listen, in the order it picks them: the
+/// runtime or temporary directory, including the ones of the Flatpak and
+/// Snap packages, with the first free number from 0 to 9.
+#[cfg(unix)]
+fn socket_paths() -> Vec<PathBuf> {
+    let directories: Vec<PathBuf> = ["XDG_RUNTIME_DIR", "TMPDIR", "TMP", "TEMP"]
+        .into_iter()
+        .filter_map(std::env::var_os)
+        .map(PathBuf::from)
+        .chain([PathBuf::from("/tmp")])
+        .collect();
+
+    directories
+        .iter()
+        .flat_map(|directory| {
+            [
+                directory.clone(),
+                directory.join("app/com.discordapp.Discord"),
+                directory.join("snap.discord"),
+            ]
+        })
+        .flat_map(|directory| {
+            (0..10).map(move |index| directory.join(format!("discord-ipc-{index}")))
+        })
+        .collect()
+}
+
+#[cfg(windows)]
+fn socket_paths() -> Vec<PathBuf> {
+    (0..10)
+        .map(|index| PathBuf::from(format!(r"\\?\pipe\discord-ipc-{index}")))
+        .collect()
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Discord is not running")]
+    NotRunning,
+    #[error("Discord closed the connection ({code}): {message}")]
+    Closed { code: i64, message: String },
+    #[error("Discord returned an error ({code}): {message}")]
+    Rpc { code: i64, message: String },
+    #[error("unexpected opcode {0}")]
+    UnexpectedOpcode(u32),
+    #[error("frame too large")]
+    FrameTooLarge,
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use tokio::net::{UnixListener, UnixStream};
+
+    use super::*;
+
+    /// A fake Discord client, answering to one connection.
+    struct FakeDiscord {
+        stream: UnixStream,
+    }
+
+    impl FakeDiscord {
+        async fn accept(listener: &UnixListener) -> Self {
+            Self {
+                stream: listener.accept().await.unwrap().0,
+            }
+        }
+
+        async fn read(&mut self) -> (u32, serde_json::Value) {
+            let opcode = self.stream.read_u32_le().await.unwrap();
+            let length = self.stream.read_u32_le().await.unwrap();
+            let mut payload = vec![0; length as usize];
+            self.stream.read_exact(&mut payload).await.unwrap();
+
+            (opcode, serde_json::from_slice(&payload).unwrap())
+        }
+
+        async fn write(&mut self, opcode: u32, payload: &serde_json::Value) {
+            let payload = serde_json::to_vec(payload).unwrap();
+            self.stream.write_u32_le(opcode).await.unwrap();
+            self.stream
+                .write_u32_le(u32::try_from(payload.len()).unwrap())
+                .await
+                .unwrap();
+            self.stream.write_all(&payload).await.unwrap();
+        }
+
+        async fn handshake(&mut self) -> serde_json::Value {
+            let (opcode, payload) = self.read().await;
+            assert_eq!(opcode, 0);
+            self.write(1, &serde_json::json!({ "cmd": "DISPATCH", "evt": "READY" }))
+                .await;
+
+            payload
+        }
+
+        /// Reads a command, and answers it with `evt` and `data`.
+        async fn answer(
+            &mut self,
+            evt: Option<&str>,
+            data: serde_json::Value,
+        ) -> serde_json::Value {
+            let (opcode, command) = self.read().await;
+            assert_eq!(opcode, 1);
+            self.write(
+                1,
+                &serde_json::json!({
+                    "cmd": command["cmd"],
+                    "evt": evt,
+                    "nonce": command["nonce"],
+                    "data": data,
+                }),
+            )
+            .await;
+
+            command
+        }
+    }
+
+    fn listen() -> (tempfile::TempDir, PathBuf, UnixListener) {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("discord-ipc-0");
+        let listener = UnixListener::bind(&path).unwrap();
+
+        (directory, path, listener)
+    }
+
+    fn activity() -> Activity {
+        Activity {
+            kind: ActivityType::Listening,
+            details: Some("Meow".to_string()),
+            state: Some("Kitty".to_string()),
+            timestamps: Some(Timestamps {
+                start: Some(1_792_400_000_000),
+                end: None,
+            }),
+            assets: Some(Assets {
+                large_image: Some("https://example.com/cover.png".to_string()),
+                large_text: Some("Purr".to_string()),
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_set_and_clear_activity() {
+        let (_directory, path, listener) = listen();
+
+        let server = tokio::spawn(async move {
+            let mut discord = FakeDiscord::accept(&listener).await;
+
+            let handshake = discord.handshake().await;
+            let set = discord.answer(None, serde_json::json!({})).await;
+            let cleared = discord.answer(None, serde_json::json!({})).await;
+            let (closing, _) = discord.read().await;
+
+            (handshake, set, cleared, closing)
+        });
+
+        let mut client = Client::connect("1234", Some(&path)).await.unwrap();
+        client.set_activity(Some(&activity())).await.unwrap();
+        client.set_activity(None).await.unwrap();
+        client.close().await.unwrap();
+
+        let (handshake, set, cleared, closing) = server.await.unwrap();
+        assert_eq!(
+            handshake,
+            serde_json::json!({ "v": 1, "client_id": "1234" })
+        );
+        assert_eq!(set["cmd"], "SET_ACTIVITY");
+        assert_eq!(set["args"]["pid"], std::process::id());
+        assert_eq!(
+            set["args"]["activity"],
+            serde_json::json!({
+                "type": 2,
+                "details": "Meow",
+                "state": "Kitty",
+                "timestamps": { "start": 1_792_400_000_000_i64 },
+                "assets": {
+                    "large_image": "https://example.com/cover.png",
+                    "large_text": "Purr",
+                },
+            })
+        );
+        assert_ne!(set["nonce"], cleared["nonce"]);
+        assert_eq!(cleared["args"]["activity"], serde_json::Value::Null);
+        assert_eq!(closing, 2);
+    }
+
+    #[tokio::test]
+    async fn test_pings_are_answered() {
+        let (_directory, path, listener) = listen();
+
+        let server = tokio::spawn(async move {
+            let mut discord = FakeDiscord::accept(&listener).await;
+            discord.handshake().await;
+
+            let (opcode, command) = discord.read().await;
+            assert_eq!(opcode, 1);
+            discord.write(3, &serde_json::json!({ "ping": 1 })).await;
+            let pong = discord.read().await;
+            discord
+                .write(1, &serde_json::json!({ "nonce": command["nonce"] }))
+                .await;
+
+            pong
+        });
+
+        let mut client = Client::connect("1234", Some(&path)).await.unwrap();
+        client.set_activity(Some(&activity())).await.unwrap();
+
+        assert_eq!(server.await.unwrap(), (4, serde_json::json!({ "ping": 1 })));
+    }
+
+    #[tokio::test]
+    async fn test_error() {
+        let (_directory, path, listener) = listen();
+
+        tokio::spawn(async move {
+            let mut discord = FakeDiscord::accept(&listener).await;
+            discord.handshake().await;
+            discord
+                .answer(
+                    Some("ERROR"),
+                    serde_json::json!({ "code": 4000, "message": "child \"activity\" fails" }),
+                )
+                .await;
+        });
+
+        let mut client = Client::connect("1234", Some(&path)).await.unwrap();
+
+        assert!(matches!(
+            client.set_activity(Some(&activity())).await,
+            Err(Error::Rpc { code: 4000, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_client_id() {
+        let (_directory, path, listener) = listen();
+
+        tokio::spawn(async move {
+            let mut discord = FakeDiscord::accept(&listener).await;
+            discord.read().await;
+            discord
+                .write(
+                    2,
+                    &serde_json::json!({ "code": 4000, "message": "Invalid Client ID" }),
+                )
+                .await;
+        });
+
+        assert!(matches!(
+            Client::connect("meow", Some(&path)).await,
+            Err(Error::Closed { code: 4000, .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_not_running() {
+        let directory = tempfile::tempdir().unwrap();
+
+        assert!(matches!(
+            Client::connect("1234", Some(&directory.path().join("discord-ipc-0"))).await,
+            Err(Error::Io(_))
+        ));
+    }
+}
@@ -0,0 +1,91 @@
+/// A Rich Presence activity.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct Activity {
+    #[serde(rename = "type")]
+    pub kind: ActivityType,
+    /// The first line, e.g. the title of the song.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<String>,
+    /// The second line, e.g. the artist of the song.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamps: Option<Timestamps>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assets: Option<Assets>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ActivityType {
+    #[default]
+    Playing,
+    Listening,
+    Watching,
+    Competing,
+}
+
+/// Unix timestamps in milliseconds, shown as the elapsed or remaining
+/// time.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct Timestamps {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub start: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end: Option<i64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct Assets {
+    /// An asset key of the application, or an image URL.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub large_image: Option<String>,
+    /// The tooltip of the large image.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub large_text: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub small_image: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub small_text: Option<String>,
+}
+
+impl serde::Serialize for ActivityType {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_u8(match self {
+            Self::Playing => 0,
+            Self::Listening => 2,
+            Self::Watching => 3,
+            Self::Competing => 5,
+        })
+    }
+}
+
+/// A command sent to Discord.
+#[derive(Debug, serde::Serialize)]
+pub struct Command<'a> {
+    pub cmd: &'static str,
+    pub args: serde_json::Value,
+    pub nonce: &'a str,
+}
+
+/// A response or an event from Discord.
+#[derive(Debug, serde::Deserialize)]
+pub struct Response {
+    #[serde(default)]
+    pub evt: Option<String>,
+    #[serde(default)]
+    pub nonce: Option<String>,
+    #[serde(default)]
+    pub data: Option<ErrorData>,
+}
+
+/// The data of an `ERROR` event, or of a closing frame.
+#[derive(Debug, Default, serde::Deserialize)]
+pub struct ErrorData {
+    #[serde(default)]
+    pub code: i64,
+    #[serde(default)]
+    pub message: String,
+}
@@ -0,0 +1,88 @@
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize)]
+pub struct Options {
+    /// The program followed by its arguments. It's run directly, not
+    /// through a shell.
+    pub command: Vec<String>,
+    /// Whether the command is run on every check, or kept running.
+    #[serde(default)]
+    pub mode: Mode,
+    /// Interval in seconds to check for listening activity.
+    #[serde(default = "default_check_interval")]
+    pub check_interval: u64,
+    /// How many seconds a run can take before it's killed. In `stream`
+    /// mode, how long the first line is waited for.
+    #[serde(default = "default_timeout")]
+    pub timeout: u64,
+    /// Number of failures in a row after which the service gives up, or
+    /// `None` to keep retrying.
+    #[serde(default)]
+    pub fatal_after: Option<u32>,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    /// The command is run on every check, and prints the current status.
+    #[default]
+    Poll,
+    /// The command keeps running, and prints a line whenever the status
+    /// changes. It's started again if it exits.
+    Stream,
+}
+
+impl Options {
+    /// The program of the command, used in errors.
+    pub fn program(&self) -> &str {
+        self.command.first().map_or("", String::as_str)
+    }
+}
+
+const fn default_check_interval() -> u64 {
+    5
+}
+
+const fn default_timeout() -> u64 {
+    10
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_options_minimal() {
+        let options: Options = serde_yaml::from_str("command: [now-playing]").unwrap();
+
+        assert_eq!(options.command, ["now-playing"]);
+        assert_eq!(options.program(), "now-playing");
+        assert_eq!(options.mode, Mode::Poll);
+        assert_eq!(options.check_interval, 5);
+        assert_eq!(options.timeout, 10);
+        assert_eq!(options.fatal_after, None);
+    }
+
+    #[test]
+    fn test_options_full() {
+        let yaml = r"
+            command: [now-playing, --follow]
+            mode: stream
+            check_interval: 2
+            timeout: 3
+            fatal_after: 5
+        ";
+
+        let options: Options = serde_yaml::from_str(yaml).unwrap();
+
+        assert_eq!(options.command, ["now-playing", "--follow"]);
+        assert_eq!(options.mode, Mode::Stream);
+        assert_eq!(options.check_interval, 2);
+        assert_eq!(options.timeout, 3);
+        assert_eq!(options.fatal_after, Some(5));
+    }
+
+    #[test]
+    #[should_panic(expected = "missing field `command`")]
+    fn test_missing_required_fields() {
+        let _: Options = serde_yaml::from_str("").unwrap();
+    }
+}
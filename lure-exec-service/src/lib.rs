@@ -0,0 +1,353 @@
+use std::process::{ExitStatus, Stdio};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+use lure_types::PlaybackStatus;
+use tokio::io::{AsyncBufReadExt as _, BufReader};
+use tokio::process::{Child, ChildStdout, Command};
+use tokio::sync::{Mutex, watch};
+use tokio::task::JoinHandle;
+
+pub mod config;
+pub mod models;
+
+/// A listening service backed by a command, for players no other service
+/// supports. See [`models::Output`] for what the command prints.
+pub struct Service {
+    options: config::Options,
+    /// The running command in `stream` mode, started on the first fetch and
+    /// again after it exits.
+    stream: Mutex<Option<Stream>>,
+    /// Number of failed fetches in a row.
+    failures: AtomicU32,
+}
+
+/// A command kept running, printing a status per line.
+struct Stream {
+    updates: watch::Receiver<Update>,
+    /// The task reading the lines, which owns the command, so that it's
+    /// killed once the task is aborted.
+    reader: JoinHandle<()>,
+}
+
+#[derive(Debug)]
+enum Update {
+    Status(PlaybackStatus),
+    /// The last line isn't a valid status.
+    Invalid(String),
+    /// The command exited, with its status if it could be read.
+    Exited(Option<ExitStatus>),
+}
+
+impl Service {
+    pub fn new(options: config::Options) -> Self {
+        Self {
+            options,
+            stream: Mutex::new(None),
+            failures: AtomicU32::new(0),
+        }
+    }
+
+    pub const fn options(&self) -> &config::Options {
+        &self.options
+    }
+
+    pub async fn poll(&self) -> Result<PlaybackStatus, ServiceError> {
+        tokio::time::sleep(Duration::from_secs(self.options.check_interval)).await;
+
+        self.fetch().await
+    }
+
+    /// Fetches the current playback status without waiting for the check
+    /// interval. Once the failures in a row reach `fatal_after`, the error
+    /// is wrapped in [`ServiceError::GaveUp`], which is fatal.
+    pub async fn fetch(&self) -> Result<PlaybackStatus, ServiceError> {
+        let result = match self.options.mode {
+            config::Mode::Poll => self.run().await,
+            config::Mode::Stream => self.read_stream().await,
+        };
+
+        let Err(error) = result else {
+            self.failures.store(0, Ordering::Relaxed);
+            return result;
+        };
+
+        let failures = self.failures.fetch_add(1, Ordering::Relaxed) + 1;
+        if self
+            .options
+            .fatal_after
+            .is_some_and(|fatal_after| failures >= fatal_after)
+        {
+            return Err(ServiceError::GaveUp {
+                failures,
+                source: Box::new(error),
+            });
+        }
+
+        Err(error)
+    }
+
+    /// Runs the command and parses its output. The command is killed if it
+    /// times out.
+    async fn run(&self) -> Result<PlaybackStatus, ServiceError> {
+        let child = self
+            .command()
+            .spawn()
+            .map_err(|error| self.io_error(error))?;
+        let output = tokio::time::timeout(
+            Duration::from_secs(self.options.timeout),
+            child.wait_with_output(),
+        )
+        .await
+        .map_err(|_| ServiceError::Timeout {
+            program: self.options.program().to_string(),
+            seconds: self.options.timeout,
+        })?
+        .map_err(|error| self.io_error(error))?;
+
+        if !output.status.success() {
+            return Err(ServiceError::Exited {
+                program: self.options.program().to_string(),
+                status: Some(output.status),
+            });
+        }
+
+        models::Output::parse(&String::from_utf8_lossy(&output.stdout))
+            .map_err(|error| ServiceError::Output(error.to_string()))
+    }
+
+    /// Returns the last status printed by the running command, starting it
+    /// first if needed. A command that exited is started again on the next
+    /// fetch.
+    async fn read_stream(&self) -> Result<PlaybackStatus, ServiceError> {
+        let mut guard = self.stream.lock().await;
+        let stream = match guard.take() {
+            Some(stream) => stream,
+            None => self.spawn_stream().await?,
+        };
+
+        let result = match &*stream.updates.borrow() {
+            Update::Status(status) => Ok(status.clone()),
+            Update::Invalid(error) => Err(ServiceError::Output(error.clone())),
+            Update::Exited(status) => Err(ServiceError::Exited {
+                program: self.options.program().to_string(),
+                status: *status,
+            }),
+        };
+        if !matches!(result, Err(ServiceError::Exited { .. })) {
+            *guard = Some(stream);
+        }
+
+        result
+    }
+
+    /// Starts the command, and waits up to the timeout for its first line,
+    /// so that the first fetch doesn't report that nothing is playing.
+    async fn spawn_stream(&self) -> Result<Stream, ServiceError> {
+        let mut child = self
+            .command()
+            .spawn()
+            .map_err(|error| self.io_error(error))?;
+        let stdout = child.stdout.take().expect("stdout is piped");
+
+        let (sender, mut updates) = watch::channel(Update::Status(PlaybackStatus::NotPlaying));
+        let reader = tokio::spawn(read_lines(child, stdout, sender));
+
+        let _ = tokio::time::timeout(Duration::from_secs(self.options.timeout), updates.changed())
+            .await;
+
+        Ok(Stream { updates, reader })
+    }
+
+    fn command(&self) -> Command {
+        let mut command = Command::new(self.options.program());
+        command
+            .args(self.options.command.iter().skip(1))
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .kill_on_drop(true);
+
+        command
+    }
+
+    fn io_error(&self, source: std::io::Error) -> ServiceError {
+        ServiceError::Io {
+            program: self.options.program().to_string(),
+            source,
+        }
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        self.reader.abort();
+    }
+}
+
+/// Publishes every line of `stdout` as a status, until the command closes
+/// it.
+async fn read_lines(mut child: Child, stdout: ChildStdout, sender: watch::Sender<Update>) {
+    let mut lines = BufReader::new(stdout).lines();
+
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        sender.send_replace(match models::Output::parse(&line) {
+            Ok(status) => Update::Status(status),
+            Err(error) => Update::Invalid(error.to_string()),
+        });
+    }
+
+    // A command closing its output without exiting is of no use anymore.
+    let _ = child.start_kill();
+    sender.send_replace(Update::Exited(child.wait().await.ok()));
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum ServiceError {
+    #[error("Failed to run `{program}`: {source}")]
+    Io {
+        program: String,
+        source: std::io::Error,
+    },
+    #[error(
+        "`{program}` exited{}.",
+        .status.map_or_else(String::new, |status| format!(" with {status}"))
+    )]
+    Exited {
+        program: String,
+        status: Option<ExitStatus>,
+    },
+    #[error("`{program}` timed out after {seconds} seconds.")]
+    Timeout { program: String, seconds: u64 },
+    #[error("Invalid output: {0}")]
+    Output(String),
+    #[error("{source} Gave up after {failures} failures in a row.")]
+    GaveUp { failures: u32, source: Box<Self> },
+}
+
+impl ServiceError {
+    /// A short, stable name for the error variant.
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::Io { .. } => "io",
+            Self::Exited { .. } => "exited",
+            Self::Timeout { .. } => "timeout",
+            Self::Output(_) => "invalid_output",
+            Self::GaveUp { .. } => "gave_up",
+        }
+    }
+
+    /// Only errors repeated `fatal_after` times in a row are fatal.
+    pub const fn is_fatal(&self) -> bool {
+        matches!(self, Self::GaveUp { .. })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+    use std::time::Instant;
+
+    use lure_types::TrackInfo;
+
+    use super::*;
+
+    const PLAYING: &str = r#"{"status": "playing", "artist": "Cat", "title": "Meow"}"#;
+
+    fn service(mode: config::Mode, script: &str, argument: &Path) -> Service {
+        Service::new(config::Options {
+            command: vec![
+                String::from("sh"),
+                String::from("-c"),
+                script.to_string(),
+                argument.to_str().unwrap().to_string(),
+            ],
+            mode,
+            check_interval: 1,
+            timeout: 1,
+            fatal_after: Some(2),
+        })
+    }
+
+    fn playing() -> PlaybackStatus {
+        PlaybackStatus::Playing(TrackInfo {
+            artist: String::from("Cat"),
+            title: String::from("Meow"),
+            album: None,
+            cover_url: None,
+            url: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_fatal_after_failures() {
+        let service = service(config::Mode::Poll, "exit 1", Path::new(""));
+
+        let error = service.fetch().await.unwrap_err();
+        assert!(matches!(error, ServiceError::Exited { .. }), "{error:?}");
+        assert!(!error.is_fatal());
+
+        let error = service.fetch().await.unwrap_err();
+        assert!(
+            matches!(error, ServiceError::GaveUp { failures: 2, .. }),
+            "{error:?}"
+        );
+        assert!(error.is_fatal());
+    }
+
+    #[tokio::test]
+    async fn test_success_resets_the_failures() {
+        let directory = tempfile::tempdir().unwrap();
+        let ready = directory.path().join("ready");
+        let service = service(
+            config::Mode::Poll,
+            &format!(r#"test -f "$0" && echo '{PLAYING}'"#),
+            &ready,
+        );
+
+        assert!(service.fetch().await.is_err());
+        std::fs::write(&ready, "").unwrap();
+        assert_eq!(service.fetch().await.unwrap(), playing());
+        std::fs::remove_file(&ready).unwrap();
+
+        let error = service.fetch().await.unwrap_err();
+        assert!(!error.is_fatal(), "{error:?}");
+    }
+
+    #[tokio::test]
+    async fn test_kills_the_command_on_timeout() {
+        let service = service(config::Mode::Poll, "sleep 10", Path::new(""));
+        let started_at = Instant::now();
+
+        let error = service.fetch().await.unwrap_err();
+
+        assert!(
+            matches!(error, ServiceError::Timeout { seconds: 1, .. }),
+            "{error:?}"
+        );
+        assert!(started_at.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_restarts_the_stream_after_it_exits() {
+        let directory = tempfile::tempdir().unwrap();
+        let runs = directory.path().join("runs");
+        let service = service(
+            config::Mode::Stream,
+            &format!(r#"echo >> "$0"; echo '{PLAYING}'; sleep 0.2"#),
+            &runs,
+        );
+
+        assert_eq!(service.fetch().await.unwrap(), playing());
+        tokio::time::sleep(Duration::from_millis(500)).await;
+
+        let error = service.fetch().await.unwrap_err();
+        assert!(matches!(error, ServiceError::Exited { .. }), "{error:?}");
+
+        assert_eq!(service.fetch().await.unwrap(), playing());
+        assert_eq!(std::fs::read_to_string(&runs).unwrap().lines().count(), 2);
+    }
+}
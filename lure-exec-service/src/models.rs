@@ -0,0 +1,103 @@
+use lure_types::{PlaybackStatus, TrackInfo};
+
+/// A status printed by the command, as a JSON object:
+///
+/// - `{"status": "playing", "artist": "...", "title": "..."}`, optionally
+///   with `album`, `cover_url` and `url`.
+/// - `{"status": "paused"}` or `{"status": "stopped"}`.
+///
+/// Other fields are ignored.
+#[derive(Debug, serde::Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Output {
+    Playing {
+        artist: String,
+        title: String,
+        #[serde(default)]
+        album: Option<String>,
+        #[serde(default)]
+        cover_url: Option<String>,
+        #[serde(default)]
+        url: Option<String>,
+    },
+    Paused,
+    Stopped,
+}
+
+impl Output {
+    /// Parses the output of the command. Empty output means nothing is
+    /// playing.
+    pub fn parse(output: &str) -> Result<PlaybackStatus, serde_json::Error> {
+        let output = output.trim();
+        if output.is_empty() {
+            return Ok(PlaybackStatus::NotPlaying);
+        }
+
+        Ok(serde_json::from_str::<Self>(output)?.into())
+    }
+}
+
+impl From<Output> for PlaybackStatus {
+    fn from(output: Output) -> Self {
+        match output {
+            Output::Playing {
+                artist,
+                title,
+                album,
+                cover_url,
+                url,
+            } => Self::Playing(TrackInfo {
+                artist,
+                title,
+                album,
+                cover_url,
+                url,
+            }),
+            Output::Paused | Output::Stopped => Self::NotPlaying,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_playing() {
+        let status = Output::parse(
+            r#"{"status": "playing", "artist": "Kitty", "title": "Meow", "album": "Purr", "player": "mpv"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            status,
+            PlaybackStatus::Playing(TrackInfo {
+                artist: String::from("Kitty"),
+                title: String::from("Meow"),
+                album: Some(String::from("Purr")),
+                cover_url: None,
+                url: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_not_playing() {
+        assert_eq!(
+            Output::parse(r#"{"status": "paused"}"#).unwrap(),
+            PlaybackStatus::NotPlaying
+        );
+        assert_eq!(
+            Output::parse(r#"{"status": "stopped"}"#).unwrap(),
+            PlaybackStatus::NotPlaying
+        );
+        assert_eq!(Output::parse(" \n").unwrap(), PlaybackStatus::NotPlaying);
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        assert!(Output::parse(r#"{"status": "playing", "artist": "Kitty"}"#).is_err());
+        assert!(Output::parse(r#"{"status": "rewinding"}"#).is_err());
+        assert!(Output::parse("Kitty - Meow").is_err());
+    }
+}
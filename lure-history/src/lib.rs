@@ -0,0 +1,618 @@
+use std::path::Path;
+use std::sync::Mutex;
+
+use lure_types::{Scrobble, TrackInfo};
+use rusqlite::{Connection, params};
+
+/// Schema migrations, applied in order. The number of applied migrations
+/// is stored in the `user_version` pragma.
+const MIGRATIONS: &[&str] = &["
+    CREATE TABLE plays (
+        id INTEGER PRIMARY KEY,
+        profile TEXT NOT NULL,
+        service TEXT NOT NULL,
+        artist TEXT NOT NULL,
+        title TEXT NOT NULL,
+        album TEXT,
+        cover_url TEXT,
+        url TEXT,
+        started_at INTEGER NOT NULL,
+        ended_at INTEGER,
+        imported INTEGER NOT NULL DEFAULT 0
+    );
+    CREATE INDEX plays_started_at ON plays (started_at);
+    CREATE UNIQUE INDEX plays_imported ON plays (profile, service, started_at, artist, title)
+        WHERE imported = 1;
+"];
+
+/// How many seconds apart an imported scrobble and a recorded play of the
+/// same track can start and still be the same play. Recorded plays start
+/// when lure notices them, up to a check interval late.
+const IMPORT_MATCH_WINDOW: i64 = 5 * 60;
+
+/// The local listening history, stored in a SQLite database.
+///
+/// Every track played is stored with the time it started and ended, so that
+/// the history doesn't depend on the retention of the listening service.
+pub struct History {
+    connection: Mutex<Connection>,
+}
+
+/// The ID of a recorded play.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayId(i64);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Play {
+    pub profile: String,
+    pub service: String,
+    pub track: TrackInfo,
+    /// Unix timestamp of when the track started playing.
+    pub started_at: i64,
+    /// Unix timestamp of when the track stopped playing, or `None` if it's
+    /// still playing, or lure stopped before it could be recorded.
+    pub ended_at: Option<i64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArtistCount {
+    pub artist: String,
+    pub plays: i64,
+    /// Total listening time in seconds.
+    pub duration: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TrackCount {
+    pub track: TrackInfo,
+    pub plays: i64,
+    /// Total listening time in seconds.
+    pub duration: i64,
+}
+
+/// Filters for history queries.
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    /// Only include plays of this profile.
+    pub profile: Option<String>,
+    /// Only include plays started at or after this Unix timestamp.
+    pub from: Option<i64>,
+    /// Only include plays started before this Unix timestamp.
+    pub to: Option<i64>,
+    /// Maximum number of results.
+    pub limit: u32,
+}
+
+impl History {
+    /// Opens the database at `path`, creating it and its parent directories
+    /// if needed.
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self, Error> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    fn from_connection(mut connection: Connection) -> Result<Self, Error> {
+        let version: i64 = connection.pragma_query_value(None, "user_version", |row| row.get(0))?;
+
+        let transaction = connection.transaction()?;
+        for migration in MIGRATIONS
+            .iter()
+            .skip(usize::try_from(version).unwrap_or(0))
+        {
+            transaction.execute_batch(migration)?;
+        }
+        transaction.pragma_update(None, "user_version", MIGRATIONS.len() as i64)?;
+        transaction.commit()?;
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+
+    /// Records that `track` started playing.
+    pub fn start_play(
+        &self,
+        profile: &str,
+        service: &str,
+        track: &TrackInfo,
+        started_at: i64,
+    ) -> Result<PlayId, Error> {
+        let connection = self.connection()?;
+        connection.execute(
+            "INSERT INTO plays (profile, service, artist, title, album, cover_url, url, started_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                profile,
+                service,
+                track.artist,
+                track.title,
+                track.album,
+                track.cover_url,
+                track.url,
+                started_at
+            ],
+        )?;
+
+        Ok(PlayId(connection.last_insert_rowid()))
+    }
+
+    /// Records that the play `id` ended.
+    pub fn end_play(&self, id: PlayId, ended_at: i64) -> Result<(), Error> {
+        self.connection()?.execute(
+            "UPDATE plays SET ended_at = ?1 WHERE id = ?2",
+            params![ended_at, id.0],
+        )?;
+
+        Ok(())
+    }
+
+    /// Imports scrobbles from the history of a listening service. Only the
+    /// time they started is known. Scrobbles that were imported before are
+    /// skipped, so an import can be repeated, and so are scrobbles of plays
+    /// lure recorded itself. Returns the number of scrobbles imported.
+    pub fn import(
+        &self,
+        profile: &str,
+        service: &str,
+        scrobbles: &[Scrobble],
+    ) -> Result<usize, Error> {
+        let mut connection = self.connection()?;
+        let transaction = connection.transaction()?;
+
+        let mut imported = 0;
+        {
+            let mut statement = transaction.prepare(
+                "INSERT OR IGNORE INTO plays
+                    (profile, service, artist, title, album, cover_url, url, started_at, imported)
+                 SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, 1
+                 WHERE NOT EXISTS (
+                     SELECT 1 FROM plays
+                     WHERE profile = ?1 AND artist = ?3 AND title = ?4 AND imported = 0
+                       AND started_at BETWEEN ?8 - ?9 AND ?8 + ?9
+                 )",
+            )?;
+            for scrobble in scrobbles {
+                imported += statement.execute(params![
+                    profile,
+                    service,
+                    scrobble.track.artist,
+                    scrobble.track.title,
+                    scrobble.track.album,
+                    scrobble.track.cover_url,
+                    scrobble.track.url,
+                    scrobble.listened_at,
+                    IMPORT_MATCH_WINDOW
+                ])?;
+            }
+        }
+
+        transaction.commit()?;
+        drop(connection);
+
+        Ok(imported)
+    }
+
+    /// Returns the most recent plays, newest first.
+    pub fn plays(&self, query: &Query) -> Result<Vec<Play>, Error> {
+        self.select(
+            "SELECT profile, service, artist, title, album, cover_url, url, started_at, ended_at
+             FROM plays
+             WHERE (?1 IS NULL OR profile = ?1)
+               AND (?2 IS NULL OR started_at >= ?2)
+               AND (?3 IS NULL OR started_at < ?3)
+             ORDER BY started_at DESC, id DESC
+             LIMIT ?4",
+            query,
+            |row| {
+                Ok(Play {
+                    profile: row.get(0)?,
+                    service: row.get(1)?,
+                    track: TrackInfo {
+                        artist: row.get(2)?,
+                        title: row.get(3)?,
+                        album: row.get(4)?,
+                        cover_url: row.get(5)?,
+                        url: row.get(6)?,
+                    },
+                    started_at: row.get(7)?,
+                    ended_at: row.get(8)?,
+                })
+            },
+        )
+    }
+
+    /// Returns the most played artists, by play count.
+    pub fn top_artists(&self, query: &Query) -> Result<Vec<ArtistCount>, Error> {
+        self.select(
+            "SELECT artist, COUNT(*) AS plays,
+                    SUM(COALESCE(ended_at - started_at, 0)) AS duration
+             FROM plays
+             WHERE (?1 IS NULL OR profile = ?1)
+               AND (?2 IS NULL OR started_at >= ?2)
+               AND (?3 IS NULL OR started_at < ?3)
+             GROUP BY artist
+             ORDER BY plays DESC, duration DESC, artist
+             LIMIT ?4",
+            query,
+            |row| {
+                Ok(ArtistCount {
+                    artist: row.get(0)?,
+                    plays: row.get(1)?,
+                    duration: row.get(2)?,
+                })
+            },
+        )
+    }
+
+    /// Returns the most played tracks, by play count, with the album and
+    /// links of their latest play.
+    pub fn top_tracks(&self, query: &Query) -> Result<Vec<TrackCount>, Error> {
+        // With `MAX`, SQLite takes the other columns from the row of the
+        // maximum, so `album`, `cover_url` and `url` are of the latest play.
+        self.select(
+            "SELECT artist, title, COUNT(*) AS plays,
+                    SUM(COALESCE(ended_at - started_at, 0)) AS duration,
+                    album, cover_url, url, MAX(started_at)
+             FROM plays
+             WHERE (?1 IS NULL OR profile = ?1)
+               AND (?2 IS NULL OR started_at >= ?2)
+               AND (?3 IS NULL OR started_at < ?3)
+             GROUP BY artist, title
+             ORDER BY plays DESC, duration DESC, artist, title
+             LIMIT ?4",
+            query,
+            |row| {
+                Ok(TrackCount {
+                    track: TrackInfo {
+                        artist: row.get(0)?,
+                        title: row.get(1)?,
+                        album: row.get(4)?,
+                        cover_url: row.get(5)?,
+                        url: row.get(6)?,
+                    },
+                    plays: row.get(2)?,
+                    duration: row.get(3)?,
+                })
+            },
+        )
+    }
+
+    /// Returns how many times `track` was played by `profile`.
+    pub fn play_count(&self, profile: &str, track: &TrackInfo) -> Result<i64, Error> {
+        Ok(self.connection()?.query_row(
+            "SELECT COUNT(*) FROM plays WHERE profile = ?1 AND artist = ?2 AND title = ?3",
+            params![profile, track.artist, track.title],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Returns how many times `artist` was played by `profile`.
+    pub fn artist_play_count(&self, profile: &str, artist: &str) -> Result<i64, Error> {
+        Ok(self.connection()?.query_row(
+            "SELECT COUNT(*) FROM plays WHERE profile = ?1 AND artist = ?2",
+            params![profile, artist],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Returns the number of consecutive days, in local time, on which
+    /// `profile` played something, up to the day of the `now` Unix
+    /// timestamp. A streak isn't broken until a whole day passes without a
+    /// play, so a streak that ended yesterday still counts.
+    pub fn streak(&self, profile: &str, now: i64) -> Result<i64, Error> {
+        let connection = self.connection()?;
+        let today: i64 = connection.query_row(
+            "SELECT CAST(julianday(?1, 'unixepoch', 'localtime') AS INTEGER)",
+            params![now],
+            |row| row.get(0),
+        )?;
+        let mut statement = connection.prepare(
+            "SELECT DISTINCT CAST(julianday(started_at, 'unixepoch', 'localtime') AS INTEGER) AS day
+             FROM plays
+             WHERE profile = ?1 AND started_at <= ?2
+             ORDER BY day DESC",
+        )?;
+        let days = statement
+            .query_map(params![profile, now], |row| row.get::<_, i64>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        drop(statement);
+        drop(connection);
+
+        let Some(&latest) = days.first().filter(|&&latest| today - latest <= 1) else {
+            return Ok(0);
+        };
+
+        Ok(days
+            .iter()
+            .zip(0..)
+            .take_while(|&(&day, offset)| day == latest - offset)
+            .map(|(_, offset)| offset + 1)
+            .last()
+            .unwrap_or(0))
+    }
+
+    /// Runs a `SELECT` statement whose parameters are the filters of
+    /// `query`, in order.
+    fn select<T>(
+        &self,
+        sql: &str,
+        query: &Query,
+        f: impl FnMut(&rusqlite::Row<'_>) -> rusqlite::Result<T>,
+    ) -> Result<Vec<T>, Error> {
+        let connection = self.connection()?;
+        let mut statement = connection.prepare(sql)?;
+        let rows = statement
+            .query_map(params![query.profile, query.from, query.to, query.limit], f)?
+            .collect::<Result<_, _>>()?;
+        drop(statement);
+        drop(connection);
+
+        Ok(rows)
+    }
+
+    fn connection(&self) -> Result<std::sync::MutexGuard<'_, Connection>, Error> {
+        self.connection.lock().map_err(|_| Error::Poisoned)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Sqlite(#[from] rusqlite::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error("The history database lock is poisoned")]
+    Poisoned,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track(artist: &str, title: &str) -> TrackInfo {
+        TrackInfo {
+            artist: artist.to_string(),
+            title: title.to_string(),
+            album: None,
+            cover_url: None,
+            url: None,
+        }
+    }
+
+    fn history() -> History {
+        let history = History::open_in_memory().unwrap();
+
+        for (profile, artist, title, started_at, ended_at) in [
+            ("kitty", "Cat", "Meow", 100, 200),
+            ("kitty", "Cat", "Purr", 200, 260),
+            ("kitty", "Kitten", "Mrrp", 300, 330),
+            ("kitty", "Cat", "Meow", 400, 500),
+            ("puppy", "Dog", "Woof", 150, 250),
+        ] {
+            let id = history
+                .start_play(profile, "listenbrainz", &track(artist, title), started_at)
+                .unwrap();
+            history.end_play(id, ended_at).unwrap();
+        }
+
+        history
+    }
+
+    fn query(profile: Option<&str>, from: Option<i64>, to: Option<i64>) -> Query {
+        Query {
+            profile: profile.map(str::to_string),
+            from,
+            to,
+            limit: 10,
+        }
+    }
+
+    #[test]
+    fn test_plays() {
+        let history = history();
+
+        let plays = history
+            .plays(&query(Some("kitty"), Some(200), None))
+            .unwrap();
+
+        assert_eq!(
+            plays
+                .iter()
+                .map(|play| (play.track.title.as_str(), play.started_at))
+                .collect::<Vec<_>>(),
+            [("Meow", 400), ("Mrrp", 300), ("Purr", 200)]
+        );
+        assert_eq!(plays[0].ended_at, Some(500));
+    }
+
+    #[test]
+    fn test_top_artists() {
+        let history = history();
+
+        assert_eq!(
+            history.top_artists(&query(None, None, Some(400))).unwrap(),
+            [
+                ArtistCount {
+                    artist: "Cat".to_string(),
+                    plays: 2,
+                    duration: 160,
+                },
+                ArtistCount {
+                    artist: "Dog".to_string(),
+                    plays: 1,
+                    duration: 100,
+                },
+                ArtistCount {
+                    artist: "Kitten".to_string(),
+                    plays: 1,
+                    duration: 30,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_top_tracks() {
+        let history = history();
+
+        let tracks = history
+            .top_tracks(&query(Some("kitty"), None, None))
+            .unwrap();
+
+        assert_eq!(tracks[0].track, track("Cat", "Meow"));
+        assert_eq!(tracks[0].plays, 2);
+        assert_eq!(tracks[0].duration, 200);
+        assert_eq!(tracks.len(), 3);
+    }
+
+    #[test]
+    fn test_import_skips_duplicates() {
+        let history = History::open_in_memory().unwrap();
+        let scrobbles = [
+            Scrobble {
+                track: track("Cat", "Meow"),
+                listened_at: 100,
+            },
+            Scrobble {
+                track: track("Cat", "Purr"),
+                listened_at: 200,
+            },
+        ];
+
+        assert_eq!(history.import("kitty", "lastfm", &scrobbles).unwrap(), 2);
+        assert_eq!(history.import("kitty", "lastfm", &scrobbles).unwrap(), 0);
+        assert_eq!(
+            history.import("puppy", "lastfm", &scrobbles[..1]).unwrap(),
+            1
+        );
+
+        let plays = history.plays(&query(Some("kitty"), None, None)).unwrap();
+        assert_eq!(plays.len(), 2);
+        assert_eq!(plays[0].ended_at, None);
+    }
+
+    #[test]
+    fn test_import_skips_recorded_plays() {
+        let history = History::open_in_memory().unwrap();
+        let id = history
+            .start_play("kitty", "lastfm", &track("Cat", "Meow"), 130)
+            .unwrap();
+        history.end_play(id, 300).unwrap();
+        let scrobble = |artist, title, listened_at| Scrobble {
+            track: track(artist, title),
+            listened_at,
+        };
+
+        let imported = history
+            .import(
+                "kitty",
+                "lastfm",
+                &[
+                    // The recorded play, noticed 30 seconds late.
+                    scrobble("Cat", "Meow", 100),
+                    // The same track, played again later.
+                    scrobble("Cat", "Meow", 1000),
+                    scrobble("Cat", "Purr", 120),
+                ],
+            )
+            .unwrap();
+        assert_eq!(imported, 2);
+        assert_eq!(
+            history
+                .import("puppy", "lastfm", &[scrobble("Cat", "Meow", 100)])
+                .unwrap(),
+            1
+        );
+
+        let plays = history.plays(&query(Some("kitty"), None, None)).unwrap();
+        assert_eq!(
+            plays
+                .iter()
+                .map(|play| (play.track.title.as_str(), play.started_at))
+                .collect::<Vec<_>>(),
+            [("Meow", 1000), ("Meow", 130), ("Purr", 120)]
+        );
+        assert_eq!(plays[1].ended_at, Some(300));
+    }
+
+    #[test]
+    fn test_track_details() {
+        let history = History::open_in_memory().unwrap();
+        let detailed = |album: &str| TrackInfo {
+            album: Some(album.to_string()),
+            cover_url: Some(format!("https://example.com/{album}.jpg")),
+            url: Some("https://example.com/meow".to_string()),
+            ..track("Cat", "Meow")
+        };
+
+        history
+            .start_play("kitty", "lastfm", &detailed("Purr"), 100)
+            .unwrap();
+        history
+            .import(
+                "kitty",
+                "lastfm",
+                &[Scrobble {
+                    track: detailed("Mrrp"),
+                    listened_at: 1000,
+                }],
+            )
+            .unwrap();
+
+        let plays = history.plays(&query(Some("kitty"), None, None)).unwrap();
+        assert_eq!(plays[0].track, detailed("Mrrp"));
+        assert_eq!(plays[1].track, detailed("Purr"));
+
+        let tracks = history
+            .top_tracks(&query(Some("kitty"), None, None))
+            .unwrap();
+        assert_eq!(tracks[0].track, detailed("Mrrp"));
+        assert_eq!(tracks[0].plays, 2);
+    }
+
+    #[test]
+    fn test_play_counts() {
+        let history = history();
+
+        assert_eq!(
+            history.play_count("kitty", &track("Cat", "Meow")).unwrap(),
+            2
+        );
+        assert_eq!(
+            history.play_count("puppy", &track("Cat", "Meow")).unwrap(),
+            0
+        );
+        assert_eq!(history.artist_play_count("kitty", "Cat").unwrap(), 3);
+    }
+
+    #[test]
+    fn test_streak() {
+        const DAY: i64 = 86_400;
+        let history = History::open_in_memory().unwrap();
+        // Noon, so that the local time zone doesn't move plays across days.
+        let now = 20_000 * DAY + DAY / 2;
+
+        for days_ago in [1, 2, 3, 5] {
+            history
+                .start_play(
+                    "kitty",
+                    "lastfm",
+                    &track("Cat", "Meow"),
+                    now - days_ago * DAY,
+                )
+                .unwrap();
+        }
+
+        assert_eq!(history.streak("kitty", now).unwrap(), 3);
+        assert_eq!(history.streak("kitty", now + DAY).unwrap(), 0);
+        assert_eq!(history.streak("kitty", now - 3 * DAY).unwrap(), 1);
+        assert_eq!(history.streak("puppy", now).unwrap(), 0);
+    }
+}
@@ -1,6 +1,6 @@
-use secrecy::SecretString;
+use secrecy::{ExposeSecret as _, SecretString};
 
-#[derive(Debug, serde::Deserialize)]
+#[derive(Debug, Clone, serde::Deserialize)]
 pub struct Options {
     /// Enable the service.
     #[serde(default = "default_enable")]
@@ -14,6 +14,17 @@ pub struct Options {
     pub check_interval: u64,
 }
 
+impl PartialEq for Options {
+    fn eq(&self, other: &Self) -> bool {
+        self.enable == other.enable
+            && self.username == other.username
+            && self.api_key.expose_secret() == other.api_key.expose_secret()
+            && self.check_interval == other.check_interval
+    }
+}
+
+impl Eq for Options {}
+
 const fn default_enable() -> bool {
     false
 }
@@ -24,8 +35,6 @@ const fn default_check_interval() -> u64 {
 
 #[cfg(test)]
 mod tests {
-    use secrecy::ExposeSecret as _;
-
     use super::*;
 
     #[test]
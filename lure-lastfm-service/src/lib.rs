@@ -1,6 +1,6 @@
 use std::time::Duration;
 
-use lure_types::{PlaybackStatus, TrackInfo};
+use lure_types::{HistoryPage, PlaybackStatus, Scrobble, TrackInfo, cache::ResponseCache};
 use reqwest::{ClientBuilder, StatusCode, Url};
 use secrecy::ExposeSecret as _;
 
@@ -9,20 +9,45 @@ pub mod models;
 
 pub struct Service {
     http_client: reqwest::Client,
+    cache: ResponseCache<PlaybackStatus>,
     options: config::Options,
 }
 
 impl Service {
     pub fn try_new(options: config::Options) -> Result<Self, ServiceError> {
-        Ok(Self {
-            http_client: ClientBuilder::new().build()?,
+        Ok(Self::with_http_client(
             options,
-        })
+            ClientBuilder::new().build()?,
+        ))
+    }
+
+    /// Creates the service using an existing HTTP client, so that its
+    /// connection pool can be shared with other services.
+    pub fn with_http_client(options: config::Options, http_client: reqwest::Client) -> Self {
+        Self {
+            http_client,
+            cache: ResponseCache::default(),
+            options,
+        }
+    }
+
+    pub const fn options(&self) -> &config::Options {
+        &self.options
     }
 
     pub async fn poll(&self) -> Result<PlaybackStatus, ServiceError> {
         tokio::time::sleep(Duration::from_secs(self.options.check_interval)).await;
 
+        self.fetch().await
+    }
+
+    /// Fetches the current playback status without waiting for the check
+    /// interval.
+    pub async fn fetch(&self) -> Result<PlaybackStatus, ServiceError> {
+        if let Some(status) = self.cache.fresh(&self.options.username) {
+            return Ok(status);
+        }
+
         let url = Url::parse_with_params(
             "https://ws.audioscrobbler.com/2.0/",
             &[
@@ -35,29 +60,204 @@ impl Service {
         )
         .map_err(|error| ServiceError::Anyhow(error.into()))?;
 
-        let response = self
+        let mut response = self
             .http_client
-            .get(url)
+            .get(url.clone())
+            .headers(self.cache.conditional_headers(&self.options.username))
             .send()
-            .await?
-            .handle_user_friendly_error()
             .await?;
 
+        if response.status() == StatusCode::NOT_MODIFIED {
+            if let Some(status) = self
+                .cache
+                .revalidate(&self.options.username, response.headers())
+            {
+                return Ok(status);
+            }
+
+            // The cached entry is gone, so the response has nothing to
+            // refer to. Ask again without the validators.
+            response = self.http_client.get(url).send().await?;
+        }
+
+        let response = response.handle_user_friendly_error().await?;
+        let headers = response.headers().clone();
+
         let mut recent_tracks: models::user::get_recent_tracks::Data = response.json().await?;
 
-        if let Some(track) = recent_tracks.recenttracks.track.first_mut()
-            && track
-                .attr
-                .as_ref()
-                .is_some_and(|attr| attr.nowplaying.as_ref().is_some_and(|np| *np))
-        {
-            return Ok(PlaybackStatus::Playing(TrackInfo {
-                artist: std::mem::take(&mut track.artist.text),
-                title: std::mem::take(&mut track.name),
-            }));
+        let status = match recent_tracks.recenttracks.track.first_mut() {
+            Some(track)
+                if track
+                    .attr
+                    .as_ref()
+                    .is_some_and(|attr| attr.nowplaying.as_ref().is_some_and(|np| *np)) =>
+            {
+                PlaybackStatus::Playing(TrackInfo {
+                    album: track.album_title(),
+                    cover_url: track.cover_url(),
+                    url: track.url(),
+                    artist: std::mem::take(&mut track.artist.text),
+                    title: std::mem::take(&mut track.name),
+                })
+            }
+            _ => PlaybackStatus::NotPlaying,
+        };
+
+        self.cache
+            .store(&self.options.username, &headers, status.clone());
+
+        Ok(status)
+    }
+
+    /// Fetches a page of the scrobbles between the `from` and `to` Unix
+    /// timestamps, newest first. The cursor of the next page is its page
+    /// number.
+    pub async fn recent_tracks(
+        &self,
+        from: Option<i64>,
+        to: Option<i64>,
+        page: i64,
+    ) -> Result<HistoryPage, ServiceError> {
+        let page = page.to_string();
+        let from = from.map(|from| from.to_string());
+        let to = to.map(|to| to.to_string());
+
+        let mut params = vec![
+            ("method", "user.getrecenttracks"),
+            ("limit", "200"),
+            ("page", page.as_str()),
+        ];
+        if let Some(from) = &from {
+            params.push(("from", from));
+        }
+        if let Some(to) = &to {
+            params.push(("to", to));
         }
 
-        Ok(PlaybackStatus::NotPlaying)
+        let recent_tracks: models::user::get_recent_tracks::Data = self.get(&params).await?;
+        let recent_tracks = recent_tracks.recenttracks;
+
+        Ok(HistoryPage {
+            next: recent_tracks
+                .attr
+                .filter(|attr| attr.page < attr.total_pages)
+                .map(|attr| attr.page + 1),
+            // The track that is playing right now has no date, and isn't
+            // scrobbled yet.
+            scrobbles: recent_tracks
+                .track
+                .into_iter()
+                .filter_map(|mut track| {
+                    Some(Scrobble {
+                        listened_at: track.date.as_ref()?.uts,
+                        track: TrackInfo {
+                            album: track.album_title(),
+                            cover_url: track.cover_url(),
+                            url: track.url(),
+                            artist: track.artist.text,
+                            title: track.name,
+                        },
+                    })
+                })
+                .collect(),
+        })
+    }
+
+    /// Fetches how many times the user played `track`.
+    pub async fn track_play_count(&self, track: &TrackInfo) -> Result<Option<i64>, ServiceError> {
+        let data: models::track::get_info::Data = self
+            .get(&[
+                ("method", "track.getInfo"),
+                ("artist", &track.artist),
+                ("track", &track.title),
+                ("username", &self.options.username),
+            ])
+            .await?;
+
+        Ok(data.track.userplaycount)
+    }
+
+    /// Fetches how many times the user played `artist`.
+    pub async fn artist_play_count(&self, artist: &str) -> Result<Option<i64>, ServiceError> {
+        let data: models::artist::get_info::Data = self
+            .get(&[
+                ("method", "artist.getInfo"),
+                ("artist", artist),
+                ("username", &self.options.username),
+            ])
+            .await?;
+
+        Ok(data.artist.stats.userplaycount)
+    }
+
+    /// Fetches the most played artist of the user in `period`, one of
+    /// `overall`, `7day`, `1month`, `3month`, `6month` or `12month`.
+    pub async fn top_artist(&self, period: &str) -> Result<Option<String>, ServiceError> {
+        let data: models::user::get_top_artists::Data = self
+            .get(&[
+                ("method", "user.getTopArtists"),
+                ("period", period),
+                ("limit", "1"),
+            ])
+            .await?;
+
+        Ok(data
+            .topartists
+            .artist
+            .into_iter()
+            .next()
+            .map(|artist| artist.name))
+    }
+
+    /// Fetches the most played track of the user in `period`. See
+    /// [`Service::top_artist`] for the periods.
+    pub async fn top_track(&self, period: &str) -> Result<Option<TrackInfo>, ServiceError> {
+        let data: models::user::get_top_tracks::Data = self
+            .get(&[
+                ("method", "user.getTopTracks"),
+                ("period", period),
+                ("limit", "1"),
+            ])
+            .await?;
+
+        Ok(data
+            .toptracks
+            .track
+            .into_iter()
+            .next()
+            .map(|track| TrackInfo {
+                artist: track.artist.name,
+                title: track.name,
+                album: None,
+                cover_url: None,
+                url: None,
+            }))
+    }
+
+    /// Calls an API method with `params`, authenticated as the user.
+    async fn get<T: serde::de::DeserializeOwned>(
+        &self,
+        params: &[(&str, &str)],
+    ) -> Result<T, ServiceError> {
+        let url = Url::parse_with_params(
+            "https://ws.audioscrobbler.com/2.0/",
+            params.iter().copied().chain([
+                ("user", self.options.username.as_str()),
+                ("api_key", self.options.api_key.expose_secret()),
+                ("format", "json"),
+            ]),
+        )
+        .map_err(|error| ServiceError::Anyhow(error.into()))?;
+
+        Ok(self
+            .http_client
+            .get(url)
+            .send()
+            .await?
+            .handle_user_friendly_error()
+            .await?
+            .json()
+            .await?)
     }
 }
 
@@ -91,7 +291,32 @@ pub enum ServiceError {
     Anyhow(#[from] anyhow::Error),
 }
 
+impl APIError {
+    /// A short, stable name for the error variant.
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::AuthenticationFailed => "authentication_failed",
+            Self::OperationFailed => "operation_failed",
+            Self::InvalidAPIKey => "invalid_api_key",
+            Self::ServiceOffline => "service_offline",
+            Self::TemporaryError => "temporary_error",
+            Self::SuspendedAPIKey => "suspended_api_key",
+            Self::RateLimitExceeded => "rate_limit_exceeded",
+            Self::Unexpected(_) => "unexpected",
+        }
+    }
+}
+
 impl ServiceError {
+    /// A short, stable name for the error variant.
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::Api(error) => error.kind(),
+            Self::Reqwest(_) => "http",
+            Self::Anyhow(_) => "other",
+        }
+    }
+
     pub const fn is_fatal(&self) -> bool {
         matches!(
             self,
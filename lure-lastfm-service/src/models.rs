@@ -1,6 +1,46 @@
 use serde::Deserialize as _;
 
 pub mod user {
+    pub mod get_top_artists {
+        #[derive(Debug, serde::Deserialize)]
+        pub struct Data {
+            pub topartists: TopArtists,
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        pub struct TopArtists {
+            pub artist: Vec<Artist>,
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        pub struct Artist {
+            pub name: String,
+        }
+    }
+
+    pub mod get_top_tracks {
+        #[derive(Debug, serde::Deserialize)]
+        pub struct Data {
+            pub toptracks: TopTracks,
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        pub struct TopTracks {
+            pub track: Vec<Track>,
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        pub struct Track {
+            pub name: String,
+            pub artist: Artist,
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        pub struct Artist {
+            pub name: String,
+        }
+    }
+
     pub mod get_recent_tracks {
         #[derive(Debug, serde::Deserialize)]
         pub struct Data {
@@ -16,14 +56,80 @@ pub mod user {
         #[derive(Debug, serde::Deserialize)]
         pub struct RecentTracks {
             pub track: Vec<Track>,
+            #[serde(rename = "@attr")]
+            pub attr: Option<RecentTracksAttr>,
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        pub struct RecentTracksAttr {
+            #[serde(deserialize_with = "crate::models::number_from_string")]
+            pub page: i64,
+            #[serde(
+                rename = "totalPages",
+                deserialize_with = "crate::models::number_from_string"
+            )]
+            pub total_pages: i64,
         }
 
         #[derive(Debug, serde::Deserialize)]
         pub struct Track {
             pub artist: Artist,
             pub name: String,
+            #[serde(default)]
+            pub album: Option<Album>,
+            /// The cover art in increasing sizes.
+            #[serde(default)]
+            pub image: Vec<Image>,
+            /// The page of the track on Last.fm.
+            #[serde(default)]
+            pub url: String,
             #[serde(rename = "@attr")]
             pub attr: Option<TrackAttr>,
+            pub date: Option<Date>,
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        pub struct Album {
+            #[serde(rename = "#text")]
+            pub text: String,
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        pub struct Image {
+            #[serde(rename = "#text")]
+            pub text: String,
+        }
+
+        impl Track {
+            /// The title of the album, or `None` if it's unknown, which the
+            /// API represents with an empty string.
+            pub fn album_title(&mut self) -> Option<String> {
+                self.album
+                    .take()
+                    .map(|album| album.text)
+                    .filter(|title| !title.is_empty())
+            }
+
+            /// The URL of the largest cover art, or `None` if there is
+            /// none, which the API represents with empty URLs.
+            pub fn cover_url(&mut self) -> Option<String> {
+                self.image
+                    .drain(..)
+                    .map(|image| image.text)
+                    .rfind(|url| !url.is_empty())
+            }
+
+            /// The URL of the page of the track, or `None` if the API
+            /// didn't send one.
+            pub fn url(&mut self) -> Option<String> {
+                Some(std::mem::take(&mut self.url)).filter(|url| !url.is_empty())
+            }
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        pub struct Date {
+            #[serde(deserialize_with = "crate::models::number_from_string")]
+            pub uts: i64,
         }
 
         #[derive(Debug, serde::Deserialize)]
@@ -40,6 +146,41 @@ pub mod user {
     }
 }
 
+pub mod track {
+    pub mod get_info {
+        #[derive(Debug, serde::Deserialize)]
+        pub struct Data {
+            pub track: Track,
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        pub struct Track {
+            #[serde(default, deserialize_with = "crate::models::option_number_from_string")]
+            pub userplaycount: Option<i64>,
+        }
+    }
+}
+
+pub mod artist {
+    pub mod get_info {
+        #[derive(Debug, serde::Deserialize)]
+        pub struct Data {
+            pub artist: Artist,
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        pub struct Artist {
+            pub stats: Stats,
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        pub struct Stats {
+            #[serde(default, deserialize_with = "crate::models::option_number_from_string")]
+            pub userplaycount: Option<i64>,
+        }
+    }
+}
+
 fn bool_from_string<'de, D>(deserializer: D) -> Result<Option<bool>, D::Error>
 where
     D: serde::Deserializer<'de>,
@@ -51,3 +192,72 @@ where
         _ => None,
     })
 }
+
+/// Last.fm sends most numbers as strings, but not all of them.
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum Number {
+    Number(i64),
+    String(String),
+}
+
+fn number_from_string<'de, D>(deserializer: D) -> Result<i64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    match Number::deserialize(deserializer)? {
+        Number::Number(number) => Ok(number),
+        Number::String(string) => string.parse().map_err(serde::de::Error::custom),
+    }
+}
+
+fn option_number_from_string<'de, D>(deserializer: D) -> Result<Option<i64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    number_from_string(deserializer).map(Some)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_numbers_as_strings_or_numbers() {
+        let as_string: track::get_info::Track =
+            serde_yaml::from_str(r#"userplaycount: "12""#).unwrap();
+        let as_number: track::get_info::Track = serde_yaml::from_str("userplaycount: 12").unwrap();
+        let missing: track::get_info::Track = serde_yaml::from_str("{}").unwrap();
+
+        assert_eq!(as_string.userplaycount, Some(12));
+        assert_eq!(as_number.userplaycount, Some(12));
+        assert_eq!(missing.userplaycount, None);
+    }
+
+    #[test]
+    fn test_cover_and_track_urls() {
+        let yaml = r##"
+            artist: { "#text": Kitty }
+            name: Meow
+            url: https://www.last.fm/music/Kitty/_/Meow
+            image:
+                - { size: small, "#text": https://example.com/34s.png }
+                - { size: large, "#text": https://example.com/174s.png }
+                - { size: extralarge, "#text": "" }
+        "##;
+        let mut track: user::get_recent_tracks::Track = serde_yaml::from_str(yaml).unwrap();
+        let mut without_image: user::get_recent_tracks::Track =
+            serde_yaml::from_str(r##"{ artist: { "#text": Kitty }, name: Meow }"##).unwrap();
+
+        assert_eq!(
+            track.cover_url().as_deref(),
+            Some("https://example.com/174s.png")
+        );
+        assert_eq!(without_image.cover_url(), None);
+        assert_eq!(
+            track.url().as_deref(),
+            Some("https://www.last.fm/music/Kitty/_/Meow")
+        );
+        assert_eq!(without_image.url(), None);
+    }
+}
@@ -1,6 +1,6 @@
 use std::time::Duration;
 
-use lure_types::{PlaybackStatus, TrackInfo};
+use lure_types::{HistoryPage, PlaybackStatus, Scrobble, TrackInfo, cache::ResponseCache};
 use reqwest::{ClientBuilder, StatusCode};
 
 pub mod config;
@@ -8,52 +8,233 @@ pub mod models;
 
 pub struct Service {
     http_client: reqwest::Client,
+    cache: ResponseCache<PlaybackStatus>,
     options: config::Options,
 }
 
 impl Service {
     pub fn try_new(options: config::Options) -> Result<Self, ServiceError> {
-        Ok(Self {
-            http_client: ClientBuilder::new().build()?,
+        Ok(Self::with_http_client(
             options,
-        })
+            ClientBuilder::new().build()?,
+        ))
+    }
+
+    /// Creates the service using an existing HTTP client, so that its
+    /// connection pool can be shared with other services.
+    pub fn with_http_client(options: config::Options, http_client: reqwest::Client) -> Self {
+        Self {
+            http_client,
+            cache: ResponseCache::default(),
+            options,
+        }
+    }
+
+    pub const fn options(&self) -> &config::Options {
+        &self.options
     }
 
     pub async fn poll(&self) -> Result<PlaybackStatus, ServiceError> {
         tokio::time::sleep(Duration::from_secs(self.options.check_interval)).await;
 
+        self.fetch().await
+    }
+
+    /// Fetches the current playback status without waiting for the check
+    /// interval.
+    pub async fn fetch(&self) -> Result<PlaybackStatus, ServiceError> {
+        if let Some(status) = self.cache.fresh(&self.options.username) {
+            return Ok(status);
+        }
+
         let url = format!(
             "{}/1/user/{}/playing-now",
             self.options.api_url, self.options.username
         );
 
-        let response = self
+        let mut response = self
             .http_client
-            .get(url)
+            .get(&url)
+            .headers(self.cache.conditional_headers(&self.options.username))
             .send()
-            .await?
-            .handle_user_friendly_error()
             .await?;
 
+        if response.status() == StatusCode::NOT_MODIFIED {
+            if let Some(status) = self
+                .cache
+                .revalidate(&self.options.username, response.headers())
+            {
+                return Ok(status);
+            }
+
+            // The cached entry is gone, so the response has nothing to
+            // refer to. Ask again without the validators.
+            response = self.http_client.get(url).send().await?;
+        }
+
+        let response = response.handle_user_friendly_error().await?;
+        let headers = response.headers().clone();
+
         let mut data: models::user::playing_now::Data = response.json().await?;
 
-        if let Some(track) = data.payload.listens.first_mut()
-            && track.playing_now
-        {
-            return Ok(PlaybackStatus::Playing(TrackInfo {
+        let status = match data.payload.listens.first_mut() {
+            Some(track) if track.playing_now => PlaybackStatus::Playing(TrackInfo {
                 artist: std::mem::take(&mut track.track_metadata.artist_name),
                 title: std::mem::take(&mut track.track_metadata.track_name),
-            }));
+                album: track.track_metadata.release_name.take(),
+                cover_url: track.track_metadata.cover_url(),
+                url: track.track_metadata.url(),
+            }),
+            _ => PlaybackStatus::NotPlaying,
+        };
+
+        self.cache
+            .store(&self.options.username, &headers, status.clone());
+
+        Ok(status)
+    }
+
+    /// Fetches a page of the listens before the `max_ts` Unix timestamp,
+    /// newest first. The cursor of the next page is its `max_ts`.
+    ///
+    /// The API doesn't allow `min_ts` together with `max_ts`, so listens at
+    /// or before `min_ts` are filtered out here, and end the pagination.
+    pub async fn listens(
+        &self,
+        min_ts: Option<i64>,
+        max_ts: Option<i64>,
+    ) -> Result<HistoryPage, ServiceError> {
+        let mut url = format!(
+            "{}/1/user/{}/listens?count={LISTENS_PER_PAGE}",
+            self.options.api_url, self.options.username
+        );
+        if let Some(max_ts) = max_ts {
+            url.push_str(&format!("&max_ts={max_ts}"));
         }
 
-        Ok(PlaybackStatus::NotPlaying)
+        let data: models::user::listens::Data = self
+            .http_client
+            .get(url)
+            .send()
+            .await?
+            .handle_user_friendly_error()
+            .await?
+            .json()
+            .await?;
+
+        Ok(history_page(data.payload, min_ts, max_ts))
+    }
+
+    /// Fetches the most listened artist of the user in `range`, like
+    /// `this_week` or `all_time`. Returns `None` if the statistics weren't
+    /// calculated yet.
+    pub async fn top_artist(&self, range: &str) -> Result<Option<String>, ServiceError> {
+        let data: Option<models::stats::user::artists::Data> =
+            self.get_stats("artists", range).await?;
+
+        Ok(data
+            .and_then(|data| data.payload.artists.into_iter().next())
+            .map(|artist| artist.artist_name))
+    }
+
+    /// Fetches the most listened track of the user in `range`. See
+    /// [`Service::top_artist`] for the ranges.
+    pub async fn top_track(&self, range: &str) -> Result<Option<TrackInfo>, ServiceError> {
+        let data: Option<models::stats::user::recordings::Data> =
+            self.get_stats("recordings", range).await?;
+
+        Ok(data
+            .and_then(|data| data.payload.recordings.into_iter().next())
+            .map(|recording| TrackInfo {
+                artist: recording.artist_name,
+                title: recording.track_name,
+                album: recording.release_name,
+                cover_url: None,
+                url: None,
+            }))
+    }
+
+    /// Fetches the top `entity` statistics of the user. The API responds
+    /// with `204 No Content` until the statistics are calculated.
+    async fn get_stats<T: serde::de::DeserializeOwned>(
+        &self,
+        entity: &str,
+        range: &str,
+    ) -> Result<Option<T>, ServiceError> {
+        let response = self
+            .http_client
+            .get(format!(
+                "{}/1/stats/user/{}/{entity}?range={range}&count=1",
+                self.options.api_url, self.options.username
+            ))
+            .send()
+            .await?
+            .handle_user_friendly_error()
+            .await?;
+
+        if response.status() == StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+
+        Ok(Some(response.json().await?))
     }
 }
 
+/// Builds a page of the history from listens fetched before `max_ts`.
+///
+/// `max_ts` is exclusive, so the next page starts right after the oldest
+/// listen, and the listens sharing its timestamp are left to that page, so
+/// that none is skipped or returned twice. A page whose listens all share
+/// one timestamp can't be split, so the next page starts before it.
+fn history_page(
+    payload: models::user::listens::Payload,
+    min_ts: Option<i64>,
+    max_ts: Option<i64>,
+) -> HistoryPage {
+    let oldest = payload.listens.last().map(|listen| listen.listened_at);
+    let next = oldest
+        .filter(|oldest| {
+            payload.count >= LISTENS_PER_PAGE && min_ts.is_none_or(|min_ts| *oldest > min_ts)
+        })
+        .map(|oldest| {
+            if max_ts == Some(oldest + 1) {
+                oldest
+            } else {
+                oldest + 1
+            }
+        });
+    let deferred = oldest.filter(|oldest| next == Some(oldest + 1));
+
+    HistoryPage {
+        next,
+        scrobbles: payload
+            .listens
+            .into_iter()
+            .filter(|listen| min_ts.is_none_or(|min_ts| listen.listened_at > min_ts))
+            .filter(|listen| Some(listen.listened_at) != deferred)
+            .map(|listen| Scrobble {
+                listened_at: listen.listened_at,
+                track: TrackInfo {
+                    cover_url: listen.track_metadata.cover_url(),
+                    url: listen.track_metadata.url(),
+                    artist: listen.track_metadata.artist_name,
+                    title: listen.track_metadata.track_name,
+                    album: listen.track_metadata.release_name,
+                },
+            })
+            .collect(),
+    }
+}
+
+/// The maximum number of listens the API returns per request.
+const LISTENS_PER_PAGE: usize = 1000;
+
 #[derive(thiserror::Error, Debug)]
 pub enum APIError {
     #[error("User not found.")]
     NotFound,
+    #[error("Rate limit exceeded, resets in {0} seconds.")]
+    RateLimitExceeded(u64),
     #[error("Unexpected API error: {0}")]
     Unexpected(String),
 }
@@ -66,7 +247,26 @@ pub enum ServiceError {
     Reqwest(#[from] reqwest::Error),
 }
 
+impl APIError {
+    /// A short, stable name for the error variant.
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::NotFound => "not_found",
+            Self::RateLimitExceeded(_) => "rate_limit_exceeded",
+            Self::Unexpected(_) => "unexpected",
+        }
+    }
+}
+
 impl ServiceError {
+    /// A short, stable name for the error variant.
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::Api(error) => error.kind(),
+            Self::Reqwest(_) => "http",
+        }
+    }
+
     pub const fn is_fatal(&self) -> bool {
         matches!(self, Self::Api(APIError::NotFound))
     }
@@ -79,11 +279,84 @@ trait HandleUserFriendlyError: Sized {
 impl HandleUserFriendlyError for reqwest::Response {
     async fn handle_user_friendly_error(self) -> Result<Self, ServiceError> {
         match self.status() {
-            StatusCode::OK => Ok(self),
+            StatusCode::OK | StatusCode::NO_CONTENT => Ok(self),
             StatusCode::NOT_FOUND => Err(APIError::NotFound.into()),
+            StatusCode::TOO_MANY_REQUESTS => Err(APIError::RateLimitExceeded(
+                self.headers()
+                    .get("X-RateLimit-Reset-In")
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse().ok())
+                    .unwrap_or(1),
+            )
+            .into()),
             _ => Err(
                 APIError::Unexpected(format!("Unexpected HTTP status: {}", self.status())).into(),
             ),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A full page of listens at the given timestamps, newest first.
+    fn payload(timestamps: &[i64]) -> models::user::listens::Payload {
+        let listens: Vec<String> = timestamps
+            .iter()
+            .map(|listened_at| {
+                format!(
+                    "{{listened_at: {listened_at}, track_metadata: {{artist_name: Kitty, track_name: Meow}}}}"
+                )
+            })
+            .collect();
+
+        serde_yaml::from_str(&format!(
+            "{{count: {LISTENS_PER_PAGE}, listens: [{}]}}",
+            listens.join(", ")
+        ))
+        .unwrap()
+    }
+
+    fn timestamps(page: &HistoryPage) -> Vec<i64> {
+        page.scrobbles
+            .iter()
+            .map(|scrobble| scrobble.listened_at)
+            .collect()
+    }
+
+    #[test]
+    fn test_history_page_leaves_the_oldest_timestamp_to_the_next_page() {
+        let page = history_page(payload(&[300, 200, 100, 100]), None, None);
+
+        assert_eq!(timestamps(&page), [300, 200]);
+        assert_eq!(page.next, Some(101));
+    }
+
+    #[test]
+    fn test_history_page_with_a_single_timestamp() {
+        let page = history_page(payload(&[100, 100]), None, Some(101));
+
+        assert_eq!(timestamps(&page), [100, 100]);
+        assert_eq!(page.next, Some(100));
+    }
+
+    #[test]
+    fn test_history_page_stops_at_min_ts() {
+        let page = history_page(payload(&[300, 200, 100]), Some(200), None);
+
+        assert_eq!(timestamps(&page), [300]);
+        assert_eq!(page.next, None);
+    }
+
+    #[test]
+    fn test_history_page_last_page() {
+        let mut payload = payload(&[300, 200, 200]);
+        payload.count = 3;
+
+        let page = history_page(payload, None, None);
+
+        assert_eq!(timestamps(&page), [300, 200, 200]);
+        assert_eq!(page.next, None);
+    }
+}
@@ -20,6 +20,128 @@ pub mod user {
         pub struct TrackMetadata {
             pub artist_name: String,
             pub track_name: String,
+            #[serde(default)]
+            pub release_name: Option<String>,
+            #[serde(default)]
+            pub additional_info: Option<AdditionalInfo>,
+            #[serde(default)]
+            pub mbid_mapping: Option<MbidMapping>,
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        pub struct AdditionalInfo {
+            #[serde(default)]
+            pub release_mbid: Option<String>,
+            /// The page of the track on the service the listen comes from.
+            #[serde(default)]
+            pub origin_url: Option<String>,
+        }
+
+        /// The MusicBrainz entities the listen was matched to.
+        #[derive(Debug, serde::Deserialize)]
+        pub struct MbidMapping {
+            /// The release the Cover Art Archive has cover art of.
+            #[serde(default)]
+            pub caa_release_mbid: Option<String>,
+            #[serde(default)]
+            pub recording_mbid: Option<String>,
+        }
+
+        impl TrackMetadata {
+            /// The URL of the cover art on the Cover Art Archive, from the
+            /// release the listen was matched to, or the one submitted with
+            /// it. The cover art may not exist.
+            pub fn cover_url(&self) -> Option<String> {
+                self.mbid_mapping
+                    .as_ref()
+                    .and_then(|mapping| mapping.caa_release_mbid.as_deref())
+                    .or_else(|| {
+                        self.additional_info
+                            .as_ref()
+                            .and_then(|info| info.release_mbid.as_deref())
+                    })
+                    .filter(|mbid| !mbid.is_empty())
+                    .map(|mbid| format!("https://coverartarchive.org/release/{mbid}/front-250"))
+            }
+
+            /// The URL of the page of the track on the service the listen
+            /// comes from, or on MusicBrainz if the listen was matched to a
+            /// recording.
+            pub fn url(&self) -> Option<String> {
+                self.additional_info
+                    .as_ref()
+                    .and_then(|info| info.origin_url.clone())
+                    .filter(|url| !url.is_empty())
+                    .or_else(|| {
+                        self.mbid_mapping
+                            .as_ref()
+                            .and_then(|mapping| mapping.recording_mbid.as_deref())
+                            .filter(|mbid| !mbid.is_empty())
+                            .map(|mbid| format!("https://musicbrainz.org/recording/{mbid}"))
+                    })
+            }
+        }
+    }
+
+    pub mod listens {
+        pub use super::playing_now::TrackMetadata;
+
+        #[derive(Debug, serde::Deserialize)]
+        pub struct Data {
+            pub payload: Payload,
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        pub struct Payload {
+            pub count: usize,
+            pub listens: Vec<Listen>,
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        pub struct Listen {
+            pub listened_at: i64,
+            pub track_metadata: TrackMetadata,
+        }
+    }
+}
+
+pub mod stats {
+    pub mod user {
+        pub mod artists {
+            #[derive(Debug, serde::Deserialize)]
+            pub struct Data {
+                pub payload: Payload,
+            }
+
+            #[derive(Debug, serde::Deserialize)]
+            pub struct Payload {
+                pub artists: Vec<Artist>,
+            }
+
+            #[derive(Debug, serde::Deserialize)]
+            pub struct Artist {
+                pub artist_name: String,
+            }
+        }
+
+        pub mod recordings {
+            #[derive(Debug, serde::Deserialize)]
+            pub struct Data {
+                pub payload: Payload,
+            }
+
+            #[derive(Debug, serde::Deserialize)]
+            pub struct Payload {
+                pub recordings: Vec<Recording>,
+            }
+
+            #[derive(Debug, serde::Deserialize)]
+            pub struct Recording {
+                pub artist_name: String,
+                pub track_name: String,
+                #[serde(default)]
+                pub release_name: Option<String>,
+            }
         }
     }
 }
@@ -0,0 +1,155 @@
+use std::collections::VecDeque;
+
+use futures::{SinkExt as _, StreamExt as _};
+use lure_stoat_models::events::{ClientEvent, ServerEvent};
+use tokio::net::TcpStream;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, tungstenite::Message};
+
+use crate::{APIError, Error};
+
+/// A connection to the events WebSocket, which receives the messages and
+/// the other changes as they happen.
+pub struct EventStream {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    /// Events received in bulk, not returned yet.
+    pending: VecDeque<ServerEvent>,
+}
+
+impl EventStream {
+    /// Connects to the events WebSocket at `ws_url`, as returned by
+    /// [`crate::Client::get_config`], and authenticates with `token`, either
+    /// a session or a bot token.
+    pub async fn connect(ws_url: &str, token: &str) -> Result<Self, Error> {
+        let (socket, _) =
+            tokio_tungstenite::connect_async(format!("{ws_url}?version=1&format=json")).await?;
+
+        let mut stream = Self {
+            socket,
+            pending: VecDeque::new(),
+        };
+        stream
+            .send(&ClientEvent::Authenticate {
+                token: token.to_owned(),
+            })
+            .await?;
+
+        loop {
+            match stream.next().await? {
+                ServerEvent::Authenticated => return Ok(stream),
+                ServerEvent::Error { error } if error == "InvalidSession" => {
+                    return Err(APIError::AuthenticationFailed.into());
+                }
+                ServerEvent::Error { error } => return Err(APIError::Unknown(error).into()),
+                _ => {}
+            }
+        }
+    }
+
+    /// Waits for the next event. Bulk events are returned one by one.
+    ///
+    /// Cancel safe, so it can be raced against [`EventStream::ping`].
+    pub async fn next(&mut self) -> Result<ServerEvent, Error> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Ok(event);
+            }
+
+            let text = match self.socket.next().await.transpose()? {
+                Some(Message::Text(text)) => text,
+                Some(Message::Close(_)) | None => return Err(Error::Closed),
+                // Pings are answered by the socket itself.
+                Some(_) => continue,
+            };
+
+            match serde_json::from_str(&text)? {
+                ServerEvent::Bulk { v } => self.pending.extend(v),
+                event => return Ok(event),
+            }
+        }
+    }
+
+    /// Pings the server, which closes the connection if it doesn't get
+    /// pinged for a while.
+    pub async fn ping(&mut self) -> Result<(), Error> {
+        self.send(&ClientEvent::Ping { data: 0 }).await
+    }
+
+    async fn send(&mut self, event: &ClientEvent) -> Result<(), Error> {
+        self.socket
+            .send(Message::text(serde_json::to_string(event)?))
+            .await?;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    /// Serves the events WebSocket, expecting lure to authenticate, then
+    /// sending `events`.
+    async fn serve(events: &'static [&'static str]) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+
+            let Some(Ok(Message::Text(authenticate))) = socket.next().await else {
+                panic!("lure didn't authenticate");
+            };
+            assert_eq!(
+                authenticate.as_str(),
+                r#"{"type":"Authenticate","token":"meow"}"#
+            );
+
+            for event in events {
+                socket.send(Message::text(*event)).await.unwrap();
+            }
+            // Keeps the connection open until lure is done with it.
+            while socket.next().await.is_some() {}
+        });
+
+        format!("ws://{address}/events")
+    }
+
+    #[tokio::test]
+    async fn test_bulk_events_are_split_up() {
+        let url = serve(&[
+            r#"{"type": "Authenticated"}"#,
+            r#"{"type": "Bulk", "v": [{"type": "Ready", "users": []}, {"type": "Pong", "data": 0}]}"#,
+            r#"{"type": "Pong", "data": 1}"#,
+        ])
+        .await;
+
+        let mut stream = EventStream::connect(&url, "meow").await.unwrap();
+
+        assert!(matches!(stream.next().await.unwrap(), ServerEvent::Ready));
+        assert!(matches!(stream.next().await.unwrap(), ServerEvent::Pong));
+        assert!(matches!(stream.next().await.unwrap(), ServerEvent::Pong));
+    }
+
+    #[tokio::test]
+    async fn test_invalid_session() {
+        let url = serve(&[r#"{"type": "Error", "error": "InvalidSession"}"#]).await;
+
+        assert!(matches!(
+            EventStream::connect(&url, "meow").await,
+            Err(Error::ApiError(APIError::AuthenticationFailed))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_other_error() {
+        let url = serve(&[r#"{"type": "Error", "error": "InternalError"}"#]).await;
+
+        assert!(matches!(
+            EventStream::connect(&url, "meow").await,
+            Err(Error::ApiError(APIError::Unknown(error))) if error == "InternalError"
+        ));
+    }
+}
@@ -1,48 +1,84 @@
 use std::{future::Future, str::FromStr as _};
 
 use lure_stoat_models::{
-    Authentication,
-    schemas::user::{DataEditUser, FieldsUser, User, UserStatus},
+    Authentication, paths,
+    schemas::{
+        channel::Channel,
+        message::{DataEditMessage, DataMessageSend, Message},
+        user::{DataEditUser, FieldsUser, User, UserProfile, UserStatus},
+    },
 };
 use reqwest::{
     StatusCode,
     header::{HeaderMap, HeaderName, HeaderValue},
 };
 
+pub mod events;
+
+pub use events::EventStream;
+
 pub struct Client {
     http_client: reqwest::Client,
     base_url: String,
+    headers: HeaderMap,
 }
 
 impl Client {
     pub fn try_new(api_url: String, authentication: &Authentication) -> Result<Self, Error> {
+        Self::with_http_client(reqwest::Client::builder().build()?, api_url, authentication)
+    }
+
+    /// Creates the client using an existing HTTP client, so that its
+    /// connection pool can be shared with other clients. The authentication
+    /// headers are sent with every request instead of being set as default
+    /// headers.
+    pub fn with_http_client(
+        http_client: reqwest::Client,
+        api_url: String,
+        authentication: &Authentication,
+    ) -> Result<Self, Error> {
         let headers = HeaderMap::from_iter([(
             HeaderName::from_str(authentication.header())?,
             HeaderValue::from_str(&authentication.value())?,
         )]);
 
-        let client = reqwest::Client::builder()
-            .default_headers(headers)
-            .build()?;
-
         Ok(Self {
-            http_client: client,
+            http_client,
             base_url: api_url,
+            headers,
         })
     }
 
-    pub async fn get_status_text(&self) -> Result<Option<String>, Error> {
-        let response: User = self
+    /// Fetches the configuration of the instance, which has the URL of the
+    /// events WebSocket.
+    pub async fn get_config(&self) -> Result<paths::root::ResponseBody, Error> {
+        Ok(self
+            .http_client
+            .get(format!("{}/", self.base_url))
+            .send()
+            .await?
+            .handle_return_error()
+            .await?
+            .json()
+            .await?)
+    }
+
+    /// Fetches the authenticated user.
+    pub async fn get_self(&self) -> Result<User, Error> {
+        Ok(self
             .http_client
             .get(format!("{}/users/@me", self.base_url))
+            .headers(self.headers.clone())
             .send()
             .await?
             .handle_return_error()
             .await?
             .json()
-            .await?;
+            .await?)
+    }
 
-        let status = response.status.and_then(|status| status.text);
+    pub async fn get_status_text(&self) -> Result<Option<String>, Error> {
+        let status = self.get_self().await?.status.and_then(|status| status.text);
 
         Ok(status)
     }
@@ -62,9 +98,152 @@ impl Client {
             },
         );
 
+        self.edit_user(&data).await
+    }
+
+    /// Edits the authenticated user.
+    pub async fn edit_user(&self, data: &DataEditUser) -> Result<(), Error> {
         self.http_client
             .patch(format!("{}/users/@me", self.base_url))
-            .json(&data)
+            .headers(self.headers.clone())
+            .json(data)
+            .send()
+            .await?
+            .handle_return_error()
+            .await?;
+
+        Ok(())
+    }
+
+    /// Fetches the profile of the user `user_id`, with their bio and
+    /// background.
+    pub async fn get_profile(&self, user_id: &str) -> Result<UserProfile, Error> {
+        Ok(self
+            .http_client
+            .get(format!("{}/users/{user_id}/profile", self.base_url))
+            .headers(self.headers.clone())
+            .send()
+            .await?
+            .handle_return_error()
+            .await?
+            .json()
+            .await?)
+    }
+
+    /// Uploads a file to the `tag` bucket of the file server at
+    /// `autumn_url`, as returned by [`Client::get_config`], and returns its
+    /// ID.
+    pub async fn upload_file(
+        &self,
+        autumn_url: &str,
+        tag: &str,
+        filename: String,
+        content_type: &str,
+        bytes: Vec<u8>,
+    ) -> Result<String, Error> {
+        let part = reqwest::multipart::Part::bytes(bytes)
+            .file_name(filename)
+            .mime_str(content_type)?;
+        let response: paths::autumn::upload::ResponseBody = self
+            .http_client
+            .post(format!("{autumn_url}/{tag}"))
+            .headers(self.headers.clone())
+            .multipart(reqwest::multipart::Form::new().part("file", part))
+            .send()
+            .await?
+            .handle_return_error()
+            .await?
+            .json()
+            .await?;
+
+        Ok(response.id)
+    }
+
+    /// Downloads the file `file_id` of the `tag` bucket of the file server
+    /// at `autumn_url`.
+    pub async fn download_file(
+        &self,
+        autumn_url: &str,
+        tag: &str,
+        file_id: &str,
+    ) -> Result<Vec<u8>, Error> {
+        Ok(self
+            .http_client
+            .get(format!("{autumn_url}/{tag}/{file_id}"))
+            .send()
+            .await?
+            .handle_return_error()
+            .await?
+            .bytes()
+            .await?
+            .to_vec())
+    }
+
+    /// Fetches the channel `channel_id`, which fails if the user can't see
+    /// it.
+    pub async fn get_channel(&self, channel_id: &str) -> Result<Channel, Error> {
+        Ok(self
+            .http_client
+            .get(format!("{}/channels/{channel_id}", self.base_url))
+            .headers(self.headers.clone())
+            .send()
+            .await?
+            .handle_return_error()
+            .await?
+            .json()
+            .await?)
+    }
+
+    /// Sends a message to the channel `channel_id`.
+    pub async fn send_message(
+        &self,
+        channel_id: &str,
+        data: &DataMessageSend,
+    ) -> Result<Message, Error> {
+        Ok(self
+            .http_client
+            .post(format!("{}/channels/{channel_id}/messages", self.base_url))
+            .headers(self.headers.clone())
+            .json(data)
+            .send()
+            .await?
+            .handle_return_error()
+            .await?
+            .json()
+            .await?)
+    }
+
+    /// Edits the message `message_id` of the channel `channel_id`.
+    pub async fn edit_message(
+        &self,
+        channel_id: &str,
+        message_id: &str,
+        data: &DataEditMessage,
+    ) -> Result<Message, Error> {
+        Ok(self
+            .http_client
+            .patch(format!(
+                "{}/channels/{channel_id}/messages/{message_id}",
+                self.base_url
+            ))
+            .headers(self.headers.clone())
+            .json(data)
+            .send()
+            .await?
+            .handle_return_error()
+            .await?
+            .json()
+            .await?)
+    }
+
+    /// Deletes the message `message_id` of the channel `channel_id`.
+    pub async fn delete_message(&self, channel_id: &str, message_id: &str) -> Result<(), Error> {
+        self.http_client
+            .delete(format!(
+                "{}/channels/{channel_id}/messages/{message_id}",
+                self.base_url
+            ))
+            .headers(self.headers.clone())
             .send()
             .await?
             .handle_return_error()
@@ -84,6 +263,12 @@ pub enum Error {
     HeaderValue(#[from] reqwest::header::InvalidHeaderValue),
     #[error(transparent)]
     Reqwest(#[from] reqwest::Error),
+    #[error(transparent)]
+    WebSocket(#[from] tokio_tungstenite::tungstenite::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error("Stoat events connection closed.")]
+    Closed,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -92,10 +277,39 @@ pub enum APIError {
     AuthenticationFailed,
     #[error("Stoat API rate limit exceeded.")]
     RateLimitExceeded(u64),
+    #[error("Stoat API couldn't find the requested resource.")]
+    NotFound,
     #[error("Stoat API returned an unexpected error: {0}")]
     Unknown(String),
 }
 
+impl Error {
+    /// A short, stable name for the error variant.
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::ApiError(error) => error.kind(),
+            Self::HeaderName(_) => "invalid_header_name",
+            Self::HeaderValue(_) => "invalid_header_value",
+            Self::Reqwest(_) => "http",
+            Self::WebSocket(_) => "websocket",
+            Self::Json(_) => "json",
+            Self::Closed => "closed",
+        }
+    }
+}
+
+impl APIError {
+    /// A short, stable name for the error variant.
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::AuthenticationFailed => "authentication_failed",
+            Self::RateLimitExceeded(_) => "rate_limit_exceeded",
+            Self::NotFound => "not_found",
+            Self::Unknown(_) => "unknown",
+        }
+    }
+}
+
 pub trait HandleAPIError: Sized {
     type Error: std::error::Error;
 
@@ -107,8 +321,9 @@ impl HandleAPIError for reqwest::Response {
 
     async fn handle_return_error(self) -> Result<Self, Self::Error> {
         match self.status() {
-            StatusCode::OK => Ok(self),
+            StatusCode::OK | StatusCode::NO_CONTENT => Ok(self),
             StatusCode::UNAUTHORIZED => Err(APIError::AuthenticationFailed),
+            StatusCode::NOT_FOUND => Err(APIError::NotFound),
             StatusCode::TOO_MANY_REQUESTS => {
                 let retry_after = self
                     .headers()
@@ -125,3 +340,67 @@ impl HandleAPIError for reqwest::Response {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    /// Returns the request sent by `request` to a server answering `404 Not
+    /// Found`.
+    async fn request_sent<F: Future>(request: impl FnOnce(String) -> F) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            while !request.ends_with(b"\r\n\r\n") {
+                request.push(stream.read_u8().await.unwrap());
+            }
+            stream
+                .write_all(b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n")
+                .await
+                .unwrap();
+
+            String::from_utf8(request).unwrap()
+        });
+        request(format!("http://{address}")).await;
+
+        server.await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_bot_token_header() {
+        let request = request_sent(|url| async move {
+            let client =
+                Client::try_new(url, &Authentication::BotToken(String::from("meow"))).unwrap();
+            assert!(matches!(
+                client.get_self().await,
+                Err(Error::ApiError(APIError::NotFound))
+            ));
+        })
+        .await;
+
+        assert!(request.starts_with("GET /users/@me "), "{request}");
+        assert!(request.contains("\r\nx-bot-token: meow\r\n"), "{request}");
+        assert!(!request.contains("x-session-token"), "{request}");
+    }
+
+    #[tokio::test]
+    async fn test_session_token_header() {
+        let request = request_sent(|url| async move {
+            let client =
+                Client::try_new(url, &Authentication::SessionToken(String::from("meow"))).unwrap();
+            let _ = client.get_self().await;
+        })
+        .await;
+
+        assert!(
+            request.contains("\r\nx-session-token: meow\r\n"),
+            "{request}"
+        );
+    }
+}
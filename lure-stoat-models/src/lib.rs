@@ -1,4 +1,40 @@
 pub mod paths {
+    pub mod root {
+        /// The configuration of the Stoat instance.
+        #[derive(Debug, serde::Deserialize)]
+        pub struct ResponseBody {
+            /// URL of the events WebSocket.
+            pub ws: String,
+            #[serde(default)]
+            pub features: Features,
+        }
+
+        #[derive(Debug, Default, serde::Deserialize)]
+        pub struct Features {
+            /// The file server.
+            #[serde(default)]
+            pub autumn: Feature,
+        }
+
+        #[derive(Debug, Default, serde::Deserialize)]
+        pub struct Feature {
+            #[serde(default)]
+            pub enabled: bool,
+            #[serde(default)]
+            pub url: String,
+        }
+    }
+
+    pub mod autumn {
+        pub mod upload {
+            #[derive(Debug, serde::Deserialize)]
+            pub struct ResponseBody {
+                /// ID of the uploaded file.
+                pub id: String,
+            }
+        }
+    }
+
     pub mod auth {
         pub mod session {
             pub mod login {
@@ -59,22 +95,48 @@ pub mod schemas {
     }
 
     pub mod user {
+        use crate::schemas::file::File;
+
         #[derive(Debug, Default, serde::Serialize)]
         pub struct DataEditUser {
             #[serde(skip_serializing_if = "Option::is_none")]
             pub status: Option<UserStatus>,
             #[serde(skip_serializing_if = "Option::is_none")]
+            pub profile: Option<DataUserProfile>,
+            #[serde(skip_serializing_if = "Option::is_none")]
             pub remove: Option<Vec<FieldsUser>>,
         }
 
+        #[derive(Debug, Default, serde::Serialize)]
+        pub struct DataUserProfile {
+            /// The bio, in Markdown.
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub content: Option<String>,
+            /// ID of a file uploaded to the `backgrounds` tag.
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub background: Option<String>,
+        }
+
+        #[derive(Debug, Default, serde::Deserialize)]
+        pub struct UserProfile {
+            #[serde(default)]
+            pub content: Option<String>,
+            #[serde(default)]
+            pub background: Option<File>,
+        }
+
         #[derive(Debug, serde::Deserialize)]
         pub struct User {
+            #[serde(rename = "_id", default)]
+            pub id: String,
             pub status: Option<UserStatus>,
         }
 
         #[derive(Debug, serde::Serialize)]
         pub enum FieldsUser {
             StatusText,
+            ProfileContent,
+            ProfileBackground,
         }
 
         #[derive(Debug, Default, serde::Deserialize, serde::Serialize)]
@@ -95,6 +157,83 @@ pub mod schemas {
         }
     }
 
+    pub mod file {
+        /// A file uploaded to the file server.
+        #[derive(Debug, serde::Deserialize)]
+        pub struct File {
+            #[serde(rename = "_id")]
+            pub id: String,
+            /// The bucket of the file, like `backgrounds`.
+            pub tag: String,
+            pub filename: String,
+            #[serde(default)]
+            pub content_type: String,
+        }
+    }
+
+    pub mod channel {
+        #[derive(Debug, serde::Deserialize)]
+        pub struct Channel {
+            #[serde(rename = "_id")]
+            pub id: String,
+        }
+    }
+
+    pub mod message {
+        #[derive(Debug, Default, serde::Serialize)]
+        pub struct DataMessageSend {
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub content: Option<String>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub embeds: Option<Vec<SendableEmbed>>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub replies: Option<Vec<ReplyIntent>>,
+        }
+
+        /// A message being replied to.
+        #[derive(Debug, serde::Serialize)]
+        pub struct ReplyIntent {
+            pub id: String,
+            /// Whether the author of the message is mentioned.
+            pub mention: bool,
+        }
+
+        #[derive(Debug, Default, serde::Serialize)]
+        pub struct DataEditMessage {
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub content: Option<String>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub embeds: Option<Vec<SendableEmbed>>,
+        }
+
+        #[derive(Debug, Default, Clone, PartialEq, Eq, serde::Serialize)]
+        pub struct SendableEmbed {
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub icon_url: Option<String>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub url: Option<String>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub title: Option<String>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub description: Option<String>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub colour: Option<String>,
+        }
+
+        #[derive(Debug, serde::Deserialize)]
+        pub struct Message {
+            #[serde(rename = "_id")]
+            pub id: String,
+            #[serde(default)]
+            pub channel: String,
+            /// ID of the user who sent the message.
+            #[serde(default)]
+            pub author: String,
+            #[serde(default)]
+            pub content: Option<String>,
+        }
+    }
+
     #[derive(Debug, serde::Deserialize)]
     #[serde(tag = "type")]
     pub enum AuthifierError {
@@ -108,9 +247,44 @@ pub mod schemas {
     }
 }
 
+pub mod events {
+    use crate::schemas::message::Message;
+
+    /// An event sent to the events WebSocket.
+    #[derive(Debug, serde::Serialize)]
+    #[serde(tag = "type")]
+    pub enum ClientEvent {
+        Authenticate { token: String },
+        Ping { data: u64 },
+    }
+
+    /// An event received from the events WebSocket. Only the events lure
+    /// handles are parsed, the rest are [`ServerEvent::Unknown`].
+    #[derive(Debug, serde::Deserialize)]
+    #[serde(tag = "type")]
+    pub enum ServerEvent {
+        /// Several events sent at once.
+        Bulk {
+            v: Vec<Self>,
+        },
+        Authenticated,
+        Ready,
+        Pong,
+        Message(Message),
+        /// The connection failed, e.g. `InvalidSession` when the token isn't
+        /// valid.
+        Error {
+            error: String,
+        },
+        #[serde(other)]
+        Unknown,
+    }
+}
+
 #[derive(Debug)]
 pub enum Authentication {
     SessionToken(String),
+    BotToken(String),
 }
 
 impl Authentication {
@@ -118,14 +292,75 @@ impl Authentication {
     pub const fn header(&self) -> &str {
         match self {
             Self::SessionToken(_) => "X-Session-Token",
+            Self::BotToken(_) => "X-Bot-Token",
         }
     }
 
     #[must_use]
     pub fn value(&self) -> String {
         match self {
-            Self::SessionToken(token) => token,
+            Self::SessionToken(token) | Self::BotToken(token) => token,
         }
         .to_owned()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::events::ServerEvent;
+
+    fn parse(json: &str) -> ServerEvent {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn test_bulk() {
+        let event = parse(
+            r#"{"type": "Bulk", "v": [
+                {"type": "Message", "_id": "01J", "channel": "meow", "author": "kitty", "content": "Hi"},
+                {"type": "Pong", "data": 0},
+                {"type": "UserUpdate", "id": "kitty", "data": {}}
+            ]}"#,
+        );
+
+        let ServerEvent::Bulk { v } = event else {
+            panic!("{event:?}");
+        };
+        let [
+            ServerEvent::Message(message),
+            ServerEvent::Pong,
+            ServerEvent::Unknown,
+        ] = &v[..]
+        else {
+            panic!("{v:?}");
+        };
+        assert_eq!(message.id, "01J");
+        assert_eq!(message.channel, "meow");
+        assert_eq!(message.content.as_deref(), Some("Hi"));
+    }
+
+    #[test]
+    fn test_unknown() {
+        assert!(matches!(
+            parse(r#"{"type": "ChannelStartTyping", "id": "meow", "user": "kitty"}"#),
+            ServerEvent::Unknown
+        ));
+    }
+
+    #[test]
+    fn test_ready_with_extra_fields() {
+        assert!(matches!(
+            parse(
+                r#"{"type": "Ready", "users": [{"_id": "kitty"}], "servers": [], "channels": []}"#
+            ),
+            ServerEvent::Ready
+        ));
+    }
+
+    #[test]
+    fn test_error() {
+        let event = parse(r#"{"type": "Error", "error": "InvalidSession"}"#);
+
+        assert!(matches!(event, ServerEvent::Error { error } if error == "InvalidSession"));
+    }
+}
@@ -0,0 +1,271 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use reqwest::header::{
+    AGE, CACHE_CONTROL, ETAG, HeaderMap, HeaderValue, IF_MODIFIED_SINCE, IF_NONE_MATCH,
+    LAST_MODIFIED,
+};
+
+/// A short-lived, in-process HTTP response cache keyed by user.
+///
+/// Stores the parsed value of a response together with its validators
+/// (`ETag` and `Last-Modified`), so that following requests can be made
+/// conditional and a `304 Not Modified` response can short-circuit to the
+/// previously parsed value. Freshness is taken from the `Cache-Control`
+/// header and capped by `max_lifetime`.
+///
+/// Now-playing data goes stale quickly, so [`ResponseCache::default`] caps
+/// it to [`DEFAULT_MAX_LIFETIME`] regardless of what the server allows.
+pub struct ResponseCache<T> {
+    entries: Mutex<HashMap<String, Entry<T>>>,
+    max_lifetime: Duration,
+}
+
+/// The maximum lifetime of an entry used by [`ResponseCache::default`].
+pub const DEFAULT_MAX_LIFETIME: Duration = Duration::from_secs(60);
+
+struct Entry<T> {
+    value: T,
+    etag: Option<HeaderValue>,
+    last_modified: Option<HeaderValue>,
+    expires_at: Option<Instant>,
+}
+
+impl<T: Clone> ResponseCache<T> {
+    #[must_use]
+    pub fn new(max_lifetime: Duration) -> Self {
+        Self {
+            entries: Mutex::new(HashMap::new()),
+            max_lifetime,
+        }
+    }
+
+    /// Returns the cached value for `key` if it's still fresh.
+    pub fn fresh(&self, key: &str) -> Option<T> {
+        self.entries
+            .lock()
+            .ok()?
+            .get(key)
+            .filter(|entry| {
+                entry
+                    .expires_at
+                    .is_some_and(|expires_at| Instant::now() < expires_at)
+            })
+            .map(|entry| entry.value.clone())
+    }
+
+    /// Returns the conditional request headers for `key`, if any
+    /// validators were stored.
+    pub fn conditional_headers(&self, key: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+
+        if let Ok(entries) = self.entries.lock()
+            && let Some(entry) = entries.get(key)
+        {
+            if let Some(etag) = &entry.etag {
+                headers.insert(IF_NONE_MATCH, etag.clone());
+            }
+            if let Some(last_modified) = &entry.last_modified {
+                headers.insert(IF_MODIFIED_SINCE, last_modified.clone());
+            }
+        }
+
+        headers
+    }
+
+    /// Refreshes the entry for `key` after a `304 Not Modified` response
+    /// and returns the cached value.
+    pub fn revalidate(&self, key: &str, headers: &HeaderMap) -> Option<T> {
+        let directives = CacheControl::from_headers(headers);
+        let mut entries = self.entries.lock().ok()?;
+
+        if directives.no_store {
+            return entries.remove(key).map(|entry| entry.value);
+        }
+
+        let entry = entries.get_mut(key)?;
+        entry.expires_at = directives.expires_at(headers, self.max_lifetime);
+        if let Some(etag) = headers.get(ETAG) {
+            entry.etag = Some(etag.clone());
+        }
+        if let Some(last_modified) = headers.get(LAST_MODIFIED) {
+            entry.last_modified = Some(last_modified.clone());
+        }
+        let value = entry.value.clone();
+        drop(entries);
+
+        Some(value)
+    }
+
+    /// Stores `value` for `key` using the validators and freshness
+    /// information from the response `headers`.
+    pub fn store(&self, key: &str, headers: &HeaderMap, value: T) {
+        let directives = CacheControl::from_headers(headers);
+        let Ok(mut entries) = self.entries.lock() else {
+            return;
+        };
+
+        if directives.no_store {
+            entries.remove(key);
+            return;
+        }
+
+        entries.insert(
+            key.to_owned(),
+            Entry {
+                value,
+                etag: headers.get(ETAG).cloned(),
+                last_modified: headers.get(LAST_MODIFIED).cloned(),
+                expires_at: directives.expires_at(headers, self.max_lifetime),
+            },
+        );
+    }
+}
+
+impl<T: Clone> Default for ResponseCache<T> {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_LIFETIME)
+    }
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct CacheControl {
+    no_store: bool,
+    no_cache: bool,
+    max_age: Option<u64>,
+}
+
+impl CacheControl {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        headers
+            .get_all(CACHE_CONTROL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .fold(Self::default(), |mut directives, directive| {
+                let (name, value) = directive
+                    .split_once('=')
+                    .map_or((directive, None), |(name, value)| (name, Some(value)));
+
+                match name.trim().to_ascii_lowercase().as_str() {
+                    "no-store" => directives.no_store = true,
+                    "no-cache" => directives.no_cache = true,
+                    "max-age" => {
+                        directives.max_age =
+                            value.and_then(|value| value.trim().trim_matches('"').parse().ok());
+                    }
+                    _ => {}
+                }
+
+                directives
+            })
+    }
+
+    fn expires_at(&self, headers: &HeaderMap, max_lifetime: Duration) -> Option<Instant> {
+        if self.no_cache {
+            return None;
+        }
+
+        let age = headers
+            .get(AGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok())
+            .unwrap_or(0);
+        let lifetime = Duration::from_secs(self.max_age?.saturating_sub(age)).min(max_lifetime);
+
+        (!lifetime.is_zero()).then(|| Instant::now() + lifetime)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(reqwest::header::HeaderName, &'static str)]) -> HeaderMap {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.clone(), HeaderValue::from_static(value)))
+            .collect()
+    }
+
+    #[test]
+    fn test_cache_control_parsing() {
+        let directives = CacheControl::from_headers(&headers(&[(
+            CACHE_CONTROL,
+            "public, Max-Age=\"30\", no-cache",
+        )]));
+
+        assert_eq!(
+            directives,
+            CacheControl {
+                no_store: false,
+                no_cache: true,
+                max_age: Some(30),
+            }
+        );
+    }
+
+    #[test]
+    fn test_fresh_respects_max_age() {
+        let cache = ResponseCache::new(Duration::from_secs(60));
+
+        cache.store("kitty", &headers(&[(CACHE_CONTROL, "max-age=30")]), 1);
+        cache.store("kitten", &headers(&[(CACHE_CONTROL, "max-age=0")]), 2);
+        cache.store("cat", &HeaderMap::new(), 3);
+
+        assert_eq!(cache.fresh("kitty"), Some(1));
+        assert_eq!(cache.fresh("kitten"), None);
+        assert_eq!(cache.fresh("cat"), None);
+    }
+
+    #[test]
+    fn test_no_store_is_not_cached() {
+        let cache = ResponseCache::new(Duration::from_secs(60));
+
+        cache.store(
+            "kitty",
+            &headers(&[(CACHE_CONTROL, "no-store"), (ETAG, "\"meow\"")]),
+            1,
+        );
+
+        assert!(cache.conditional_headers("kitty").is_empty());
+        assert_eq!(cache.revalidate("kitty", &HeaderMap::new()), None);
+    }
+
+    #[test]
+    fn test_conditional_headers_and_revalidate() {
+        let cache = ResponseCache::new(Duration::from_secs(60));
+
+        cache.store(
+            "kitty",
+            &headers(&[
+                (ETAG, "\"meow\""),
+                (LAST_MODIFIED, "Wed, 21 Oct 2015 07:28:00 GMT"),
+            ]),
+            1,
+        );
+
+        let conditional = cache.conditional_headers("kitty");
+        assert_eq!(conditional.get(IF_NONE_MATCH).unwrap(), "\"meow\"");
+        assert_eq!(
+            conditional.get(IF_MODIFIED_SINCE).unwrap(),
+            "Wed, 21 Oct 2015 07:28:00 GMT"
+        );
+
+        assert_eq!(
+            cache.revalidate("kitty", &headers(&[(CACHE_CONTROL, "max-age=10")])),
+            Some(1)
+        );
+        assert_eq!(cache.fresh("kitty"), Some(1));
+    }
+
+    #[test]
+    fn test_max_lifetime_caps_freshness() {
+        let cache = ResponseCache::new(Duration::ZERO);
+
+        cache.store("kitty", &headers(&[(CACHE_CONTROL, "max-age=3600")]), 1);
+
+        assert_eq!(cache.fresh("kitty"), None);
+    }
+}
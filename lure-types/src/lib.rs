@@ -1,11 +1,38 @@
-#[derive(Debug, PartialEq, Eq)]
+pub mod cache;
+pub mod stats;
+pub mod template;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, serde::Serialize)]
 pub struct TrackInfo {
     pub artist: String,
     pub title: String,
+    /// The album of the track, if the service knows it.
+    pub album: Option<String>,
+    /// URL of the cover art of the track, if the service knows it.
+    pub cover_url: Option<String>,
+    /// URL of the page of the track, if the service knows it.
+    pub url: Option<String>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum PlaybackStatus {
     Playing(TrackInfo),
     NotPlaying,
 }
+
+/// A track listened to, from the history of a listening service.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct Scrobble {
+    pub track: TrackInfo,
+    /// Unix timestamp of when the track was listened to.
+    pub listened_at: i64,
+}
+
+/// A page of the listening history of a listening service, newest first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryPage {
+    pub scrobbles: Vec<Scrobble>,
+    /// The cursor of the next page, or `None` if this is the last page. Its
+    /// meaning depends on the service.
+    pub next: Option<i64>,
+}
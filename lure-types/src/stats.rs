@@ -0,0 +1,17 @@
+use crate::TrackInfo;
+
+/// Listening statistics that can be used in status templates. Values that
+/// are unknown, or weren't asked for, are `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Number of times the current track was played.
+    pub play_count: Option<i64>,
+    /// Number of times the artist of the current track was played.
+    pub artist_play_count: Option<i64>,
+    /// The most played artist of the week.
+    pub top_artist_week: Option<String>,
+    /// The most played track of the week.
+    pub top_track_week: Option<TrackInfo>,
+    /// Number of consecutive days with at least one play, up to today.
+    pub streak: Option<i64>,
+}
@@ -0,0 +1,182 @@
+use crate::TrackInfo;
+use crate::stats::Stats;
+
+/// Placeholders of the current track, which can only be used while
+/// something is playing.
+pub const TRACK_PLACEHOLDERS: &[&str] =
+    &["%NAME%", "%ARTIST%", "%PLAYCOUNT%", "%ARTIST_PLAYCOUNT%"];
+
+/// Placeholders that can be used in status templates.
+pub const PLACEHOLDERS: &[&str] = &[
+    "%NAME%",
+    "%ARTIST%",
+    "%PLAYCOUNT%",
+    "%ARTIST_PLAYCOUNT%",
+    "%TOP_ARTIST_WEEK%",
+    "%TOP_TRACK_WEEK%",
+    "%STREAK%",
+];
+
+/// Renders `template` by replacing the placeholders with the values from
+/// `track` and `stats`. Placeholders without a value are replaced with an
+/// empty string.
+#[must_use]
+pub fn render(template: &str, track: Option<&TrackInfo>, stats: &Stats) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+
+    for (start, placeholder) in placeholders(template) {
+        let offset = template.len() - rest.len();
+        let value = match placeholder {
+            "%NAME%" => track.map(|track| track.title.clone()),
+            "%ARTIST%" => track.map(|track| track.artist.clone()),
+            "%PLAYCOUNT%" => stats.play_count.map(|count| count.to_string()),
+            "%ARTIST_PLAYCOUNT%" => stats.artist_play_count.map(|count| count.to_string()),
+            "%TOP_ARTIST_WEEK%" => stats.top_artist_week.clone(),
+            "%TOP_TRACK_WEEK%" => stats
+                .top_track_week
+                .as_ref()
+                .map(|track| format!("{} by {}", track.title, track.artist)),
+            "%STREAK%" => stats.streak.map(|streak| streak.to_string()),
+            _ => continue,
+        };
+
+        rendered.push_str(&template[offset..start]);
+        rendered.push_str(value.as_deref().unwrap_or_default());
+        rest = &template[start + placeholder.len()..];
+    }
+
+    rendered.push_str(rest);
+    rendered
+}
+
+/// Whether `template` uses `placeholder`.
+#[must_use]
+pub fn uses(template: &str, placeholder: &str) -> bool {
+    placeholders(template).any(|(_, found)| found == placeholder)
+}
+
+/// Returns the `%PLACEHOLDER%`-looking parts of `template` that aren't
+/// known placeholders.
+#[must_use]
+pub fn unknown_placeholders(template: &str) -> Vec<&str> {
+    placeholders(template)
+        .map(|(_, placeholder)| placeholder)
+        .filter(|placeholder| !PLACEHOLDERS.contains(placeholder))
+        .collect()
+}
+
+/// Finds the `%PLACEHOLDER%`-looking parts of `template`, with their byte
+/// offsets.
+fn placeholders(template: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut offset = 0;
+
+    std::iter::from_fn(move || {
+        loop {
+            let rest = &template[offset..];
+            let start = offset + rest.find('%')?;
+            let after_start = &template[start + 1..];
+            let end = after_start.find('%')?;
+            let name = &after_start[..end];
+
+            if !name.is_empty()
+                && name
+                    .chars()
+                    .all(|character| character.is_ascii_alphanumeric() || character == '_')
+            {
+                offset = start + end + 2;
+                return Some((start, &template[start..offset]));
+            }
+
+            offset = start + 1;
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn track() -> TrackInfo {
+        TrackInfo {
+            artist: "Kitty".to_string(),
+            title: "Meow".to_string(),
+            album: None,
+            cover_url: None,
+            url: None,
+        }
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            render(
+                "🎵 Listening to %NAME% by %ARTIST%",
+                Some(&track()),
+                &Stats::default()
+            ),
+            "🎵 Listening to Meow by Kitty"
+        );
+    }
+
+    #[test]
+    fn test_render_stats() {
+        let stats = Stats {
+            play_count: Some(12),
+            top_artist_week: Some("Kitten".to_string()),
+            top_track_week: Some(track()),
+            streak: Some(3),
+            ..Stats::default()
+        };
+
+        assert_eq!(
+            render("%NAME% (played %PLAYCOUNT%×)", Some(&track()), &stats),
+            "Meow (played 12×)"
+        );
+        assert_eq!(
+            render(
+                "Top artist this week: %TOP_ARTIST_WEEK%, track: %TOP_TRACK_WEEK%, %STREAK% days",
+                None,
+                &stats
+            ),
+            "Top artist this week: Kitten, track: Meow by Kitty, 3 days"
+        );
+        assert_eq!(
+            render("%ARTIST_PLAYCOUNT% 100% %NAME%", None, &stats),
+            " 100% "
+        );
+    }
+
+    #[test]
+    fn test_render_does_not_replace_values() {
+        let track = TrackInfo {
+            artist: "%NAME%".to_string(),
+            title: "%ARTIST%".to_string(),
+            album: None,
+            cover_url: None,
+            url: None,
+        };
+
+        assert_eq!(
+            render("%NAME% by %ARTIST%", Some(&track), &Stats::default()),
+            "%ARTIST% by %NAME%"
+        );
+    }
+
+    #[test]
+    fn test_uses() {
+        assert!(uses("played %PLAYCOUNT%×", "%PLAYCOUNT%"));
+        assert!(!uses("played %PLAYCOUNT%×", "%ARTIST_PLAYCOUNT%"));
+    }
+
+    #[test]
+    fn test_unknown_placeholders() {
+        assert!(unknown_placeholders("%NAME% by %ARTIST%").is_empty());
+        assert!(unknown_placeholders("100% cat, 50% kitten").is_empty());
+        assert_eq!(
+            unknown_placeholders("%TITLE% by %ARTIST% at %album%"),
+            ["%TITLE%", "%album%"]
+        );
+        assert_eq!(unknown_placeholders("50% %NAME%%TITLE%"), ["%TITLE%"]);
+    }
+}
@@ -0,0 +1,223 @@
+use std::convert::Infallible;
+use std::sync::Arc;
+use std::time::Duration;
+
+use lure_config::bot::{Options, PROFILE_PLACEHOLDER};
+use lure_stoat_api::EventStream;
+use lure_stoat_models::events::ServerEvent;
+use lure_stoat_models::schemas::message::{DataMessageSend, Message, ReplyIntent};
+use lure_types::{stats::Stats, template};
+use tokio::task::JoinHandle;
+use tokio::time::{MissedTickBehavior, interval, sleep};
+
+use crate::state::{NowPlaying, State};
+
+/// Delay before reconnecting after the connection is lost.
+const RECONNECT_DELAY: Duration = Duration::from_secs(10);
+/// Interval of the pings keeping the connection alive.
+const PING_INTERVAL: Duration = Duration::from_secs(20);
+
+/// A Stoat bot that answers a command with what the profiles are listening
+/// to, as shown by their sinks.
+struct Bot {
+    options: Options,
+    client: lure_stoat_api::Client,
+    state: Arc<State>,
+    /// Log the replies instead of sending them.
+    dry_run: bool,
+}
+
+/// Starts the bot in the background. It keeps reconnecting until its token
+/// gets rejected.
+pub fn spawn(
+    options: Options,
+    state: Arc<State>,
+    dry_run: bool,
+) -> Result<JoinHandle<()>, lure_stoat_api::Error> {
+    let client = lure_stoat_api::Client::try_new(
+        options.api_url.clone(),
+        &lure_stoat_models::Authentication::BotToken(options.bot_token.clone()),
+    )?;
+    let bot = Bot {
+        options,
+        client,
+        state,
+        dry_run,
+    };
+
+    Ok(tokio::spawn(bot.run()))
+}
+
+impl Bot {
+    async fn run(self) {
+        loop {
+            let Err(error) = self.listen().await;
+
+            if matches!(
+                error,
+                lure_stoat_api::Error::ApiError(lure_stoat_api::APIError::AuthenticationFailed)
+            ) {
+                eprintln!("Stoat bot stopped: {error}");
+                return;
+            }
+
+            eprintln!(
+                "Stoat bot disconnected, reconnecting in {}s: {error}",
+                RECONNECT_DELAY.as_secs()
+            );
+            sleep(RECONNECT_DELAY).await;
+        }
+    }
+
+    /// Connects to the events WebSocket and answers the commands, until the
+    /// connection fails.
+    async fn listen(&self) -> Result<Infallible, lure_stoat_api::Error> {
+        let user = self.client.get_self().await?;
+        let config = self.client.get_config().await?;
+        let mut events = EventStream::connect(&config.ws, &self.options.bot_token).await?;
+        println!(
+            "Stoat bot connected, answering `{}`{}.",
+            self.options.command,
+            if self.dry_run { " in dry run" } else { "" }
+        );
+
+        let mut ping = interval(PING_INTERVAL);
+        ping.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        ping.reset();
+
+        loop {
+            tokio::select! {
+                _ = ping.tick() => events.ping().await?,
+                event = events.next() => {
+                    let ServerEvent::Message(message) = event? else {
+                        continue;
+                    };
+
+                    if message.author != user.id
+                        && let Err(error) = self.answer(&message).await
+                    {
+                        eprintln!(
+                            "Stoat bot failed to reply in channel {}: {error}",
+                            message.channel
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Replies to `message` if it's the command.
+    async fn answer(&self, message: &Message) -> Result<(), lure_stoat_api::Error> {
+        let Some(profile) = message
+            .content
+            .as_deref()
+            .and_then(|content| parse_command(&self.options.command, content))
+        else {
+            return Ok(());
+        };
+        let content = self.reply(profile);
+
+        if self.dry_run {
+            println!(
+                "Stoat bot dry run, reply in channel {}: {content}",
+                message.channel
+            );
+            return Ok(());
+        }
+
+        self.client
+            .send_message(
+                &message.channel,
+                &DataMessageSend {
+                    content: Some(content),
+                    replies: Some(vec![ReplyIntent {
+                        id: message.id.clone(),
+                        mention: false,
+                    }]),
+                    ..Default::default()
+                },
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    /// Renders the reply for the running profile named `profile`, or for
+    /// every running profile the bot answers for, one per line.
+    fn reply(&self, profile: Option<&str>) -> String {
+        let snapshot = self.state.snapshot();
+        let lines: Vec<_> = snapshot
+            .profiles
+            .iter()
+            .filter(|(name, snapshot)| {
+                snapshot.running
+                    && (self.options.profiles.is_empty() || self.options.profiles.contains(name))
+                    && profile.is_none_or(|profile| profile.eq_ignore_ascii_case(name))
+            })
+            .map(|(name, snapshot)| self.render(name, snapshot.now_playing.as_ref()))
+            .collect();
+
+        if lines.is_empty() {
+            return profile.map_or_else(
+                || String::from("Nobody to answer for."),
+                |profile| format!("There is no profile named `{profile}`."),
+            );
+        }
+
+        lines.join("\n")
+    }
+
+    fn render(&self, profile: &str, now_playing: Option<&NowPlaying>) -> String {
+        let rendered = match now_playing {
+            Some(NowPlaying::Playing { track, stats }) => {
+                template::render(&self.options.template, Some(track), stats)
+            }
+            // Only the replacement text is shown for tracks hidden by a
+            // privacy filter.
+            Some(NowPlaying::Hidden { replacement }) => return format!("{profile}: {replacement}"),
+            Some(NowPlaying::Idle { stats }) => template::render(&self.options.idle, None, stats),
+            None => template::render(&self.options.idle, None, &Stats::default()),
+        };
+
+        rendered.replace(PROFILE_PLACEHOLDER, profile)
+    }
+}
+
+/// Returns the argument of `content` if it's `command`, `Some(None)` if it
+/// has none.
+fn parse_command<'a>(command: &str, content: &'a str) -> Option<Option<&'a str>> {
+    let rest = content.trim().strip_prefix(command)?;
+    if !rest.is_empty() && !rest.starts_with(char::is_whitespace) {
+        return None;
+    }
+
+    let argument = rest.trim();
+
+    Some((!argument.is_empty()).then_some(argument))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(parse_command("!np", "!np"), Some(None));
+        assert_eq!(parse_command("!np", "!np kitty"), Some(Some("kitty")));
+        assert_eq!(parse_command("!np", "!np   kitty \n"), Some(Some("kitty")));
+    }
+
+    #[test]
+    fn test_parse_command_leading_whitespace() {
+        assert_eq!(parse_command("!np", "  !np"), Some(None));
+        assert_eq!(parse_command("!np", "\n!np kitty"), Some(Some("kitty")));
+    }
+
+    #[test]
+    fn test_parse_unknown_command() {
+        assert_eq!(parse_command("!np", "!help"), None);
+        assert_eq!(parse_command("!np", "!npkitty"), None);
+        assert_eq!(parse_command("!np", "what's !np"), None);
+        assert_eq!(parse_command("!np", ""), None);
+    }
+}
@@ -0,0 +1,470 @@
+use std::time::{Duration, Instant};
+
+use lure_config::channel::Options;
+use lure_stoat_models::schemas::message::{DataEditMessage, DataMessageSend, SendableEmbed};
+use lure_types::{TrackInfo, stats::Stats, template};
+
+use crate::metrics::ProfileMetrics;
+use crate::sink::{Event, Sink, SinkError, SinkOptions};
+
+/// Placeholder ID of the messages "posted" in dry run.
+const DRY_RUN_MESSAGE_ID: &str = "dry-run";
+
+/// A Stoat channel a message is posted to for every track.
+pub struct ChannelSink {
+    options: Options,
+    /// The API client, or `None` in dry run, where the messages are only
+    /// logged.
+    client: Option<lure_stoat_api::Client>,
+    /// The track last posted, along with its replacement text if it's
+    /// hidden by a privacy filter.
+    posted: Option<(TrackInfo, Option<String>)>,
+    /// ID of the message last posted, until it's deleted.
+    message_id: Option<String>,
+    /// When the next message can be posted, if throttled or rate limited.
+    next_post_at: Option<Instant>,
+}
+
+impl ChannelSink {
+    pub fn new(
+        options: Options,
+        http_client: &reqwest::Client,
+        dry_run: bool,
+    ) -> Result<Self, lure_stoat_api::Error> {
+        let client = if dry_run {
+            None
+        } else {
+            Some(lure_stoat_api::Client::with_http_client(
+                http_client.clone(),
+                options.api_url.clone(),
+                &lure_stoat_models::Authentication::SessionToken(options.session_token.clone()),
+            )?)
+        };
+
+        Ok(Self {
+            options,
+            client,
+            posted: None,
+            message_id: None,
+            next_post_at: None,
+        })
+    }
+
+    /// Renders the message for `track`. Tracks hidden by a privacy filter
+    /// are posted as the rendered replacement text alone, without an
+    /// embed.
+    fn message(
+        &self,
+        track: &TrackInfo,
+        replacement: Option<&str>,
+        stats: &Stats,
+    ) -> (String, Option<Vec<SendableEmbed>>) {
+        if let Some(replacement) = replacement {
+            return (replacement.to_string(), None);
+        }
+
+        let content = template::render(&self.options.template, Some(track), stats);
+        let embeds = self.options.embed.then(|| {
+            vec![SendableEmbed {
+                icon_url: track.cover_url.clone(),
+                url: track.url.clone(),
+                title: Some(track.title.clone()),
+                description: Some(track.album.as_ref().map_or_else(
+                    || track.artist.clone(),
+                    |album| format!("{} · {album}", track.artist),
+                )),
+                ..Default::default()
+            }]
+        });
+
+        (content, embeds)
+    }
+
+    /// Posts the message, or edits the previous one if enabled and it still
+    /// exists.
+    async fn post(
+        &mut self,
+        content: String,
+        embeds: Option<Vec<SendableEmbed>>,
+    ) -> Result<(), lure_stoat_api::Error> {
+        let Some(client) = &self.client else {
+            let action = if self.options.edit && self.message_id.is_some() {
+                "edited"
+            } else {
+                "posted"
+            };
+            println!(
+                "Stoat channel ({}) dry run, message {action}: {content}",
+                self.options.channel_id
+            );
+            self.message_id = Some(DRY_RUN_MESSAGE_ID.to_string());
+
+            return Ok(());
+        };
+
+        if self.options.edit
+            && let Some(message_id) = &self.message_id
+        {
+            let result = client
+                .edit_message(
+                    &self.options.channel_id,
+                    message_id,
+                    &DataEditMessage {
+                        content: Some(content.clone()),
+                        // An empty list removes the embeds of the previous
+                        // track.
+                        embeds: Some(embeds.clone().unwrap_or_default()),
+                    },
+                )
+                .await;
+
+            match result {
+                Ok(_) => return Ok(()),
+                // The message was deleted by someone else, so a new one is
+                // posted.
+                Err(lure_stoat_api::Error::ApiError(lure_stoat_api::APIError::NotFound)) => {}
+                Err(error) => return Err(error),
+            }
+        }
+
+        let message = client
+            .send_message(
+                &self.options.channel_id,
+                &DataMessageSend {
+                    content: Some(content),
+                    embeds,
+                    ..Default::default()
+                },
+            )
+            .await?;
+        self.message_id = Some(message.id);
+
+        Ok(())
+    }
+
+    /// Deletes the message last posted, if enabled.
+    async fn delete(&mut self) -> Result<(), lure_stoat_api::Error> {
+        if !self.options.delete_on_stop {
+            return Ok(());
+        }
+        let Some(message_id) = &self.message_id else {
+            return Ok(());
+        };
+
+        match &self.client {
+            Some(client) => match client
+                .delete_message(&self.options.channel_id, message_id)
+                .await
+            {
+                Ok(())
+                | Err(lure_stoat_api::Error::ApiError(lure_stoat_api::APIError::NotFound)) => {}
+                Err(error) => return Err(error),
+            },
+            None => println!(
+                "Stoat channel ({}) dry run, message deleted.",
+                self.options.channel_id
+            ),
+        }
+        self.message_id = None;
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Sink for ChannelSink {
+    fn name(&self) -> String {
+        format!("Stoat channel {}", self.options.channel_id)
+    }
+
+    /// Accepts the options of the same channel, posted to by the same
+    /// account, in the same dry run mode.
+    fn accepts(&self, options: &SinkOptions) -> bool {
+        let SinkOptions::Channel { options, dry_run } = options else {
+            return false;
+        };
+
+        *dry_run == self.client.is_none()
+            && self.options.api_url == options.api_url
+            && self.options.session_token == options.session_token
+            && self.options.channel_id == options.channel_id
+    }
+
+    fn reconfigure(&mut self, options: SinkOptions) {
+        debug_assert!(self.accepts(&options));
+
+        if let SinkOptions::Channel { options, .. } = options {
+            self.options = options;
+        }
+    }
+
+    /// Posts a message when the track changes. While throttled or rate
+    /// limited, the message is skipped and retried on the next call, so
+    /// only the latest track is posted.
+    async fn update(
+        &mut self,
+        event: &Event<'_>,
+        stats: &Stats,
+        _metrics: &ProfileMetrics,
+    ) -> Result<(), SinkError> {
+        let Event::Playing { track, replacement } = *event else {
+            self.posted = None;
+            return Ok(self.delete().await?);
+        };
+
+        let replacement =
+            replacement.map(|replacement| template::render(replacement, Some(track), stats));
+        if self
+            .posted
+            .as_ref()
+            .is_some_and(|(posted, posted_replacement)| {
+                posted == track && *posted_replacement == replacement
+            })
+            || self
+                .next_post_at
+                .is_some_and(|next_post_at| Instant::now() < next_post_at)
+        {
+            return Ok(());
+        }
+
+        let (content, embeds) = self.message(track, replacement.as_deref(), stats);
+
+        match self.post(content, embeds).await {
+            Ok(()) => {
+                self.posted = Some((track.clone(), replacement));
+                self.next_post_at = (self.options.throttle > 0)
+                    .then(|| Instant::now() + Duration::from_secs(self.options.throttle));
+
+                Ok(())
+            }
+            Err(lure_stoat_api::Error::ApiError(lure_stoat_api::APIError::RateLimitExceeded(
+                remaining,
+            ))) => {
+                self.next_post_at = Some(Instant::now() + Duration::from_millis(remaining));
+                println!(
+                    "Stoat channel ({}) rate limit exceeded, retrying in {remaining}ms.",
+                    self.options.channel_id
+                );
+
+                Ok(())
+            }
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Checks that the account can see the channel.
+    async fn verify(&self) -> Result<(), SinkError> {
+        if let Some(client) = &self.client {
+            client.get_channel(&self.options.channel_id).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the message last posted, if enabled.
+    async fn reset(&mut self) -> Result<(), SinkError> {
+        self.posted = None;
+
+        Ok(self.delete().await?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::extract::{Path, State};
+    use axum::routing::{patch, post};
+
+    use super::*;
+    use crate::metrics::Metrics;
+
+    fn track(title: &str) -> TrackInfo {
+        TrackInfo {
+            artist: String::from("Cat"),
+            title: title.to_string(),
+            album: Some(String::from("Purr")),
+            cover_url: Some(String::from("https://example.com/cover.png")),
+            url: Some(String::from("https://example.com/meow")),
+        }
+    }
+
+    fn sink(yaml: &str) -> ChannelSink {
+        ChannelSink::new(
+            serde_yaml::from_str(yaml).unwrap(),
+            &reqwest::Client::new(),
+            true,
+        )
+        .unwrap()
+    }
+
+    async fn play(sink: &mut ChannelSink, track: &TrackInfo) {
+        let metrics = Arc::new(Metrics::new()).profile("kitty");
+
+        sink.update(
+            &Event::Playing {
+                track,
+                replacement: None,
+            },
+            &Stats::default(),
+            &metrics,
+        )
+        .await
+        .unwrap();
+    }
+
+    #[test]
+    fn test_message() {
+        let sink = sink("{channel_id: meow, template: '%NAME% by %ARTIST%'}");
+        let mut track = track("Meow");
+
+        let (content, embeds) = sink.message(&track, None, &Stats::default());
+        assert_eq!(content, "Meow by Cat");
+        assert_eq!(
+            embeds,
+            Some(vec![SendableEmbed {
+                icon_url: Some(String::from("https://example.com/cover.png")),
+                url: Some(String::from("https://example.com/meow")),
+                title: Some(String::from("Meow")),
+                description: Some(String::from("Cat · Purr")),
+                ..Default::default()
+            }])
+        );
+
+        track.album = None;
+        let (_, embeds) = sink.message(&track, None, &Stats::default());
+        assert_eq!(embeds.unwrap()[0].description.as_deref(), Some("Cat"));
+    }
+
+    #[test]
+    fn test_message_of_a_hidden_track() {
+        let sink = sink("channel_id: meow");
+
+        let (content, embeds) = sink.message(&track("Meow"), Some("Something"), &Stats::default());
+
+        assert_eq!(content, "Something");
+        assert_eq!(embeds, None);
+    }
+
+    #[test]
+    fn test_message_without_embed() {
+        let sink = sink("{channel_id: meow, embed: false}");
+
+        let (_, embeds) = sink.message(&track("Meow"), None, &Stats::default());
+
+        assert_eq!(embeds, None);
+    }
+
+    #[tokio::test]
+    async fn test_skips_the_track_already_posted() {
+        let mut sink = sink("channel_id: meow");
+
+        play(&mut sink, &track("Meow")).await;
+        assert_eq!(sink.message_id.as_deref(), Some(DRY_RUN_MESSAGE_ID));
+
+        // Forgets the message, to see whether another one is posted.
+        sink.message_id = None;
+        play(&mut sink, &track("Meow")).await;
+        assert_eq!(sink.message_id, None);
+
+        play(&mut sink, &track("Purr")).await;
+        assert_eq!(sink.message_id.as_deref(), Some(DRY_RUN_MESSAGE_ID));
+    }
+
+    #[tokio::test]
+    async fn test_throttle() {
+        let mut sink = sink("{channel_id: meow, throttle: 60}");
+
+        play(&mut sink, &track("Meow")).await;
+        let next_post_at = sink.next_post_at.unwrap() - Instant::now();
+        assert!(next_post_at > Duration::from_secs(59), "{next_post_at:?}");
+
+        play(&mut sink, &track("Purr")).await;
+        assert_eq!(sink.posted.as_ref().unwrap().0.title, "Meow");
+
+        // Once the window is over, the latest track is posted.
+        sink.next_post_at = Some(Instant::now());
+        play(&mut sink, &track("Purr")).await;
+        assert_eq!(sink.posted.as_ref().unwrap().0.title, "Purr");
+    }
+
+    #[tokio::test]
+    async fn test_delete_on_stop() {
+        let mut sink = sink("{channel_id: meow, delete_on_stop: true}");
+        let metrics = Arc::new(Metrics::new()).profile("kitty");
+
+        play(&mut sink, &track("Meow")).await;
+        sink.update(&Event::Idle, &Stats::default(), &metrics)
+            .await
+            .unwrap();
+        assert_eq!(sink.message_id, None);
+        assert!(sink.posted.is_none());
+
+        play(&mut sink, &track("Meow")).await;
+        sink.reset().await.unwrap();
+        assert_eq!(sink.message_id, None);
+        assert!(sink.posted.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_keeps_the_message_on_stop() {
+        let mut sink = sink("channel_id: meow");
+        let metrics = Arc::new(Metrics::new()).profile("kitty");
+
+        play(&mut sink, &track("Meow")).await;
+        sink.update(&Event::Idle, &Stats::default(), &metrics)
+            .await
+            .unwrap();
+
+        assert_eq!(sink.message_id.as_deref(), Some(DRY_RUN_MESSAGE_ID));
+        assert!(sink.posted.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_edit_reuses_the_message() {
+        type Requests = Arc<Mutex<Vec<String>>>;
+
+        // A Stoat API recording the requests, where every message posted
+        // gets the ID `1`.
+        let requests = Requests::default();
+        let router = axum::Router::new()
+            .route(
+                "/channels/{channel}/messages",
+                post(|State(requests): State<Requests>| async move {
+                    requests.lock().unwrap().push(String::from("POST"));
+                    axum::Json(serde_json::json!({ "_id": "1" }))
+                }),
+            )
+            .route(
+                "/channels/{channel}/messages/{message}",
+                patch(
+                    |State(requests): State<Requests>,
+                     Path((_, message)): Path<(String, String)>| async move {
+                        requests.lock().unwrap().push(format!("PATCH {message}"));
+                        axum::Json(serde_json::json!({ "_id": message }))
+                    },
+                ),
+            )
+            .with_state(Arc::clone(&requests));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let mut sink = ChannelSink::new(
+            serde_yaml::from_str(&format!(
+                "{{channel_id: meow, api_url: 'http://{address}', session_token: meow, edit: true}}"
+            ))
+            .unwrap(),
+            &reqwest::Client::new(),
+            false,
+        )
+        .unwrap();
+
+        play(&mut sink, &track("Meow")).await;
+        play(&mut sink, &track("Purr")).await;
+        play(&mut sink, &track("Mrrp")).await;
+
+        assert_eq!(*requests.lock().unwrap(), ["POST", "PATCH 1", "PATCH 1"]);
+        assert_eq!(sink.message_id.as_deref(), Some("1"));
+    }
+}
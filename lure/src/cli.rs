@@ -0,0 +1,88 @@
+use std::path::PathBuf;
+
+#[derive(Debug, clap::Parser)]
+#[command(version, about)]
+pub struct Cli {
+    /// Path to the configuration file.
+    #[arg(short, long, global = true, default_value = "config.yaml")]
+    pub config: PathBuf,
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum Command {
+    /// Start syncing the listening status to Stoat.
+    Start {
+        /// Log the statuses instead of setting them. Stoat isn't called, so
+        /// no session token is needed.
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Manage the configuration.
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Show the local listening history, most recent first.
+    History {
+        #[command(flatten)]
+        filter: HistoryFilter,
+        #[command(subcommand)]
+        command: Option<HistoryCommand>,
+    },
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum ConfigCommand {
+    /// Validate the configuration and report every problem found.
+    Check {
+        /// Also verify the credentials against each API.
+        #[arg(long)]
+        online: bool,
+    },
+}
+
+#[derive(Debug, clap::Args)]
+pub struct HistoryFilter {
+    /// Only include plays since this time. Accepts a Unix or RFC 3339
+    /// timestamp, a date, or a duration ago, like `7d`.
+    #[arg(long, global = true, value_parser = crate::history::parse_time)]
+    pub from: Option<jiff::Timestamp>,
+    /// Only include plays before this time. Accepts the same formats as
+    /// `--from`.
+    #[arg(long, global = true, value_parser = crate::history::parse_time)]
+    pub to: Option<jiff::Timestamp>,
+    /// Only include plays of this profile.
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+    /// Maximum number of entries to show.
+    #[arg(short = 'n', long, global = true, default_value_t = 20)]
+    pub limit: u32,
+}
+
+#[derive(Debug, clap::Subcommand)]
+pub enum HistoryCommand {
+    /// Show the most played artists.
+    TopArtists,
+    /// Show the most played tracks.
+    TopTracks,
+    /// Import the listening history from the listening service of a
+    /// profile, resuming the previous import if it was interrupted.
+    Import {
+        /// Export to this file instead of the local history database.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Format of the output file. Inferred from its extension by
+        /// default.
+        #[arg(long, value_enum, requires = "output")]
+        format: Option<ExportFormat>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, clap::ValueEnum)]
+pub enum ExportFormat {
+    /// One JSON object per line.
+    Jsonl,
+    Csv,
+}
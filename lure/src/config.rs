@@ -0,0 +1,104 @@
+use std::path::Path;
+
+use crate::service::Service;
+use crate::sink::SinkOptions;
+use crate::start::RunError;
+
+/// Validates the configuration, and optionally verifies the credentials
+/// against each API.
+pub async fn check(config_path: &Path, online: bool) -> Result<(), CheckError> {
+    let config = match lure_config::load(config_path) {
+        Ok(config) => config,
+        Err(diagnostics) => {
+            eprintln!("{diagnostics}");
+            return Err(CheckError::Invalid(diagnostics.0.len()));
+        }
+    };
+
+    println!("Configuration is valid.");
+
+    if !online {
+        return Ok(());
+    }
+
+    let http_client = reqwest::Client::new();
+    let mut failed = 0;
+
+    for profile in config.profiles() {
+        let service_result = match Service::try_from_options(profile.service.clone(), &http_client)
+        {
+            Ok(service) => service
+                .fetch()
+                .await
+                .map(|_| service.name())
+                .map_err(RunError::from),
+            Err(error) => Err(error),
+        };
+        match service_result {
+            Ok(name) => println!("[{}] {name}: credentials verified.", profile.name),
+            Err(error) => {
+                eprintln!("[{}] service: {error}", profile.name);
+                failed += 1;
+            }
+        }
+
+        if profile.dry_run {
+            println!("[{}] sinks: skipped, dry run.", profile.name);
+            continue;
+        }
+
+        // Building a sink connects to its output, which checks its
+        // credentials.
+        for options in SinkOptions::of(&profile) {
+            let kind = options.kind();
+            let result = match options.build(&http_client).await {
+                Ok(sink) => sink.verify().await.map(|()| sink.name()),
+                Err(error) => Err(error),
+            };
+
+            match result {
+                Ok(name) => println!("[{}] {name}: verified.", profile.name),
+                Err(error) => {
+                    eprintln!("[{}] {kind}: {error}", profile.name);
+                    failed += 1;
+                }
+            }
+        }
+    }
+
+    if let Some(bot) = config.bot {
+        if config.dry_run {
+            println!("bot: skipped, dry run.");
+        } else {
+            let result = match lure_stoat_api::Client::try_new(
+                bot.api_url,
+                &lure_stoat_models::Authentication::BotToken(bot.bot_token),
+            ) {
+                Ok(client) => client.get_self().await.map(|_| ()),
+                Err(error) => Err(error),
+            };
+
+            match result {
+                Ok(()) => println!("bot: credentials verified."),
+                Err(error) => {
+                    eprintln!("bot: {error}");
+                    failed += 1;
+                }
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(CheckError::Online(failed));
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum CheckError {
+    #[error("Configuration has {0} problem(s).")]
+    Invalid(usize),
+    #[error("{0} online check(s) failed.")]
+    Online(usize),
+}
@@ -0,0 +1,326 @@
+use std::time::{Duration, Instant};
+
+use lure_config::discord::Options;
+use lure_discord_ipc::{Activity, ActivityType, Assets, Client, Timestamps};
+use lure_types::{TrackInfo, stats::Stats, template};
+
+use crate::metrics::ProfileMetrics;
+use crate::sink::{Event, Sink, SinkError, SinkOptions};
+
+/// Longest text Discord accepts in an activity field.
+const MAX_TEXT_LENGTH: usize = 128;
+/// Shortest text Discord accepts in an activity field.
+const MIN_TEXT_LENGTH: usize = 2;
+/// Longest image URL Discord accepts.
+const MAX_IMAGE_URL_LENGTH: usize = 256;
+/// How often the activity is set again even if it didn't change, since
+/// Discord forgets it when it restarts, and the connection to the previous
+/// instance only fails once it's used.
+const REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+/// The local Discord client, showing the current track as a "Listening to"
+/// activity.
+///
+/// The client is connected to on the first update and reconnected to after
+/// any error, so Discord can be started or restarted while lure runs.
+pub struct DiscordSink {
+    options: Options,
+    /// Whether the activity is only logged.
+    dry_run: bool,
+    client: Option<Client>,
+    /// The current track, the replacement text if it's hidden by a privacy
+    /// filter, and the Unix timestamp in milliseconds of when it started.
+    playing: Option<(TrackInfo, Option<String>, i64)>,
+    /// The activity last set, or `None` if it was cleared, and when.
+    shown: Option<(Option<Activity>, Instant)>,
+}
+
+impl DiscordSink {
+    pub const fn new(options: Options, dry_run: bool) -> Self {
+        Self {
+            options,
+            dry_run,
+            client: None,
+            playing: None,
+            shown: None,
+        }
+    }
+
+    /// Sets the activity if it changed or is due to be refreshed, or clears
+    /// it.
+    async fn show(&mut self, activity: Option<Activity>) -> Result<(), SinkError> {
+        if self.shown.as_ref().is_some_and(|(shown, shown_at)| {
+            *shown == activity && (self.dry_run || shown_at.elapsed() < REFRESH_INTERVAL)
+        }) {
+            return Ok(());
+        }
+
+        if self.dry_run {
+            match &activity {
+                Some(activity) => println!(
+                    "Discord ({}) dry run, activity set to: {}",
+                    self.options.client_id,
+                    serde_json::to_string(activity)?
+                ),
+                None => println!(
+                    "Discord ({}) dry run, activity cleared.",
+                    self.options.client_id
+                ),
+            }
+        } else {
+            self.set_activity(activity.as_ref()).await?;
+        }
+        self.shown = Some((activity, Instant::now()));
+
+        Ok(())
+    }
+
+    async fn set_activity(&mut self, activity: Option<&Activity>) -> Result<(), SinkError> {
+        // The connection may be to a Discord that was restarted since, so a
+        // failure on it is retried once on a new connection.
+        if let Some(client) = &mut self.client {
+            if client.set_activity(activity).await.is_ok() {
+                return Ok(());
+            }
+            self.client = None;
+        }
+
+        let client = self.client.insert(
+            Client::connect(&self.options.client_id, self.options.socket.as_deref()).await?,
+        );
+        if let Err(error) = client.set_activity(activity).await {
+            // The connection may be broken, so the next attempt starts a new
+            // one.
+            self.client = None;
+            return Err(error.into());
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Sink for DiscordSink {
+    fn name(&self) -> String {
+        format!("Discord application {}", self.options.client_id)
+    }
+
+    /// Accepts the same options, in the same dry run mode, since the
+    /// connection is bound to the application.
+    fn accepts(&self, options: &SinkOptions) -> bool {
+        let SinkOptions::Discord { options, dry_run } = options else {
+            return false;
+        };
+
+        *dry_run == self.dry_run && self.options == *options
+    }
+
+    fn reconfigure(&mut self, options: SinkOptions) {
+        debug_assert!(self.accepts(&options));
+    }
+
+    async fn update(
+        &mut self,
+        event: &Event<'_>,
+        stats: &Stats,
+        _metrics: &ProfileMetrics,
+    ) -> Result<(), SinkError> {
+        let Event::Playing { track, replacement } = *event else {
+            self.playing = None;
+            return self.show(None).await;
+        };
+
+        let replacement =
+            replacement.map(|replacement| template::render(replacement, Some(track), stats));
+        let started_at = match &self.playing {
+            Some((playing, playing_replacement, started_at))
+                if playing == track && *playing_replacement == replacement =>
+            {
+                *started_at
+            }
+            _ => jiff::Timestamp::now().as_millisecond(),
+        };
+        self.playing = Some((track.clone(), replacement.clone(), started_at));
+
+        let timestamps = Some(Timestamps {
+            start: Some(started_at),
+            end: None,
+        });
+        let activity = match replacement {
+            // Only the replacement text is shown for tracks hidden by a
+            // privacy filter.
+            Some(replacement) => Activity {
+                kind: ActivityType::Listening,
+                details: fit(&replacement),
+                timestamps,
+                ..Default::default()
+            },
+            None => Activity {
+                kind: ActivityType::Listening,
+                details: fit(&track.title),
+                state: fit(&track.artist),
+                timestamps,
+                assets: Some(Assets {
+                    large_image: track
+                        .cover_url
+                        .clone()
+                        .filter(|url| url.len() <= MAX_IMAGE_URL_LENGTH),
+                    large_text: track.album.as_deref().and_then(fit),
+                    ..Default::default()
+                })
+                .filter(|assets| assets.large_image.is_some()),
+            },
+        };
+
+        self.show(Some(activity)).await
+    }
+
+    async fn reset(&mut self) -> Result<(), SinkError> {
+        self.playing = None;
+        self.show(None).await
+    }
+
+    /// Clears the activity, then disconnects.
+    async fn restore(&mut self) -> Result<(), SinkError> {
+        // Discord clears the activity on its own once disconnected, so
+        // there's nothing to restore if it isn't connected.
+        if self.client.is_some() || self.dry_run {
+            self.reset().await?;
+        }
+
+        if let Some(client) = self.client.take() {
+            client.close().await?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Fits `text` in the length limits of Discord, shortening it with an
+/// ellipsis or padding it. Returns `None` for blank texts.
+fn fit(text: &str) -> Option<String> {
+    let text = text.trim();
+    if text.is_empty() {
+        return None;
+    }
+
+    let length = text.chars().count();
+    Some(if length > MAX_TEXT_LENGTH {
+        text.chars()
+            .take(MAX_TEXT_LENGTH - 1)
+            .chain(['…'])
+            .collect()
+    } else {
+        format!("{text:<MIN_TEXT_LENGTH$}")
+    })
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use std::sync::Arc;
+
+    use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+    use tokio::net::{UnixListener, UnixStream};
+
+    use super::*;
+    use crate::metrics::Metrics;
+
+    /// A fake Discord client, answering to one connection.
+    struct FakeDiscord {
+        stream: UnixStream,
+    }
+
+    impl FakeDiscord {
+        async fn accept(listener: &UnixListener) -> Self {
+            Self {
+                stream: listener.accept().await.unwrap().0,
+            }
+        }
+
+        async fn read(&mut self) -> serde_json::Value {
+            let _opcode = self.stream.read_u32_le().await.unwrap();
+            let length = self.stream.read_u32_le().await.unwrap();
+            let mut payload = vec![0; length as usize];
+            self.stream.read_exact(&mut payload).await.unwrap();
+
+            serde_json::from_slice(&payload).unwrap()
+        }
+
+        async fn write(&mut self, payload: &serde_json::Value) {
+            let payload = serde_json::to_vec(payload).unwrap();
+            self.stream.write_u32_le(1).await.unwrap();
+            self.stream
+                .write_u32_le(u32::try_from(payload.len()).unwrap())
+                .await
+                .unwrap();
+            self.stream.write_all(&payload).await.unwrap();
+        }
+
+        /// Answers the handshake, then the next command, and returns the
+        /// activity set by it.
+        async fn activity(&mut self) -> serde_json::Value {
+            self.read().await;
+            self.write(&serde_json::json!({ "cmd": "DISPATCH", "evt": "READY" }))
+                .await;
+
+            let command = self.read().await;
+            self.write(&serde_json::json!({ "cmd": command["cmd"], "nonce": command["nonce"] }))
+                .await;
+
+            command["args"]["activity"].clone()
+        }
+    }
+
+    fn track() -> TrackInfo {
+        TrackInfo {
+            artist: String::from("Cat"),
+            title: String::from("Meow"),
+            album: None,
+            cover_url: None,
+            url: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_activity_is_set_again_after_discord_restarts() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("discord-ipc-0");
+        let listener = UnixListener::bind(&path).unwrap();
+        let mut sink = DiscordSink::new(
+            serde_yaml::from_str(&format!("{{client_id: '1234', socket: {path:?}}}")).unwrap(),
+            false,
+        );
+        let metrics = Arc::new(Metrics::new()).profile("kitty");
+        let track = track();
+        let playing = Event::Playing {
+            track: &track,
+            replacement: None,
+        };
+
+        let server = tokio::spawn(async move {
+            // Discord quits once the activity is set, and is started again.
+            let first = FakeDiscord::accept(&listener).await.activity().await;
+            let second = FakeDiscord::accept(&listener).await.activity().await;
+
+            (first, second)
+        });
+
+        sink.update(&playing, &Stats::default(), &metrics)
+            .await
+            .unwrap();
+        // Unchanged, so it isn't sent again yet.
+        sink.update(&playing, &Stats::default(), &metrics)
+            .await
+            .unwrap();
+
+        let shown_at = &mut sink.shown.as_mut().unwrap().1;
+        *shown_at = shown_at.checked_sub(REFRESH_INTERVAL).unwrap();
+        sink.update(&playing, &Stats::default(), &metrics)
+            .await
+            .unwrap();
+
+        let (first, second) = server.await.unwrap();
+        assert_eq!(first["details"], "Meow");
+        assert_eq!(second, first);
+    }
+}
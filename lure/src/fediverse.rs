@@ -0,0 +1,498 @@
+use std::time::{Duration, Instant};
+
+use lure_config::fediverse::Options;
+use lure_types::{stats::Stats, template};
+use reqwest::StatusCode;
+use secrecy::ExposeSecret as _;
+
+use crate::metrics::ProfileMetrics;
+use crate::sink::{Event, Sink, SinkError, SinkOptions};
+
+/// Longest value Mastodon accepts in a profile field.
+const MAX_VALUE_LENGTH: usize = 255;
+/// Number of profile fields Mastodon accepts, for instances that don't
+/// tell theirs.
+const DEFAULT_MAX_FIELDS: usize = 4;
+
+/// A Fediverse account with a profile field kept up to date with the
+/// current track, through the Mastodon API.
+///
+/// Every update is sent to the followers of the account, so updates are
+/// at least `min_interval` seconds apart. Updates due sooner are delayed,
+/// and only the latest one is sent.
+pub struct FediverseSink {
+    options: Options,
+    /// The API client, or `None` in dry run, where the field is only
+    /// logged.
+    client: Option<Client>,
+    /// The value the field had before lure started, or `None` if the
+    /// account didn't have it.
+    first_value: Option<String>,
+    /// The value last set by lure.
+    applied_value: Option<String>,
+    /// When the next update can be sent.
+    next_update_at: Option<Instant>,
+}
+
+struct Client {
+    http_client: reqwest::Client,
+    /// The handle of the account, used in logs.
+    acct: String,
+    /// Number of profile fields the instance accepts.
+    max_fields: usize,
+}
+
+#[derive(serde::Deserialize)]
+struct Account {
+    acct: String,
+    source: Source,
+}
+
+/// The profile of the account as written, before the instance renders it.
+#[derive(serde::Deserialize)]
+struct Source {
+    #[serde(default)]
+    fields: Vec<Field>,
+}
+
+#[derive(Debug, PartialEq, Eq, serde::Deserialize)]
+struct Field {
+    name: String,
+    value: String,
+}
+
+#[derive(serde::Deserialize)]
+struct Instance {
+    configuration: Configuration,
+}
+
+#[derive(serde::Deserialize)]
+struct Configuration {
+    #[serde(default)]
+    accounts: Option<AccountLimits>,
+}
+
+#[derive(serde::Deserialize)]
+struct AccountLimits {
+    #[serde(default)]
+    max_profile_fields: Option<usize>,
+}
+
+/// The error of a rejected request.
+#[derive(serde::Deserialize)]
+struct ApiError {
+    error: String,
+}
+
+impl FediverseSink {
+    /// Connects to the account, and fetches the current value of the field
+    /// and the number of fields the instance accepts. In dry run, nothing is
+    /// fetched, and the account is assumed not to have the field.
+    pub async fn connect(
+        options: Options,
+        http_client: &reqwest::Client,
+        dry_run: bool,
+    ) -> Result<Self, SinkError> {
+        if dry_run {
+            return Ok(Self {
+                options,
+                client: None,
+                first_value: None,
+                applied_value: None,
+                next_update_at: None,
+            });
+        }
+
+        let account = verify_credentials(http_client, &options).await?;
+        let max_fields = max_fields(http_client, &options).await;
+        let first_value = account
+            .source
+            .fields
+            .into_iter()
+            .find(|field| field.name == options.field)
+            .map(|field| field.value);
+
+        Ok(Self {
+            client: Some(Client {
+                http_client: http_client.clone(),
+                acct: account.acct,
+                max_fields,
+            }),
+            applied_value: first_value.clone(),
+            first_value,
+            options,
+            next_update_at: None,
+        })
+    }
+
+    fn account_name(&self) -> &str {
+        self.client
+            .as_ref()
+            .map_or(&self.options.instance, |client| &client.acct)
+    }
+
+    /// Renders the value of the field for `event`, with the replacement
+    /// template of the privacy filter instead of the configured one if set,
+    /// or the idle value if nothing is playing. Falls back to the value the
+    /// field had before lure started when no idle value is configured.
+    fn value(&self, event: &Event<'_>, stats: &Stats) -> Option<String> {
+        match *event {
+            Event::Playing { track, replacement } => Some(shorten(template::render(
+                replacement.unwrap_or(&self.options.template),
+                Some(track),
+                stats,
+            ))),
+            Event::Idle => self
+                .options
+                .idle
+                .as_deref()
+                .map(|idle| shorten(template::render(idle, None, stats)))
+                .or_else(|| self.first_value.clone()),
+        }
+    }
+
+    /// Sets the field to `value` if it changed, or removes it. Unless
+    /// `now`, the update is skipped until `min_interval` has passed since
+    /// the previous one.
+    async fn set_value(&mut self, value: Option<String>, now: bool) -> Result<(), SinkError> {
+        if value == self.applied_value
+            || (!now
+                && self
+                    .next_update_at
+                    .is_some_and(|next_update_at| Instant::now() < next_update_at))
+        {
+            return Ok(());
+        }
+
+        let Some(client) = &self.client else {
+            match &value {
+                Some(value) => println!(
+                    "Fediverse ({}) dry run, {} set to: {value}",
+                    self.account_name(),
+                    self.options.field
+                ),
+                None => println!(
+                    "Fediverse ({}) dry run, {} removed.",
+                    self.account_name(),
+                    self.options.field
+                ),
+            }
+            self.applied_value = value;
+
+            return Ok(());
+        };
+
+        // The fields are fetched again right before updating them, since
+        // they're all replaced at once, and the others may have changed
+        // since lure started.
+        let mut fields = verify_credentials(&client.http_client, &self.options)
+            .await?
+            .source
+            .fields;
+        replace_field(
+            &mut fields,
+            &self.options.field,
+            value.as_deref(),
+            client.max_fields,
+        )?;
+
+        let response = client
+            .http_client
+            .patch(format!(
+                "{}/api/v1/accounts/update_credentials",
+                self.options.instance.trim_end_matches('/')
+            ))
+            .bearer_auth(access_token(&self.options))
+            .form(&fields_attributes(&fields))
+            .send()
+            .await?;
+
+        if response.status() == StatusCode::TOO_MANY_REQUESTS {
+            let delay = rate_limit_reset(&response)
+                .unwrap_or_else(|| Duration::from_secs(self.options.min_interval));
+            self.next_update_at = Some(Instant::now() + delay);
+            println!(
+                "Fediverse ({}) rate limit exceeded, retrying in {}s.",
+                self.account_name(),
+                delay.as_secs()
+            );
+
+            return Ok(());
+        }
+        // The fields don't pass the checks of the instance, which won't
+        // change by retrying.
+        if response.status() == StatusCode::UNPROCESSABLE_ENTITY {
+            let error = response.json::<ApiError>().await.map_or_else(
+                |_| StatusCode::UNPROCESSABLE_ENTITY.to_string(),
+                |error| error.error,
+            );
+
+            return Err(SinkError::Rejected(format!(
+                "The Fediverse instance rejected the profile fields: {error}"
+            )));
+        }
+        response.error_for_status()?;
+
+        self.applied_value = value;
+        self.next_update_at = Some(Instant::now() + Duration::from_secs(self.options.min_interval));
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Sink for FediverseSink {
+    fn name(&self) -> String {
+        format!("Fediverse account {}", self.account_name())
+    }
+
+    /// Accepts the options of the same field of the same account, in the
+    /// same dry run mode.
+    fn accepts(&self, options: &SinkOptions) -> bool {
+        let SinkOptions::Fediverse { options, dry_run } = options else {
+            return false;
+        };
+
+        *dry_run == self.client.is_none()
+            && self.options.instance == options.instance
+            && access_token(&self.options) == access_token(options)
+            && self.options.field == options.field
+    }
+
+    fn reconfigure(&mut self, options: SinkOptions) {
+        debug_assert!(self.accepts(&options));
+
+        if let SinkOptions::Fediverse { options, .. } = options {
+            self.options = options;
+        }
+    }
+
+    async fn update(
+        &mut self,
+        event: &Event<'_>,
+        stats: &Stats,
+        _metrics: &ProfileMetrics,
+    ) -> Result<(), SinkError> {
+        let value = self.value(event, stats);
+
+        self.set_value(value, false).await
+    }
+
+    /// Sets the field back to the value it had before lure started, or
+    /// removes it, right away.
+    async fn reset(&mut self) -> Result<(), SinkError> {
+        self.set_value(self.first_value.clone(), true).await
+    }
+}
+
+async fn verify_credentials(
+    http_client: &reqwest::Client,
+    options: &Options,
+) -> Result<Account, SinkError> {
+    Ok(http_client
+        .get(format!(
+            "{}/api/v1/accounts/verify_credentials",
+            options.instance.trim_end_matches('/')
+        ))
+        .bearer_auth(access_token(options))
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?)
+}
+
+/// Fetches the number of profile fields the instance accepts. Instances
+/// that don't tell it are assumed to accept as many as Mastodon.
+async fn max_fields(http_client: &reqwest::Client, options: &Options) -> usize {
+    let instance = async {
+        http_client
+            .get(format!(
+                "{}/api/v2/instance",
+                options.instance.trim_end_matches('/')
+            ))
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<Instance>()
+            .await
+    };
+
+    instance
+        .await
+        .ok()
+        .and_then(|instance| instance.configuration.accounts)
+        .and_then(|accounts| accounts.max_profile_fields)
+        .unwrap_or(DEFAULT_MAX_FIELDS)
+}
+
+fn access_token(options: &Options) -> &str {
+    options
+        .access_token
+        .as_ref()
+        .map(|access_token| access_token.expose_secret())
+        .unwrap_or_default()
+}
+
+/// Sets the field called `name` to `value` in `fields`, or removes it.
+/// Fails if the field would go over the `max_fields` of the instance.
+fn replace_field(
+    fields: &mut Vec<Field>,
+    name: &str,
+    value: Option<&str>,
+    max_fields: usize,
+) -> Result<(), SinkError> {
+    let position = fields.iter().position(|field| field.name == name);
+    match (position, value) {
+        (Some(position), Some(value)) => value.clone_into(&mut fields[position].value),
+        (None, Some(_)) if fields.len() >= max_fields => {
+            return Err(SinkError::Rejected(format!(
+                "The Fediverse account already has {} profile fields, the most its instance accepts, so `{name}` can't be added.",
+                fields.len()
+            )));
+        }
+        (None, Some(value)) => fields.push(Field {
+            name: name.to_string(),
+            value: value.to_string(),
+        }),
+        (Some(position), None) => {
+            fields.remove(position);
+        }
+        (None, None) => {}
+    }
+
+    Ok(())
+}
+
+/// Encodes `fields` as the `fields_attributes` form parameters, which
+/// replace every field of the account. A single blank field removes them
+/// all, since an empty list would leave them untouched.
+fn fields_attributes(fields: &[Field]) -> Vec<(String, &str)> {
+    if fields.is_empty() {
+        return vec![
+            ("fields_attributes[0][name]".to_string(), ""),
+            ("fields_attributes[0][value]".to_string(), ""),
+        ];
+    }
+
+    fields
+        .iter()
+        .enumerate()
+        .flat_map(|(index, field)| {
+            [
+                (
+                    format!("fields_attributes[{index}][name]"),
+                    field.name.as_str(),
+                ),
+                (
+                    format!("fields_attributes[{index}][value]"),
+                    field.value.as_str(),
+                ),
+            ]
+        })
+        .collect()
+}
+
+/// Returns how long until the rate limit resets, from the
+/// `X-RateLimit-Reset` timestamp.
+fn rate_limit_reset(response: &reqwest::Response) -> Option<Duration> {
+    let reset: jiff::Timestamp = response
+        .headers()
+        .get("X-RateLimit-Reset")?
+        .to_str()
+        .ok()?
+        .parse()
+        .ok()?;
+
+    Duration::try_from(reset.duration_since(jiff::Timestamp::now())).ok()
+}
+
+/// Shortens `value` to the length Mastodon accepts, with an ellipsis.
+fn shorten(value: String) -> String {
+    if value.chars().count() <= MAX_VALUE_LENGTH {
+        return value;
+    }
+
+    value
+        .chars()
+        .take(MAX_VALUE_LENGTH - 1)
+        .chain(['…'])
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn field(name: &str, value: &str) -> Field {
+        Field {
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_replace_field() {
+        let mut fields = vec![field("Web", "kitty.example"), field("Now playing", "old")];
+
+        replace_field(&mut fields, "Now playing", Some("Meow"), 4).unwrap();
+        assert_eq!(
+            fields,
+            [field("Web", "kitty.example"), field("Now playing", "Meow")]
+        );
+
+        replace_field(&mut fields, "Now playing", None, 4).unwrap();
+        assert_eq!(fields, [field("Web", "kitty.example")]);
+
+        replace_field(&mut fields, "Now playing", Some("Purr"), 4).unwrap();
+        assert_eq!(
+            fields,
+            [field("Web", "kitty.example"), field("Now playing", "Purr")]
+        );
+    }
+
+    #[test]
+    fn test_replace_field_over_the_limit() {
+        let mut fields = vec![field("Web", "kitty.example"), field("Pronouns", "they")];
+
+        let error = replace_field(&mut fields, "Now playing", Some("Meow"), 2).unwrap_err();
+
+        assert!(error.is_fatal());
+        assert_eq!(fields.len(), 2);
+        // Replacing a field doesn't add one.
+        replace_field(&mut fields, "Web", Some("meow.example"), 2).unwrap();
+    }
+
+    #[test]
+    fn test_fields_attributes() {
+        let fields = [field("Web", "kitty.example"), field("Now playing", "Meow")];
+
+        assert_eq!(
+            fields_attributes(&fields),
+            [
+                ("fields_attributes[0][name]".to_string(), "Web"),
+                ("fields_attributes[0][value]".to_string(), "kitty.example"),
+                ("fields_attributes[1][name]".to_string(), "Now playing"),
+                ("fields_attributes[1][value]".to_string(), "Meow"),
+            ]
+        );
+        assert_eq!(
+            fields_attributes(&[]),
+            [
+                ("fields_attributes[0][name]".to_string(), ""),
+                ("fields_attributes[0][value]".to_string(), ""),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_shorten() {
+        let value = "é".repeat(MAX_VALUE_LENGTH);
+        assert_eq!(shorten(value.clone()), value);
+
+        let shortened = shorten("🎵".repeat(MAX_VALUE_LENGTH + 1));
+        assert_eq!(shortened.chars().count(), MAX_VALUE_LENGTH);
+        assert!(shortened.starts_with("🎵"));
+        assert!(shortened.ends_with("🎵…"));
+    }
+}
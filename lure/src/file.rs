@@ -0,0 +1,216 @@
+use std::path::{Path, PathBuf};
+
+use lure_config::file::{Format, Options};
+use lure_types::{TrackInfo, stats::Stats, template};
+
+use crate::metrics::ProfileMetrics;
+use crate::sink::{Event, Sink, SinkError, SinkOptions};
+
+/// A file kept up to date with the current track, for stream overlays.
+pub struct FileSink {
+    options: Options,
+    /// Whether the contents are only logged.
+    dry_run: bool,
+    /// The contents last written by lure.
+    written: Option<String>,
+}
+
+/// The contents of a JSON file.
+#[derive(serde::Serialize)]
+struct NowPlaying<'a> {
+    playing: bool,
+    /// The current track, or `None` if it's hidden by a privacy filter.
+    track: Option<&'a TrackInfo>,
+    /// The rendered template, or `None` if nothing is playing and no idle
+    /// text is configured.
+    text: Option<String>,
+}
+
+impl FileSink {
+    pub const fn new(options: Options, dry_run: bool) -> Self {
+        Self {
+            options,
+            dry_run,
+            written: None,
+        }
+    }
+
+    /// Renders the contents of the file for `event`. Tracks shown with the
+    /// replacement template of a privacy filter are left out of the JSON.
+    fn contents(&self, event: &Event<'_>, stats: &Stats) -> Result<String, SinkError> {
+        let (playing, track, text) = match *event {
+            Event::Playing { track, replacement } => (
+                true,
+                replacement.is_none().then_some(track),
+                Some(template::render(
+                    replacement.unwrap_or(&self.options.template),
+                    Some(track),
+                    stats,
+                )),
+            ),
+            Event::Idle => (
+                false,
+                None,
+                self.options
+                    .idle
+                    .as_deref()
+                    .map(|idle| template::render(idle, None, stats)),
+            ),
+        };
+
+        Ok(match self.options.format {
+            Format::Text => text.unwrap_or_default(),
+            Format::Json => serde_json::to_string_pretty(&NowPlaying {
+                playing,
+                track,
+                text,
+            })?,
+        })
+    }
+
+    /// Writes the contents if they changed, through a temporary file
+    /// renamed over the file, so that readers never see a partial write.
+    async fn write(&mut self, contents: String) -> Result<(), SinkError> {
+        if self.written.as_ref() == Some(&contents) {
+            return Ok(());
+        }
+
+        if self.dry_run {
+            println!(
+                "File ({}) dry run, contents set to: {contents}",
+                self.options.path.display()
+            );
+        } else {
+            let temporary = temporary_path(&self.options.path);
+            tokio::fs::write(&temporary, &contents).await?;
+            tokio::fs::rename(&temporary, &self.options.path).await?;
+        }
+        self.written = Some(contents);
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Sink for FileSink {
+    fn name(&self) -> String {
+        format!("file {}", self.options.path.display())
+    }
+
+    /// Accepts the options of the same file, in the same dry run mode.
+    fn accepts(&self, options: &SinkOptions) -> bool {
+        let SinkOptions::File { options, dry_run } = options else {
+            return false;
+        };
+
+        *dry_run == self.dry_run && self.options.path == options.path
+    }
+
+    fn reconfigure(&mut self, options: SinkOptions) {
+        debug_assert!(self.accepts(&options));
+
+        if let SinkOptions::File { options, .. } = options {
+            self.options = options;
+        }
+    }
+
+    async fn update(
+        &mut self,
+        event: &Event<'_>,
+        stats: &Stats,
+        _metrics: &ProfileMetrics,
+    ) -> Result<(), SinkError> {
+        let contents = self.contents(event, stats)?;
+
+        self.write(contents).await
+    }
+
+    /// Writes the idle text, since an overlay shouldn't keep showing a
+    /// track once lure is no longer following the playback.
+    async fn reset(&mut self) -> Result<(), SinkError> {
+        let contents = self.contents(&Event::Idle, &Stats::default())?;
+
+        self.write(contents).await
+    }
+}
+
+/// Returns the path of the temporary file for `path`, next to it so that
+/// the rename doesn't cross file systems.
+fn temporary_path(path: &Path) -> PathBuf {
+    let mut name = std::ffi::OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(".tmp");
+
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::metrics::Metrics;
+
+    fn track() -> TrackInfo {
+        TrackInfo {
+            artist: String::from("Cat"),
+            title: String::from("Meow"),
+            album: None,
+            cover_url: None,
+            url: None,
+        }
+    }
+
+    fn sink(path: &Path, yaml: &str) -> FileSink {
+        let mut options: Options = serde_yaml::from_str(yaml).unwrap();
+        options.path = path.to_path_buf();
+
+        FileSink::new(options, false)
+    }
+
+    #[tokio::test]
+    async fn test_text() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("now-playing.txt");
+        let mut sink = sink(&path, "{path: '', idle: Nothing playing}");
+        let metrics = Arc::new(Metrics::new()).profile("kitty");
+        let track = track();
+
+        let event = Event::Playing {
+            track: &track,
+            replacement: None,
+        };
+        sink.update(&event, &Stats::default(), &metrics)
+            .await
+            .unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "Meow by Cat");
+
+        sink.reset().await.unwrap();
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "Nothing playing");
+        assert!(!temporary_path(&path).exists());
+    }
+
+    #[tokio::test]
+    async fn test_json_leaves_hidden_tracks_out() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("now-playing.json");
+        let mut sink = sink(&path, "{path: '', format: json}");
+        let metrics = Arc::new(Metrics::new()).profile("kitty");
+        let track = track();
+
+        let event = Event::Playing {
+            track: &track,
+            replacement: Some("Something"),
+        };
+        sink.update(&event, &Stats::default(), &metrics)
+            .await
+            .unwrap();
+        let contents: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+
+        assert_eq!(
+            contents,
+            serde_json::json!({"playing": true, "track": null, "text": "Something"})
+        );
+    }
+}
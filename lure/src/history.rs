@@ -0,0 +1,171 @@
+use std::path::{Path, PathBuf};
+
+use jiff::{Timestamp, Zoned, civil::Date, tz::TimeZone};
+use lure_history::{History, Query};
+
+use crate::cli::{HistoryCommand, HistoryFilter};
+use crate::start::{RunError, ServiceError};
+
+/// What to list from the local listening history.
+enum Listing {
+    Plays,
+    TopArtists,
+    TopTracks,
+}
+
+pub async fn run(
+    config_path: &Path,
+    filter: &HistoryFilter,
+    command: Option<HistoryCommand>,
+) -> Result<(), HistoryError> {
+    let listing = match command {
+        None => Listing::Plays,
+        Some(HistoryCommand::TopArtists) => Listing::TopArtists,
+        Some(HistoryCommand::TopTracks) => Listing::TopTracks,
+        Some(HistoryCommand::Import { output, format }) => {
+            return crate::import::run(config_path, filter, output.as_deref(), format).await;
+        }
+    };
+
+    show(config_path, filter, &listing)
+}
+
+/// Prints the plays, or the top artists or tracks, from the local listening
+/// history.
+fn show(config_path: &Path, filter: &HistoryFilter, listing: &Listing) -> Result<(), HistoryError> {
+    let config = lure_config::load(config_path)?;
+    let path = config.history.path();
+
+    if !path.exists() {
+        return Err(HistoryError::NotFound(path));
+    }
+
+    let history = History::open(&path)?;
+    let query = Query {
+        profile: filter.profile.clone(),
+        from: filter.from.map(Timestamp::as_second),
+        to: filter.to.map(Timestamp::as_second),
+        limit: filter.limit,
+    };
+
+    let lines: Vec<String> = match listing {
+        Listing::Plays => history
+            .plays(&query)?
+            .into_iter()
+            .map(|play| {
+                Ok(format!(
+                    "{}  {:>7}  {} - {} ({}, {})",
+                    format_timestamp(play.started_at)?,
+                    play.ended_at.map_or_else(
+                        || "-".to_string(),
+                        |ended_at| format_duration(ended_at - play.started_at)
+                    ),
+                    play.track.artist,
+                    play.track.title,
+                    play.profile,
+                    play.service
+                ))
+            })
+            .collect::<Result<_, HistoryError>>()?,
+        Listing::TopArtists => history
+            .top_artists(&query)?
+            .into_iter()
+            .enumerate()
+            .map(|(index, artist)| {
+                format!(
+                    "{:>3}. {} ({} plays, {})",
+                    index + 1,
+                    artist.artist,
+                    artist.plays,
+                    format_duration(artist.duration)
+                )
+            })
+            .collect(),
+        Listing::TopTracks => history
+            .top_tracks(&query)?
+            .into_iter()
+            .enumerate()
+            .map(|(index, track)| {
+                format!(
+                    "{:>3}. {} - {} ({} plays, {})",
+                    index + 1,
+                    track.track.artist,
+                    track.track.title,
+                    track.plays,
+                    format_duration(track.duration)
+                )
+            })
+            .collect(),
+    };
+
+    if lines.is_empty() {
+        println!("No plays found.");
+    }
+    for line in lines {
+        println!("{line}");
+    }
+
+    Ok(())
+}
+
+/// Parses a time given on the command line: a Unix timestamp, an RFC 3339
+/// timestamp, a date (midnight in the system time zone), or a duration ago,
+/// like `7d`.
+pub fn parse_time(value: &str) -> Result<Timestamp, jiff::Error> {
+    if let Ok(seconds) = value.parse::<i64>() {
+        return Timestamp::from_second(seconds);
+    }
+
+    if let Ok(timestamp) = value.parse::<Timestamp>() {
+        return Ok(timestamp);
+    }
+
+    if let Ok(date) = value.parse::<Date>() {
+        return Ok(date.to_zoned(TimeZone::system())?.timestamp());
+    }
+
+    let span: jiff::Span = value.parse()?;
+
+    Ok(Zoned::now().checked_sub(span)?.timestamp())
+}
+
+fn format_timestamp(timestamp: i64) -> Result<String, jiff::Error> {
+    Ok(Timestamp::from_second(timestamp)?
+        .to_zoned(TimeZone::system())
+        .strftime("%Y-%m-%d %H:%M")
+        .to_string())
+}
+
+fn format_duration(seconds: i64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+
+    if hours > 0 {
+        format!("{hours}:{minutes:02}:{seconds:02}")
+    } else {
+        format!("{minutes}:{seconds:02}")
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum HistoryError {
+    #[error(transparent)]
+    Config(#[from] lure_config::Diagnostics),
+    #[error("No listening history found at {}. Enable `history` in the configuration to record it.", .0.display())]
+    NotFound(PathBuf),
+    #[error(transparent)]
+    History(#[from] lure_history::Error),
+    #[error(transparent)]
+    Time(#[from] jiff::Error),
+    #[error("No profile named `{0}` is configured.")]
+    ProfileNotFound(String),
+    #[error(transparent)]
+    Run(#[from] RunError),
+    #[error(transparent)]
+    Service(#[from] ServiceError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+}
@@ -0,0 +1,465 @@
+use std::process::{ExitStatus, Stdio};
+use std::sync::Arc;
+use std::time::Duration;
+
+use lure_config::hook::{Event as HookEvent, Options};
+use lure_types::{TrackInfo, stats::Stats};
+use tokio::io::AsyncWriteExt as _;
+use tokio::process::Command;
+use tokio::sync::{Semaphore, mpsc};
+use tokio::task::{JoinHandle, JoinSet};
+
+use crate::metrics::ProfileMetrics;
+use crate::sink::{Event, Sink, SinkError, SinkOptions};
+
+/// How long the pending runs are given to finish when the hook is removed
+/// or lure stops.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A command run on the playback events, with the event passed as
+/// `LURE_*` environment variables and as JSON on its standard input.
+///
+/// Runs are started in order by a background task, at most
+/// `max_concurrent` at once, so a slow command never holds back the other
+/// sinks.
+pub struct HookSink {
+    options: Options,
+    profile: String,
+    service: &'static str,
+    /// What's playing as last seen, or `None` if nothing is.
+    playing: Option<Playing>,
+    /// Whether the service is failing, so that only the first error of a
+    /// streak runs the command.
+    failing: bool,
+    /// The queue of the running task, closed on restore.
+    runs: Option<mpsc::UnboundedSender<Run>>,
+    /// The running task, or `None` in dry run, where the runs are only
+    /// logged.
+    worker: Option<JoinHandle<()>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Playing {
+    /// The current track, or `None` if it's hidden by a privacy filter.
+    track: Option<TrackInfo>,
+    /// Unix timestamp of when the track started.
+    started_at: i64,
+}
+
+#[derive(serde::Serialize)]
+struct Payload<'a> {
+    event: HookEvent,
+    profile: &'a str,
+    service: &'a str,
+    /// The current track, or `None` if nothing is playing or the track is
+    /// hidden by a privacy filter.
+    track: Option<&'a TrackInfo>,
+    started_at: Option<i64>,
+    /// Unix timestamp of the event.
+    timestamp: i64,
+    /// The error of the service, for error events.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<&'a str>,
+}
+
+/// A run of the command.
+#[derive(Debug)]
+struct Run {
+    environment: Vec<(&'static str, String)>,
+    stdin: String,
+}
+
+impl HookSink {
+    pub fn new(options: Options, profile: String, service: &'static str, dry_run: bool) -> Self {
+        let (runs, worker) = if dry_run {
+            (None, None)
+        } else {
+            let (sender, receiver) = mpsc::unbounded_channel();
+            let worker = tokio::spawn(run_all(options.clone(), receiver));
+
+            (Some(sender), Some(worker))
+        };
+
+        Self {
+            options,
+            profile,
+            service,
+            playing: None,
+            failing: false,
+            runs,
+            worker,
+        }
+    }
+
+    /// Runs the command for the current track if it changed.
+    fn change(&mut self, track: Option<Option<&TrackInfo>>) -> Result<(), SinkError> {
+        let event = match (&self.playing, track) {
+            (None, None) => return Ok(()),
+            (Some(playing), Some(track)) if playing.track.as_ref() == track => return Ok(()),
+            (None, Some(_)) => HookEvent::Started,
+            (Some(_), Some(_)) => HookEvent::Changed,
+            (Some(_), None) => HookEvent::Stopped,
+        };
+
+        self.playing = track.map(|track| Playing {
+            track: track.cloned(),
+            started_at: jiff::Timestamp::now().as_second(),
+        });
+
+        self.run(event, None)
+    }
+
+    /// Queues a run for `event` if the hook is interested in it.
+    fn run(&self, event: HookEvent, error: Option<&str>) -> Result<(), SinkError> {
+        if !self.options.events.contains(&event) {
+            return Ok(());
+        }
+
+        let track = self
+            .playing
+            .as_ref()
+            .and_then(|playing| playing.track.as_ref());
+        let stdin = serde_json::to_string(&Payload {
+            event,
+            profile: &self.profile,
+            service: self.service,
+            track,
+            started_at: self.playing.as_ref().map(|playing| playing.started_at),
+            timestamp: jiff::Timestamp::now().as_second(),
+            error,
+        })?;
+
+        if self.worker.is_none() {
+            println!(
+                "Hook ({}) dry run, running with: {stdin}",
+                self.options.program()
+            );
+            return Ok(());
+        }
+
+        let mut environment = vec![
+            ("LURE_EVENT", event_name(event).to_string()),
+            ("LURE_PROFILE", self.profile.clone()),
+            ("LURE_SERVICE", self.service.to_string()),
+        ];
+        if let Some(track) = track {
+            environment.extend([
+                ("LURE_ARTIST", track.artist.clone()),
+                ("LURE_TITLE", track.title.clone()),
+                ("LURE_ALBUM", track.album.clone().unwrap_or_default()),
+                (
+                    "LURE_COVER_URL",
+                    track.cover_url.clone().unwrap_or_default(),
+                ),
+                ("LURE_URL", track.url.clone().unwrap_or_default()),
+            ]);
+        }
+        if let Some(error) = error {
+            environment.push(("LURE_ERROR", error.to_string()));
+        }
+
+        // The queue is only closed by a restore, after which runs are
+        // dropped.
+        if let Some(runs) = &self.runs {
+            let _ = runs.send(Run { environment, stdin });
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for HookSink {
+    fn drop(&mut self) {
+        if let Some(worker) = &self.worker {
+            worker.abort();
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Sink for HookSink {
+    fn name(&self) -> String {
+        format!("hook {}", self.options.program())
+    }
+
+    /// Accepts the same options in the same dry run mode, since the
+    /// running task is bound to them.
+    fn accepts(&self, options: &SinkOptions) -> bool {
+        let SinkOptions::Hook {
+            options, dry_run, ..
+        } = options
+        else {
+            return false;
+        };
+
+        *dry_run == self.worker.is_none() && self.options == *options
+    }
+
+    fn reconfigure(&mut self, options: SinkOptions) {
+        debug_assert!(self.accepts(&options));
+
+        if let SinkOptions::Hook {
+            profile, service, ..
+        } = options
+        {
+            self.profile = profile;
+            self.service = service;
+        }
+    }
+
+    async fn update(
+        &mut self,
+        event: &Event<'_>,
+        _stats: &Stats,
+        _metrics: &ProfileMetrics,
+    ) -> Result<(), SinkError> {
+        self.failing = false;
+
+        self.change(match *event {
+            Event::Playing { track, replacement } => Some(replacement.is_none().then_some(track)),
+            Event::Idle => None,
+        })
+    }
+
+    /// Runs the command on the first error of a streak.
+    async fn error(&mut self, error: &str) -> Result<(), SinkError> {
+        if self.failing {
+            return Ok(());
+        }
+        self.failing = true;
+
+        self.run(HookEvent::Error, Some(error))
+    }
+
+    /// Runs the command for a stop if something was playing.
+    async fn reset(&mut self) -> Result<(), SinkError> {
+        self.change(None)
+    }
+
+    /// Runs the command for a stop if something was playing, and gives the
+    /// pending runs a few seconds to finish.
+    async fn restore(&mut self) -> Result<(), SinkError> {
+        self.change(None)?;
+
+        // Closing the queue lets the worker return once every run is done.
+        self.runs = None;
+        let Some(worker) = &mut self.worker else {
+            return Ok(());
+        };
+
+        if tokio::time::timeout(FLUSH_TIMEOUT, worker).await.is_err() {
+            eprintln!(
+                "Hook ({}) runs didn't finish in time, killing them.",
+                self.options.program()
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Starts the queued runs in order, at most `max_concurrent` at once, until
+/// the queue is closed, then waits for the remaining ones.
+async fn run_all(options: Options, mut receiver: mpsc::UnboundedReceiver<Run>) {
+    let options = Arc::new(options);
+    let semaphore = Arc::new(Semaphore::new(options.max_concurrent));
+    let mut tasks = JoinSet::new();
+
+    while let Some(run) = receiver.recv().await {
+        let Ok(permit) = Arc::clone(&semaphore).acquire_owned().await else {
+            return;
+        };
+        let options = Arc::clone(&options);
+        tasks.spawn(async move {
+            if let Err(error) = execute(&options, run).await {
+                eprintln!("Hook ({}) {error}", options.program());
+            }
+            drop(permit);
+        });
+
+        while tasks.try_join_next().is_some() {}
+    }
+
+    while tasks.join_next().await.is_some() {}
+}
+
+const fn event_name(event: HookEvent) -> &'static str {
+    match event {
+        HookEvent::Started => "started",
+        HookEvent::Changed => "changed",
+        HookEvent::Stopped => "stopped",
+        HookEvent::Error => "error",
+    }
+}
+
+/// Runs the command once, killing it if it takes longer than the timeout.
+async fn execute(options: &Options, run: Run) -> Result<(), HookError> {
+    let (program, arguments) = options.command.split_first().ok_or(HookError::NoCommand)?;
+    let mut child = Command::new(program)
+        .args(arguments)
+        .envs(run.environment)
+        .stdin(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(HookError::Start)?;
+
+    let stdin = child.stdin.take();
+    let result = tokio::time::timeout(Duration::from_secs(options.timeout), async {
+        if let Some(mut stdin) = stdin {
+            // The command doesn't have to read it.
+            let _ = stdin.write_all(run.stdin.as_bytes()).await;
+        }
+
+        child.wait().await
+    })
+    .await;
+
+    match result {
+        Ok(Ok(status)) if status.success() => Ok(()),
+        Ok(Ok(status)) => Err(HookError::Exited(status)),
+        Ok(Err(error)) => Err(HookError::Wait(error)),
+        Err(_) => {
+            let _ = child.kill().await;
+            Err(HookError::TimedOut(options.timeout))
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+enum HookError {
+    #[error("has no command to run.")]
+    NoCommand,
+    #[error("failed to start: {0}")]
+    Start(std::io::Error),
+    #[error("exited with {0}.")]
+    Exited(ExitStatus),
+    #[error("failed: {0}")]
+    Wait(std::io::Error),
+    #[error("timed out after {0}s, killed it.")]
+    TimedOut(u64),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use super::*;
+    use crate::metrics::Metrics;
+
+    fn options(command: &[&str]) -> Options {
+        Options {
+            command: command.iter().map(ToString::to_string).collect(),
+            events: vec![
+                HookEvent::Started,
+                HookEvent::Changed,
+                HookEvent::Stopped,
+                HookEvent::Error,
+            ],
+            timeout: 1,
+            max_concurrent: 1,
+        }
+    }
+
+    fn run() -> Run {
+        Run {
+            environment: Vec::new(),
+            stdin: String::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_passes_the_event() {
+        let directory = tempfile::tempdir().unwrap();
+        let output = directory.path().join("output");
+        let mut sink = HookSink::new(
+            options(&[
+                "sh",
+                "-c",
+                r#"echo "$LURE_EVENT $LURE_PROFILE $LURE_ARTIST - $LURE_TITLE" >> "$0"; cat >> "$0"; echo >> "$0""#,
+                output.to_str().unwrap(),
+            ]),
+            String::from("kitty"),
+            "lastfm",
+            false,
+        );
+        let track = TrackInfo {
+            artist: String::from("Cat"),
+            title: String::from("Meow"),
+            album: None,
+            cover_url: None,
+            url: None,
+        };
+        let metrics = Arc::new(Metrics::new()).profile("kitty");
+
+        sink.update(
+            &Event::Playing {
+                track: &track,
+                replacement: None,
+            },
+            &Stats::default(),
+            &metrics,
+        )
+        .await
+        .unwrap();
+        sink.restore().await.unwrap();
+
+        let output = std::fs::read_to_string(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines[0], "started kitty Cat - Meow");
+        let payload: serde_json::Value = serde_json::from_str(lines[1]).unwrap();
+        assert_eq!(payload["event"], "started");
+        assert_eq!(payload["service"], "lastfm");
+        assert_eq!(payload["track"]["title"], "Meow");
+        assert_eq!(lines[2], "stopped kitty  - ");
+        let payload: serde_json::Value = serde_json::from_str(lines[3]).unwrap();
+        assert_eq!(payload["event"], "stopped");
+        assert!(payload["track"].is_null());
+    }
+
+    #[tokio::test]
+    async fn test_kills_the_command_on_timeout() {
+        let started_at = Instant::now();
+
+        let result = execute(&options(&["sleep", "10"]), run()).await;
+
+        assert!(matches!(result, Err(HookError::TimedOut(1))), "{result:?}");
+        assert!(started_at.elapsed() < Duration::from_secs(5));
+    }
+
+    #[tokio::test]
+    async fn test_empty_command() {
+        let result = execute(&options(&[]), run()).await;
+
+        assert!(matches!(result, Err(HookError::NoCommand)), "{result:?}");
+    }
+
+    #[tokio::test]
+    async fn test_limits_the_concurrent_runs() {
+        let directory = tempfile::tempdir().unwrap();
+        let output = directory.path().join("output");
+        let options = Options {
+            max_concurrent: 2,
+            ..options(&[
+                "sh",
+                "-c",
+                r#"echo + >> "$0"; sleep 0.3; echo - >> "$0""#,
+                output.to_str().unwrap(),
+            ])
+        };
+
+        let (sender, receiver) = mpsc::unbounded_channel();
+        for _ in 0..5 {
+            sender.send(run()).unwrap();
+        }
+        drop(sender);
+        run_all(options, receiver).await;
+
+        let output = std::fs::read_to_string(output).unwrap();
+        let running = output.lines().scan(0, |running, line| {
+            *running += if line == "+" { 1 } else { -1 };
+            Some(*running)
+        });
+        assert_eq!(running.max(), Some(2));
+        assert_eq!(output.lines().count(), 10);
+    }
+}
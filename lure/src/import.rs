@@ -0,0 +1,369 @@
+use std::ffi::OsString;
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Write as _};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use jiff::Timestamp;
+use lure_history::History;
+use lure_types::Scrobble;
+use tokio::time::sleep;
+
+use crate::cli::{ExportFormat, HistoryFilter};
+use crate::history::HistoryError;
+use crate::service::Service;
+
+/// Number of attempts to fetch a page before giving up.
+const MAX_ATTEMPTS: u32 = 5;
+/// Delay before the first retry, doubled after every attempt.
+const INITIAL_BACKOFF: Duration = Duration::from_secs(2);
+/// Delay between pages, to stay well within the rate limits.
+const PAGE_DELAY: Duration = Duration::from_millis(250);
+
+/// Progress of an import, saved after every page so that an interrupted
+/// import can be resumed.
+#[derive(Debug, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+struct Checkpoint {
+    profile: String,
+    service: String,
+    from: Option<i64>,
+    to: i64,
+    /// The cursor of the next page to fetch.
+    cursor: Option<i64>,
+    imported: usize,
+    /// Length of the output file once the pages before `cursor` were
+    /// written to it. The file is truncated back to it on resume, since the
+    /// page being written when the import stopped is fetched again.
+    #[serde(default)]
+    output_length: Option<u64>,
+}
+
+enum Sink {
+    Store(History),
+    Jsonl(BufWriter<File>),
+    Csv(Box<csv::Writer<File>>),
+}
+
+/// Imports the listening history of a profile from its listening service,
+/// either to the local history database or to a JSONL or CSV file.
+pub async fn run(
+    config_path: &Path,
+    filter: &HistoryFilter,
+    output: Option<&Path>,
+    format: Option<ExportFormat>,
+) -> Result<(), HistoryError> {
+    let config = lure_config::load(config_path)?;
+    let profiles = config.profiles();
+    let profile = match &filter.profile {
+        Some(name) => profiles
+            .into_iter()
+            .find(|profile| &profile.name == name)
+            .ok_or_else(|| HistoryError::ProfileNotFound(name.clone()))?,
+        None => profiles.into_iter().next().ok_or_else(|| {
+            HistoryError::ProfileNotFound(lure_config::profile::DEFAULT_PROFILE_NAME.to_string())
+        })?,
+    };
+
+    let service = Service::try_from_options(profile.service, &reqwest::Client::new())?;
+
+    let target = output.map_or_else(|| config.history.path(), Path::to_path_buf);
+    let checkpoint_path = checkpoint_path(&target, &profile.name);
+
+    let mut checkpoint = Checkpoint {
+        profile: profile.name,
+        service: service.name().to_string(),
+        from: filter.from.map(Timestamp::as_second),
+        to: filter.to.unwrap_or_else(Timestamp::now).as_second(),
+        cursor: None,
+        imported: 0,
+        output_length: None,
+    };
+    let resumed = Checkpoint::load(&checkpoint_path).filter(|previous| {
+        previous.profile == checkpoint.profile
+            && previous.service == checkpoint.service
+            && previous.from == checkpoint.from
+            && filter.to.is_none_or(|to| previous.to == to.as_second())
+    });
+    if let Some(resumed) = resumed {
+        println!(
+            "Resuming the previous import, {} scrobbles were imported so far.",
+            resumed.imported
+        );
+        checkpoint = resumed;
+    }
+
+    let mut sink = Sink::open(
+        output,
+        format,
+        &target,
+        checkpoint.cursor.is_some(),
+        checkpoint.output_length,
+    )?;
+
+    loop {
+        let page = fetch_page(&service, &checkpoint).await?;
+
+        checkpoint.imported += sink.write(&checkpoint.profile, service.name(), &page.scrobbles)?;
+        checkpoint.cursor = page.next;
+
+        if checkpoint.cursor.is_none() {
+            break;
+        }
+
+        sink.flush()?;
+        checkpoint.output_length = sink.length()?;
+        checkpoint.save(&checkpoint_path)?;
+        println!("Imported {} scrobbles so far...", checkpoint.imported);
+
+        sleep(PAGE_DELAY).await;
+    }
+
+    sink.flush()?;
+    if checkpoint_path.exists() {
+        std::fs::remove_file(&checkpoint_path)?;
+    }
+
+    println!(
+        "Imported {} scrobbles from {} to {}.",
+        checkpoint.imported,
+        service.name(),
+        target.display()
+    );
+
+    Ok(())
+}
+
+/// Fetches the next page, retrying with an exponential backoff, or after
+/// the delay the service asked for.
+async fn fetch_page(
+    service: &Service,
+    checkpoint: &Checkpoint,
+) -> Result<lure_types::HistoryPage, HistoryError> {
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 1;
+
+    loop {
+        let error = match service
+            .history_page(checkpoint.from, Some(checkpoint.to), checkpoint.cursor)
+            .await
+        {
+            Ok(page) => return Ok(page),
+            Err(error) => error,
+        };
+
+        if attempt >= MAX_ATTEMPTS || service.is_fatal_error(&error) {
+            eprintln!("Failed to import the history. Run the command again to resume.");
+            return Err(error.into());
+        }
+
+        let delay = Service::retry_after(&error).unwrap_or(backoff);
+        eprintln!(
+            "Failed to fetch the history, retrying in {}s: {error}",
+            delay.as_secs()
+        );
+        sleep(delay).await;
+        backoff *= 2;
+        attempt += 1;
+    }
+}
+
+/// Returns the path of the checkpoint of an import to `target`.
+fn checkpoint_path(target: &Path, profile: &str) -> PathBuf {
+    let mut path = OsString::from(target.as_os_str());
+    path.push(format!(".{profile}.checkpoint"));
+
+    PathBuf::from(path)
+}
+
+impl Checkpoint {
+    fn load(path: &Path) -> Option<Self> {
+        let data = std::fs::read(path).ok()?;
+
+        serde_json::from_slice(&data)
+            .inspect_err(|error| eprintln!("Ignoring the invalid import checkpoint: {error}"))
+            .ok()
+    }
+
+    /// Writes the checkpoint to a temporary file first, so that it's never
+    /// left half-written.
+    fn save(&self, path: &Path) -> Result<(), HistoryError> {
+        let mut temporary = OsString::from(path.as_os_str());
+        temporary.push(".tmp");
+
+        std::fs::write(&temporary, serde_json::to_vec(self)?)?;
+        std::fs::rename(&temporary, path)?;
+
+        Ok(())
+    }
+}
+
+impl Sink {
+    /// Opens the local history database, or the output file. The output
+    /// file is appended to when `resume` is set, after truncating it to
+    /// `length` if known, and truncated otherwise.
+    ///
+    /// The database needs no truncating, since importing the same scrobbles
+    /// again doesn't duplicate them.
+    fn open(
+        output: Option<&Path>,
+        format: Option<ExportFormat>,
+        target: &Path,
+        resume: bool,
+        length: Option<u64>,
+    ) -> Result<Self, HistoryError> {
+        let Some(output) = output else {
+            return Ok(Self::Store(History::open(target)?));
+        };
+
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(resume)
+            .truncate(!resume)
+            .open(output)?;
+        if resume && let Some(length) = length {
+            file.set_len(length)?;
+        }
+
+        let format = format.unwrap_or_else(|| {
+            if output
+                .extension()
+                .is_some_and(|extension| extension == "csv")
+            {
+                ExportFormat::Csv
+            } else {
+                ExportFormat::Jsonl
+            }
+        });
+
+        Ok(match format {
+            ExportFormat::Jsonl => Self::Jsonl(BufWriter::new(file)),
+            ExportFormat::Csv => Self::Csv(Box::new(
+                csv::WriterBuilder::new()
+                    .has_headers(!resume)
+                    .from_writer(file),
+            )),
+        })
+    }
+
+    /// Writes `scrobbles`, and returns how many of them were new.
+    fn write(
+        &mut self,
+        profile: &str,
+        service: &str,
+        scrobbles: &[Scrobble],
+    ) -> Result<usize, HistoryError> {
+        match self {
+            Self::Store(history) => return Ok(history.import(profile, service, scrobbles)?),
+            Self::Jsonl(writer) => {
+                for scrobble in scrobbles {
+                    serde_json::to_writer(&mut *writer, &Record::new(scrobble))?;
+                    writer.write_all(b"\n")?;
+                }
+            }
+            Self::Csv(writer) => {
+                for scrobble in scrobbles {
+                    writer.serialize(Record::new(scrobble))?;
+                }
+            }
+        }
+
+        Ok(scrobbles.len())
+    }
+
+    /// Returns the length of the output file, or `None` for the database.
+    fn length(&self) -> Result<Option<u64>, HistoryError> {
+        Ok(match self {
+            Self::Store(_) => None,
+            Self::Jsonl(writer) => Some(writer.get_ref().metadata()?.len()),
+            Self::Csv(writer) => Some(writer.get_ref().metadata()?.len()),
+        })
+    }
+
+    fn flush(&mut self) -> Result<(), HistoryError> {
+        match self {
+            Self::Store(_) => {}
+            Self::Jsonl(writer) => writer.flush()?,
+            Self::Csv(writer) => writer.flush()?,
+        }
+
+        Ok(())
+    }
+}
+
+/// A scrobble as it's exported. Flat, so that it can be written as CSV.
+#[derive(serde::Serialize)]
+struct Record<'a> {
+    listened_at: i64,
+    artist: &'a str,
+    title: &'a str,
+    album: Option<&'a str>,
+}
+
+impl<'a> Record<'a> {
+    fn new(scrobble: &'a Scrobble) -> Self {
+        Self {
+            listened_at: scrobble.listened_at,
+            artist: scrobble.track.artist.as_str(),
+            title: scrobble.track.title.as_str(),
+            album: scrobble.track.album.as_deref(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use lure_types::TrackInfo;
+
+    use super::*;
+
+    fn scrobble(title: &str, listened_at: i64) -> Scrobble {
+        Scrobble {
+            track: TrackInfo {
+                artist: String::from("Cat"),
+                title: title.to_string(),
+                album: Some(String::from("Purr")),
+                cover_url: None,
+                url: None,
+            },
+            listened_at,
+        }
+    }
+
+    fn export(path: &Path, resume: bool, length: Option<u64>, scrobbles: &[Scrobble]) -> u64 {
+        let mut sink = Sink::open(Some(path), None, path, resume, length).unwrap();
+        sink.write("kitty", "lastfm", scrobbles).unwrap();
+        sink.flush().unwrap();
+
+        sink.length().unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_jsonl() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("history.jsonl");
+
+        export(&path, false, None, &[scrobble("Meow", 2000)]);
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "{\"listened_at\":2000,\"artist\":\"Cat\",\"title\":\"Meow\",\"album\":\"Purr\"}\n"
+        );
+    }
+
+    #[test]
+    fn test_resume_drops_what_was_written_after_the_checkpoint() {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("history.csv");
+
+        let length = export(&path, false, None, &[scrobble("Meow", 2000)]);
+        // Written, but the import stopped before saving the checkpoint.
+        export(&path, true, None, &[scrobble("Purr", 1000)]);
+        export(&path, true, Some(length), &[scrobble("Purr", 1000)]);
+
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "listened_at,artist,title,album\n2000,Cat,Meow,Purr\n1000,Cat,Purr,Purr\n"
+        );
+    }
+}
@@ -1,8 +1,44 @@
+use clap::Parser as _;
+
+use crate::cli::{Cli, Command, ConfigCommand};
+
+mod bot;
+mod channel;
+mod cli;
+mod config;
+mod discord;
+mod fediverse;
+mod file;
+mod history;
+mod hook;
+mod import;
+mod matrix;
+mod metrics;
+mod mqtt;
+mod reload;
+mod runner;
+mod server;
 mod service;
+mod sink;
 mod start;
+mod state;
+mod stats;
+mod stoat;
+mod stoat_profile;
+mod supervisor;
+mod webhook;
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
-    let config_path = std::env::args().nth(1).map(std::path::PathBuf::from);
-    Ok(start::run(config_path).await?)
+    let cli = Cli::parse();
+
+    match cli.command {
+        Command::Start { dry_run } => start::run(&cli.config, dry_run).await?,
+        Command::Config {
+            command: ConfigCommand::Check { online },
+        } => config::check(&cli.config, online).await?,
+        Command::History { filter, command } => history::run(&cli.config, &filter, command).await?,
+    }
+
+    Ok(())
 }
@@ -0,0 +1,321 @@
+use std::fmt::Write as _;
+
+use lure_config::matrix::Options;
+use lure_types::{stats::Stats, template};
+use secrecy::ExposeSecret as _;
+
+use crate::metrics::ProfileMetrics;
+use crate::sink::{Event, Sink, SinkError, SinkOptions};
+
+/// A Matrix account whose presence status message is kept in sync with the
+/// listening activity.
+///
+/// The presence itself (`online`, `unavailable` or `offline`) is left as it
+/// is: it's fetched again before every change, since the API requires it
+/// along the status message.
+pub struct MatrixSink {
+    options: Options,
+    /// The API client, or `None` in dry run, where the status messages are
+    /// only logged.
+    client: Option<Client>,
+    /// The status message the account had before lure started.
+    first_status: Option<String>,
+    /// The status message last set by lure.
+    applied_status: Option<String>,
+}
+
+struct Client {
+    http_client: reqwest::Client,
+    /// URL of the presence status of the account.
+    url: String,
+    /// The user ID of the account, used in logs.
+    user_id: String,
+}
+
+#[derive(serde::Deserialize)]
+struct WhoAmI {
+    user_id: String,
+}
+
+#[derive(serde::Deserialize)]
+struct Presence {
+    presence: String,
+    #[serde(default)]
+    status_msg: Option<String>,
+}
+
+/// The body setting the status message, which is cleared when empty.
+#[derive(serde::Serialize)]
+struct SetPresence<'a> {
+    presence: &'a str,
+    status_msg: &'a str,
+}
+
+impl MatrixSink {
+    /// Connects to the account, and fetches its current presence. In dry
+    /// run, nothing is fetched, and the account is assumed to have no
+    /// status message.
+    pub async fn connect(
+        options: Options,
+        http_client: &reqwest::Client,
+        dry_run: bool,
+    ) -> Result<Self, SinkError> {
+        if dry_run {
+            return Ok(Self {
+                options,
+                client: None,
+                first_status: None,
+                applied_status: None,
+            });
+        }
+
+        let homeserver = options.homeserver.trim_end_matches('/');
+        let access_token = options
+            .access_token
+            .as_ref()
+            .map(|access_token| access_token.expose_secret())
+            .unwrap_or_default();
+
+        let who_am_i: WhoAmI = http_client
+            .get(format!("{homeserver}/_matrix/client/v3/account/whoami"))
+            .bearer_auth(access_token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let url = format!(
+            "{homeserver}/_matrix/client/v3/presence/{}/status",
+            encode_path_segment(&who_am_i.user_id)
+        );
+        let presence: Presence = http_client
+            .get(&url)
+            .bearer_auth(access_token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(Self {
+            client: Some(Client {
+                http_client: http_client.clone(),
+                url,
+                user_id: who_am_i.user_id,
+            }),
+            applied_status: presence.status_msg.clone(),
+            first_status: presence.status_msg,
+            options,
+        })
+    }
+
+    fn account_name(&self) -> &str {
+        self.client
+            .as_ref()
+            .map_or(&self.options.homeserver, |client| &client.user_id)
+    }
+
+    /// Renders the status message for `event`, the same way as the Stoat
+    /// statuses.
+    fn status_text(&self, event: &Event<'_>, stats: &Stats) -> Option<String> {
+        match *event {
+            Event::Playing { track, replacement } => Some(template::render(
+                replacement.unwrap_or(&self.options.status.template),
+                Some(track),
+                stats,
+            )),
+            Event::Idle => self
+                .options
+                .status
+                .idle
+                .as_deref()
+                .map(|idle| template::render(idle, None, stats))
+                .or_else(|| self.first_status.clone()),
+        }
+    }
+
+    /// Sets the status message if it changed.
+    async fn set_status(&mut self, status_text: Option<String>) -> Result<(), SinkError> {
+        if status_text == self.applied_status {
+            return Ok(());
+        }
+
+        match &self.client {
+            Some(client) => {
+                let access_token = self
+                    .options
+                    .access_token
+                    .as_ref()
+                    .map(|access_token| access_token.expose_secret())
+                    .unwrap_or_default();
+
+                // The presence may have changed since the last time, from
+                // another client or from being idle.
+                let presence: Presence = client
+                    .http_client
+                    .get(&client.url)
+                    .bearer_auth(access_token)
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+                client
+                    .http_client
+                    .put(&client.url)
+                    .bearer_auth(access_token)
+                    .json(&SetPresence {
+                        presence: &presence.presence,
+                        status_msg: status_text.as_deref().unwrap_or_default(),
+                    })
+                    .send()
+                    .await?
+                    .error_for_status()?;
+            }
+            None => match &status_text {
+                Some(status_text) => println!(
+                    "Matrix ({}) dry run, status message set to: {status_text}",
+                    self.account_name()
+                ),
+                None => println!(
+                    "Matrix ({}) dry run, status message cleared.",
+                    self.account_name()
+                ),
+            },
+        }
+        self.applied_status = status_text;
+
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Sink for MatrixSink {
+    fn name(&self) -> String {
+        format!("Matrix account {}", self.account_name())
+    }
+
+    /// Accepts the options of the same account, in the same dry run mode.
+    fn accepts(&self, options: &SinkOptions) -> bool {
+        let SinkOptions::Matrix { options, dry_run } = options else {
+            return false;
+        };
+
+        *dry_run == self.client.is_none()
+            && self.options.homeserver == options.homeserver
+            && self
+                .options
+                .access_token
+                .as_ref()
+                .map(|access_token| access_token.expose_secret())
+                == options
+                    .access_token
+                    .as_ref()
+                    .map(|access_token| access_token.expose_secret())
+    }
+
+    fn reconfigure(&mut self, options: SinkOptions) {
+        debug_assert!(self.accepts(&options));
+
+        if let SinkOptions::Matrix { options, .. } = options {
+            self.options = options;
+        }
+    }
+
+    async fn update(
+        &mut self,
+        event: &Event<'_>,
+        stats: &Stats,
+        _metrics: &ProfileMetrics,
+    ) -> Result<(), SinkError> {
+        let status_text = self.status_text(event, stats);
+
+        self.set_status(status_text).await
+    }
+
+    /// Sets the status message back to the one the account had before lure
+    /// started.
+    async fn reset(&mut self) -> Result<(), SinkError> {
+        self.set_status(self.first_status.clone()).await
+    }
+}
+
+/// Percent-encodes `value` for use as a single path segment, since user IDs
+/// contain `@` and `:`, and may contain `/`.
+fn encode_path_segment(value: &str) -> String {
+    value.bytes().fold(String::new(), |mut encoded, byte| {
+        if byte.is_ascii_alphanumeric() || matches!(byte, b'-' | b'.' | b'_' | b'~') {
+            encoded.push(char::from(byte));
+        } else {
+            let _ = write!(encoded, "%{byte:02X}");
+        }
+
+        encoded
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use lure_types::TrackInfo;
+
+    use super::*;
+
+    async fn sink(yaml: &str) -> MatrixSink {
+        MatrixSink::connect(
+            serde_yaml::from_str(yaml).unwrap(),
+            &reqwest::Client::new(),
+            true,
+        )
+        .await
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_status_text() {
+        let sink = sink("{homeserver: https://matrix.example.com, status: {idle: Sleeping}}").await;
+        let track = TrackInfo {
+            artist: String::from("Cat"),
+            title: String::from("Meow"),
+            album: None,
+            cover_url: None,
+            url: None,
+        };
+        let stats = Stats::default();
+
+        let status_text = |replacement| {
+            sink.status_text(
+                &Event::Playing {
+                    track: &track,
+                    replacement,
+                },
+                &stats,
+            )
+        };
+        assert_eq!(
+            status_text(None).as_deref(),
+            Some("🎵 Listening to Meow by Cat")
+        );
+        assert_eq!(status_text(Some("Something")).as_deref(), Some("Something"));
+        assert_eq!(
+            sink.status_text(&Event::Idle, &stats).as_deref(),
+            Some("Sleeping")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_idle_restores_the_first_status() {
+        let mut sink = sink("homeserver: https://matrix.example.com").await;
+        sink.first_status = Some(String::from("Purring"));
+
+        assert_eq!(
+            sink.status_text(&Event::Idle, &Stats::default()).as_deref(),
+            Some("Purring")
+        );
+
+        sink.set_status(Some(String::from("Meowing")))
+            .await
+            .unwrap();
+        sink.reset().await.unwrap();
+        assert_eq!(sink.applied_status.as_deref(), Some("Purring"));
+    }
+}
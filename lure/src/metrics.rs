@@ -0,0 +1,300 @@
+use std::sync::atomic::AtomicU64;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use prometheus_client::{
+    encoding::EncodeLabelSet,
+    metrics::{
+        counter::Counter,
+        family::Family,
+        histogram::{Histogram, exponential_buckets},
+    },
+    registry::{Registry, Unit},
+};
+
+/// Prometheus metrics of lure, shared with the HTTP server.
+pub struct Metrics {
+    registry: Registry,
+    polls: Family<PollLabels, Counter>,
+    poll_duration: Family<ServiceLabels, Histogram>,
+    errors: Family<ErrorLabels, Counter>,
+    stoat_status_updates: Family<ResultLabels, Counter>,
+    rate_limit_sleeps: Family<ProfileLabels, Counter>,
+    rate_limit_sleep_duration: Family<ProfileLabels, Counter<f64, AtomicU64>>,
+    playback_status_duration: Family<PlaybackLabels, Counter<f64, AtomicU64>>,
+    track_changes: Family<ProfileLabels, Counter>,
+}
+
+/// The metrics of a single profile, labelled with its name.
+pub struct ProfileMetrics {
+    metrics: Arc<Metrics>,
+    profile: String,
+    playback_status: Mutex<Option<(&'static str, Instant)>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+struct ProfileLabels {
+    profile: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+struct ServiceLabels {
+    profile: String,
+    service: &'static str,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+struct PollLabels {
+    profile: String,
+    service: &'static str,
+    result: &'static str,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+struct ErrorLabels {
+    profile: String,
+    source: &'static str,
+    error: &'static str,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+struct ResultLabels {
+    profile: String,
+    result: &'static str,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, EncodeLabelSet)]
+struct PlaybackLabels {
+    profile: String,
+    status: &'static str,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let mut registry = Registry::with_prefix("lure");
+
+        let polls = Family::<PollLabels, Counter>::default();
+        registry.register("polls", "Number of listening service polls", polls.clone());
+
+        let poll_duration = Family::<ServiceLabels, Histogram>::new_with_constructor(|| {
+            Histogram::new(exponential_buckets(0.05, 2.0, 10))
+        });
+        registry.register_with_unit(
+            "poll_duration",
+            "Latency of listening service polls",
+            Unit::Seconds,
+            poll_duration.clone(),
+        );
+
+        let errors = Family::<ErrorLabels, Counter>::default();
+        registry.register(
+            "errors",
+            "Number of errors by source and API error variant",
+            errors.clone(),
+        );
+
+        let stoat_status_updates = Family::<ResultLabels, Counter>::default();
+        registry.register(
+            "stoat_status_updates",
+            "Number of Stoat status updates",
+            stoat_status_updates.clone(),
+        );
+
+        let rate_limit_sleeps = Family::<ProfileLabels, Counter>::default();
+        registry.register(
+            "rate_limit_sleeps",
+            "Number of times lure slept because of a rate limit",
+            rate_limit_sleeps.clone(),
+        );
+
+        let rate_limit_sleep_duration = Family::<ProfileLabels, Counter<f64, AtomicU64>>::default();
+        registry.register_with_unit(
+            "rate_limit_sleep_duration",
+            "Time spent sleeping because of rate limits",
+            Unit::Seconds,
+            rate_limit_sleep_duration.clone(),
+        );
+
+        let playback_status_duration = Family::<PlaybackLabels, Counter<f64, AtomicU64>>::default();
+        registry.register_with_unit(
+            "playback_status_duration",
+            "Time spent in each playback status",
+            Unit::Seconds,
+            playback_status_duration.clone(),
+        );
+
+        let track_changes = Family::<ProfileLabels, Counter>::default();
+        registry.register(
+            "track_changes",
+            "Number of track changes, use `rate()` for the track-change rate",
+            track_changes.clone(),
+        );
+
+        Self {
+            registry,
+            polls,
+            poll_duration,
+            errors,
+            stoat_status_updates,
+            rate_limit_sleeps,
+            rate_limit_sleep_duration,
+            playback_status_duration,
+            track_changes,
+        }
+    }
+
+    /// Encodes all metrics in the Prometheus text format.
+    pub fn encode(&self) -> Result<String, std::fmt::Error> {
+        let mut buffer = String::new();
+        prometheus_client::encoding::text::encode(&mut buffer, &self.registry)?;
+
+        Ok(buffer)
+    }
+
+    /// Returns the metrics of the profile `name`.
+    pub fn profile(self: &Arc<Self>, name: &str) -> ProfileMetrics {
+        ProfileMetrics {
+            metrics: Arc::clone(self),
+            profile: name.to_string(),
+            playback_status: Mutex::new(None),
+        }
+    }
+}
+
+impl ProfileMetrics {
+    pub fn observe_poll(
+        &self,
+        service: &'static str,
+        duration: Duration,
+        error: Option<&'static str>,
+    ) {
+        self.metrics
+            .polls
+            .get_or_create(&PollLabels {
+                profile: self.profile.clone(),
+                service,
+                result: if error.is_some() { "error" } else { "ok" },
+            })
+            .inc();
+        self.metrics
+            .poll_duration
+            .get_or_create(&ServiceLabels {
+                profile: self.profile.clone(),
+                service,
+            })
+            .observe(duration.as_secs_f64());
+
+        if let Some(error) = error {
+            self.observe_error(service, error);
+        }
+    }
+
+    pub fn observe_error(&self, source: &'static str, error: &'static str) {
+        self.metrics
+            .errors
+            .get_or_create(&ErrorLabels {
+                profile: self.profile.clone(),
+                source,
+                error,
+            })
+            .inc();
+    }
+
+    pub fn observe_stoat_status_update(&self, error: Option<&'static str>) {
+        self.metrics
+            .stoat_status_updates
+            .get_or_create(&ResultLabels {
+                profile: self.profile.clone(),
+                result: if error.is_some() { "error" } else { "ok" },
+            })
+            .inc();
+
+        if let Some(error) = error {
+            self.observe_error("stoat", error);
+        }
+    }
+
+    pub fn observe_rate_limit_sleep(&self, duration: Duration) {
+        self.metrics
+            .rate_limit_sleeps
+            .get_or_create(&self.labels())
+            .inc();
+        self.metrics
+            .rate_limit_sleep_duration
+            .get_or_create(&self.labels())
+            .inc_by(duration.as_secs_f64());
+    }
+
+    pub fn observe_track_change(&self) {
+        self.metrics
+            .track_changes
+            .get_or_create(&self.labels())
+            .inc();
+    }
+
+    /// Adds the time since the previous observation to the previous
+    /// playback status, and starts counting for `status`.
+    pub fn observe_playback_status(&self, status: &'static str) {
+        let Ok(mut current) = self.playback_status.lock() else {
+            return;
+        };
+        let now = Instant::now();
+
+        if let Some((previous, since)) = current.replace((status, now)) {
+            self.metrics
+                .playback_status_duration
+                .get_or_create(&PlaybackLabels {
+                    profile: self.profile.clone(),
+                    status: previous,
+                })
+                .inc_by(now.duration_since(since).as_secs_f64());
+        }
+    }
+
+    fn labels(&self) -> ProfileLabels {
+        ProfileLabels {
+            profile: self.profile.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode() {
+        let metrics = Arc::new(Metrics::new());
+        let kitty = metrics.profile("kitty");
+        let puppy = metrics.profile("puppy");
+
+        kitty.observe_poll("lastfm", Duration::from_millis(100), None);
+        kitty.observe_poll("lastfm", Duration::from_millis(100), Some("rate_limit"));
+        kitty.observe_error("stoat", "authentication_failed");
+        kitty.observe_track_change();
+        kitty.observe_playback_status("playing");
+        kitty.observe_playback_status("idle");
+        puppy.observe_track_change();
+
+        let encoded = metrics.encode().unwrap();
+        let lines: Vec<_> = encoded.lines().collect();
+
+        for line in [
+            r#"lure_polls_total{profile="kitty",service="lastfm",result="ok"} 1"#,
+            r#"lure_polls_total{profile="kitty",service="lastfm",result="error"} 1"#,
+            r#"lure_poll_duration_seconds_count{profile="kitty",service="lastfm"} 2"#,
+            r#"lure_errors_total{profile="kitty",source="lastfm",error="rate_limit"} 1"#,
+            r#"lure_errors_total{profile="kitty",source="stoat",error="authentication_failed"} 1"#,
+            r#"lure_track_changes_total{profile="kitty"} 1"#,
+            r#"lure_track_changes_total{profile="puppy"} 1"#,
+        ] {
+            assert!(lines.contains(&line), "{line} missing from:\n{encoded}");
+        }
+        // The time spent playing is only counted once the status changes.
+        assert!(encoded.contains(
+            r#"lure_playback_status_duration_seconds_total{profile="kitty",status="playing"} "#
+        ));
+        assert!(!encoded.contains(r#"status="idle""#));
+        assert_eq!(lines.last(), Some(&"# EOF"));
+    }
+}
@@ -0,0 +1,564 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use lure_config::mqtt::Options;
+use lure_types::stats::Stats;
+use rumqttc::{AsyncClient, EventLoop, LastWill, MqttOptions, Outgoing, Packet, QoS};
+use secrecy::ExposeSecret as _;
+use tokio::task::JoinHandle;
+
+use crate::metrics::ProfileMetrics;
+use crate::sink::{Event, Sink, SinkError, SinkOptions};
+
+/// How many messages can wait for the connection.
+const REQUEST_CAPACITY: usize = 16;
+/// Delay before reconnecting to the broker after the connection failed.
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+/// How long the last messages are given to be sent when the broker is
+/// removed or lure stops.
+const DISCONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+
+const ONLINE: &str = "online";
+const OFFLINE: &str = "offline";
+
+/// An MQTT broker the current track and the playback state are published
+/// to, as retained messages.
+///
+/// The availability topic says `online` while lure is connected, and
+/// `offline` once it stops, or through the last will if the connection is
+/// lost.
+pub struct MqttSink {
+    options: Options,
+    profile: String,
+    topics: Arc<Topics>,
+    /// The connection, or `None` in dry run, where the messages are only
+    /// logged.
+    connection: Option<Connection>,
+    /// The state and the track last published.
+    published: Option<(&'static str, String)>,
+}
+
+struct Connection {
+    client: AsyncClient,
+    event_loop: JoinHandle<()>,
+    /// Whether the broker is reachable, so that stopping doesn't wait for
+    /// it when it isn't.
+    connected: Arc<AtomicBool>,
+}
+
+struct Topics {
+    /// The current track as JSON, or `{}` if nothing is playing or the
+    /// track is hidden by a privacy filter.
+    track: String,
+    /// `playing` or `idle`.
+    state: String,
+    availability: String,
+    /// Topic and payload of the Home Assistant discovery message.
+    discovery: Option<(String, String)>,
+}
+
+impl MqttSink {
+    pub fn connect(options: Options, profile: String, dry_run: bool) -> Self {
+        let topics = Arc::new(Topics::new(&options, &profile));
+
+        let connection = (!dry_run).then(|| {
+            let mut mqtt_options = MqttOptions::new(
+                options.client_id(&profile),
+                options.host.clone(),
+                options.port,
+            );
+            mqtt_options.set_last_will(LastWill::new(
+                &topics.availability,
+                OFFLINE,
+                QoS::AtLeastOnce,
+                true,
+            ));
+            if let Some(username) = &options.username {
+                mqtt_options.set_credentials(
+                    username,
+                    options
+                        .password
+                        .as_ref()
+                        .map(|password| password.expose_secret())
+                        .unwrap_or_default(),
+                );
+            }
+
+            let (client, event_loop) = AsyncClient::new(mqtt_options, REQUEST_CAPACITY);
+            let connected = Arc::new(AtomicBool::new(false));
+            let event_loop = tokio::spawn(run(
+                event_loop,
+                client.clone(),
+                Arc::clone(&topics),
+                Arc::clone(&connected),
+                broker_name(&options),
+            ));
+
+            Connection {
+                client,
+                event_loop,
+                connected,
+            }
+        });
+
+        Self {
+            options,
+            profile,
+            topics,
+            connection,
+            published: None,
+        }
+    }
+
+    /// Publishes the state and the track if they changed.
+    fn publish(&mut self, state: &'static str, track: String) -> Result<(), SinkError> {
+        if matches!(&self.published, Some((published_state, published_track))
+            if *published_state == state && *published_track == track)
+        {
+            return Ok(());
+        }
+
+        match &self.connection {
+            Some(connection) => {
+                connection
+                    .client
+                    .try_publish(&self.topics.state, QoS::AtLeastOnce, true, state)?;
+                connection.client.try_publish(
+                    &self.topics.track,
+                    QoS::AtLeastOnce,
+                    true,
+                    track.clone(),
+                )?;
+            }
+            None => println!(
+                "MQTT ({}) dry run, state set to {state}, track set to: {track}",
+                broker_name(&self.options)
+            ),
+        }
+        self.published = Some((state, track));
+
+        Ok(())
+    }
+}
+
+impl Drop for MqttSink {
+    fn drop(&mut self) {
+        if let Some(connection) = &self.connection {
+            connection.event_loop.abort();
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Sink for MqttSink {
+    fn name(&self) -> String {
+        format!("MQTT broker {}", broker_name(&self.options))
+    }
+
+    /// Accepts the same options for the same profile, in the same dry run
+    /// mode, since the connection and the topics are bound to them.
+    fn accepts(&self, options: &SinkOptions) -> bool {
+        let SinkOptions::Mqtt {
+            options,
+            profile,
+            dry_run,
+        } = options
+        else {
+            return false;
+        };
+
+        *dry_run == self.connection.is_none()
+            && self.options == *options
+            && self.profile == *profile
+    }
+
+    fn reconfigure(&mut self, options: SinkOptions) {
+        debug_assert!(self.accepts(&options));
+    }
+
+    async fn update(
+        &mut self,
+        event: &Event<'_>,
+        _stats: &Stats,
+        _metrics: &ProfileMetrics,
+    ) -> Result<(), SinkError> {
+        match *event {
+            Event::Playing {
+                track,
+                replacement: None,
+            } => self.publish("playing", serde_json::to_string(track)?),
+            Event::Playing {
+                replacement: Some(_),
+                ..
+            } => self.publish("playing", "{}".to_string()),
+            Event::Idle => self.publish("idle", "{}".to_string()),
+        }
+    }
+
+    async fn reset(&mut self) -> Result<(), SinkError> {
+        self.publish("idle", "{}".to_string())
+    }
+
+    /// Publishes the idle state and the `offline` availability, then
+    /// disconnects cleanly.
+    async fn restore(&mut self) -> Result<(), SinkError> {
+        self.reset().await?;
+
+        let Some(connection) = &mut self.connection else {
+            return Ok(());
+        };
+        if !connection.connected.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        connection.client.try_publish(
+            &self.topics.availability,
+            QoS::AtLeastOnce,
+            true,
+            OFFLINE,
+        )?;
+        connection.client.disconnect().await?;
+
+        if tokio::time::timeout(DISCONNECT_TIMEOUT, &mut connection.event_loop)
+            .await
+            .is_err()
+        {
+            eprintln!(
+                "MQTT ({}) couldn't disconnect in time.",
+                broker_name(&self.options)
+            );
+        }
+
+        Ok(())
+    }
+}
+
+impl Topics {
+    fn new(options: &Options, profile: &str) -> Self {
+        let prefix = options.topic(profile);
+        let track = format!("{prefix}/track");
+        let availability = format!("{prefix}/availability");
+
+        let discovery = options.discovery.enable.then(|| {
+            // Object IDs can only contain letters, digits, `_` and `-`.
+            let object_id: String = options
+                .client_id(profile)
+                .chars()
+                .map(|char| {
+                    if char.is_ascii_alphanumeric() || char == '-' {
+                        char
+                    } else {
+                        '_'
+                    }
+                })
+                .collect();
+            let payload = serde_json::json!({
+                "name": "Now playing",
+                "unique_id": format!("{object_id}_now_playing"),
+                "icon": "mdi:music",
+                "state_topic": track,
+                "value_template": "{{ value_json.title ~ ' by ' ~ value_json.artist if value_json.title is defined else 'idle' }}",
+                "json_attributes_topic": track,
+                "availability_topic": availability,
+                "device": {
+                    "identifiers": [object_id],
+                    "name": format!("lure {profile}"),
+                    "manufacturer": "lure",
+                },
+            });
+
+            (
+                format!(
+                    "{}/sensor/{object_id}/now_playing/config",
+                    options.discovery.prefix
+                ),
+                payload.to_string(),
+            )
+        });
+
+        Self {
+            track,
+            state: format!("{prefix}/state"),
+            availability,
+            discovery,
+        }
+    }
+}
+
+/// Drives the connection, reconnecting when it fails. Every time lure
+/// (re)connects, the availability is set back to `online`, since the last
+/// will may have set it to `offline`, and the discovery message is sent.
+async fn run(
+    mut event_loop: EventLoop,
+    client: AsyncClient,
+    topics: Arc<Topics>,
+    connected: Arc<AtomicBool>,
+    name: String,
+) {
+    let mut failing = false;
+    let mut disconnecting = false;
+
+    loop {
+        match event_loop.poll().await {
+            Ok(rumqttc::Event::Incoming(Packet::ConnAck(_))) => {
+                connected.store(true, Ordering::Relaxed);
+                if failing {
+                    println!("MQTT ({name}) reconnected.");
+                    failing = false;
+                }
+
+                let result = client
+                    .try_publish(&topics.availability, QoS::AtLeastOnce, true, ONLINE)
+                    .and_then(|()| {
+                        topics
+                            .discovery
+                            .as_ref()
+                            .map_or(Ok(()), |(topic, payload)| {
+                                client.try_publish(topic, QoS::AtLeastOnce, true, payload.clone())
+                            })
+                    });
+                if let Err(error) = result {
+                    eprintln!("MQTT ({name}) failed to announce lure: {error}");
+                }
+            }
+            // The disconnect is only flushed on the next poll, along with
+            // the messages published right before it, and the broker closes
+            // the connection once it gets it.
+            Ok(rumqttc::Event::Outgoing(Outgoing::Disconnect)) => disconnecting = true,
+            Ok(_) => {}
+            Err(_) if disconnecting => break,
+            Err(error) => {
+                connected.store(false, Ordering::Relaxed);
+                if !failing {
+                    eprintln!(
+                        "MQTT ({name}) connection failed, reconnecting every {}s: {error}",
+                        RECONNECT_DELAY.as_secs()
+                    );
+                    failing = true;
+                }
+                tokio::time::sleep(RECONNECT_DELAY).await;
+            }
+        }
+    }
+}
+
+fn broker_name(options: &Options) -> String {
+    format!("{}:{}", options.host, options.port)
+}
+
+#[cfg(test)]
+mod tests {
+    use bytes::BytesMut;
+    use lure_types::TrackInfo;
+    use rumqttc::{ConnAck, ConnectReturnCode, PubAck};
+    use tokio::io::{AsyncReadExt as _, AsyncWriteExt as _};
+    use tokio::net::TcpListener;
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::metrics::Metrics;
+
+    fn track() -> TrackInfo {
+        TrackInfo {
+            artist: String::from("Cat"),
+            title: String::from("Meow"),
+            album: None,
+            cover_url: None,
+            url: None,
+        }
+    }
+
+    /// A broker accepting a single connection, acknowledging what it
+    /// receives and passing it on.
+    async fn broker(listener: TcpListener, packets: mpsc::UnboundedSender<Packet>) {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        let mut buffer = BytesMut::new();
+
+        loop {
+            let packet = match Packet::read(&mut buffer, usize::MAX) {
+                Ok(packet) => packet,
+                Err(rumqttc::mqttbytes::Error::InsufficientBytes(_)) => {
+                    if stream.read_buf(&mut buffer).await.unwrap() == 0 {
+                        return;
+                    }
+                    continue;
+                }
+                Err(error) => panic!("{error}"),
+            };
+
+            let reply = match &packet {
+                Packet::Connect(_) => Some(Packet::ConnAck(ConnAck::new(
+                    ConnectReturnCode::Success,
+                    false,
+                ))),
+                Packet::Publish(publish) if publish.qos == QoS::AtLeastOnce => {
+                    Some(Packet::PubAck(PubAck::new(publish.pkid)))
+                }
+                Packet::PingReq => Some(Packet::PingResp),
+                _ => None,
+            };
+            if let Some(reply) = reply {
+                let mut bytes = BytesMut::new();
+                reply.write(&mut bytes, usize::MAX).unwrap();
+                stream.write_all(&bytes).await.unwrap();
+            }
+
+            // Like a real broker, close the connection once lure
+            // disconnects.
+            let disconnect = matches!(packet, Packet::Disconnect);
+            let _ = packets.send(packet);
+            if disconnect {
+                return;
+            }
+        }
+    }
+
+    /// Returns the topic, the payload and the retain flag of the next
+    /// message published.
+    async fn next_publish(packets: &mut mpsc::UnboundedReceiver<Packet>) -> (String, String, bool) {
+        loop {
+            let packet = tokio::time::timeout(Duration::from_secs(5), packets.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            if let Packet::Publish(publish) = packet {
+                return (
+                    publish.topic,
+                    String::from_utf8(publish.payload.to_vec()).unwrap(),
+                    publish.retain,
+                );
+            }
+        }
+    }
+
+    fn published(topic: &str, payload: &str) -> (String, String, bool) {
+        (topic.to_string(), payload.to_string(), true)
+    }
+
+    #[test]
+    fn test_discovery() {
+        let options: Options = serde_yaml::from_str(
+            "{host: localhost, client_id: lure.kitty, discovery: {enable: true}}",
+        )
+        .unwrap();
+
+        let (topic, payload) = Topics::new(&options, "kitty").discovery.unwrap();
+        let payload: serde_json::Value = serde_json::from_str(&payload).unwrap();
+
+        assert_eq!(topic, "homeassistant/sensor/lure_kitty/now_playing/config");
+        assert_eq!(payload["unique_id"], "lure_kitty_now_playing");
+        assert_eq!(payload["state_topic"], "lure/kitty/track");
+        assert_eq!(payload["json_attributes_topic"], "lure/kitty/track");
+        assert_eq!(payload["availability_topic"], "lure/kitty/availability");
+        assert_eq!(payload["device"]["identifiers"][0], "lure_kitty");
+        assert_eq!(payload["device"]["name"], "lure kitty");
+    }
+
+    #[test]
+    fn test_no_discovery() {
+        let options: Options = serde_yaml::from_str("host: localhost").unwrap();
+
+        assert!(Topics::new(&options, "kitty").discovery.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_dry_run() {
+        let options: Options = serde_yaml::from_str("host: localhost").unwrap();
+        let mut sink = MqttSink::connect(options, String::from("kitty"), true);
+        let metrics = Arc::new(Metrics::new()).profile("kitty");
+        let track = track();
+
+        sink.update(
+            &Event::Playing {
+                track: &track,
+                replacement: None,
+            },
+            &Stats::default(),
+            &metrics,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            sink.published,
+            Some(("playing", serde_json::to_string(&track).unwrap()))
+        );
+
+        sink.update(
+            &Event::Playing {
+                track: &track,
+                replacement: Some("Hidden"),
+            },
+            &Stats::default(),
+            &metrics,
+        )
+        .await
+        .unwrap();
+        assert_eq!(sink.published, Some(("playing", String::from("{}"))));
+
+        sink.restore().await.unwrap();
+        assert_eq!(sink.published, Some(("idle", String::from("{}"))));
+    }
+
+    #[tokio::test]
+    async fn test_publishes_to_the_broker() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (sender, mut packets) = mpsc::unbounded_channel();
+        tokio::spawn(broker(listener, sender));
+
+        let mut options: Options = serde_yaml::from_str(
+            "{host: 127.0.0.1, client_id: lure-kitty, discovery: {enable: true}}",
+        )
+        .unwrap();
+        options.port = port;
+        let mut sink = MqttSink::connect(options, String::from("kitty"), false);
+        let metrics = Arc::new(Metrics::new()).profile("kitty");
+        let track = track();
+
+        let Some(Packet::Connect(connect)) = packets.recv().await else {
+            panic!("lure didn't connect first");
+        };
+        let last_will = connect.last_will.unwrap();
+        assert_eq!(connect.client_id, "lure-kitty");
+        assert_eq!(last_will.topic, "lure/kitty/availability");
+        assert_eq!(last_will.message, OFFLINE.as_bytes());
+        assert!(last_will.retain);
+
+        assert_eq!(
+            next_publish(&mut packets).await,
+            published("lure/kitty/availability", "online")
+        );
+        let (topic, _, retain) = next_publish(&mut packets).await;
+        assert_eq!(topic, "homeassistant/sensor/lure-kitty/now_playing/config");
+        assert!(retain);
+
+        let playing = Event::Playing {
+            track: &track,
+            replacement: None,
+        };
+        sink.update(&playing, &Stats::default(), &metrics)
+            .await
+            .unwrap();
+        // Unchanged, so nothing is published.
+        sink.update(&playing, &Stats::default(), &metrics)
+            .await
+            .unwrap();
+        sink.update(&Event::Idle, &Stats::default(), &metrics)
+            .await
+            .unwrap();
+        sink.restore().await.unwrap();
+
+        let json = serde_json::to_string(&track).unwrap();
+        for expected in [
+            published("lure/kitty/state", "playing"),
+            published("lure/kitty/track", &json),
+            published("lure/kitty/state", "idle"),
+            published("lure/kitty/track", "{}"),
+            published("lure/kitty/availability", "offline"),
+        ] {
+            assert_eq!(next_publish(&mut packets).await, expected);
+        }
+        assert!(matches!(packets.recv().await, Some(Packet::Disconnect)));
+    }
+}
@@ -0,0 +1,153 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use notify::{RecursiveMode, Watcher as _};
+use tokio::sync::mpsc;
+
+/// Events are debounced for this long, since editors usually write a file
+/// in more than one step.
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Notifies when the configuration should be reloaded: when the
+/// configuration file changes, or when `SIGHUP` is received.
+pub struct Reloader {
+    receiver: mpsc::UnboundedReceiver<()>,
+    _watcher: Option<notify::RecommendedWatcher>,
+    #[cfg(unix)]
+    sighup: Option<tokio::signal::unix::Signal>,
+}
+
+impl Reloader {
+    pub fn new(config_path: &Path) -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        let watcher = match watch(config_path, sender) {
+            Ok(watcher) => Some(watcher),
+            Err(error) => {
+                eprintln!(
+                    "Failed to watch the configuration file, hot reload is disabled: {error}"
+                );
+                None
+            }
+        };
+
+        Self {
+            receiver,
+            _watcher: watcher,
+            #[cfg(unix)]
+            sighup: tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+                .inspect_err(|error| eprintln!("Failed to listen for SIGHUP: {error}"))
+                .ok(),
+        }
+    }
+
+    /// Waits until the configuration should be reloaded.
+    pub async fn changed(&mut self) {
+        #[cfg(unix)]
+        let sighup = async {
+            match &mut self.sighup {
+                Some(sighup) => sighup.recv().await,
+                None => std::future::pending().await,
+            }
+        };
+        #[cfg(not(unix))]
+        let sighup = std::future::pending::<Option<()>>();
+
+        tokio::select! {
+            Some(()) = self.receiver.recv() => {
+                tokio::time::sleep(DEBOUNCE).await;
+                while self.receiver.try_recv().is_ok() {}
+            }
+            Some(()) = sighup => {}
+        }
+    }
+}
+
+/// Watches the directory of the configuration file rather than the file
+/// itself, so that the watch survives editors replacing the file.
+fn watch(
+    config_path: &Path,
+    sender: mpsc::UnboundedSender<()>,
+) -> Result<notify::RecommendedWatcher, notify::Error> {
+    let config_path = std::path::absolute(config_path)?;
+    let directory = config_path
+        .parent()
+        .map_or_else(|| PathBuf::from("."), Path::to_path_buf);
+
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        if let Ok(event) = event
+            && (event.kind.is_create() || event.kind.is_modify() || event.kind.is_remove())
+            && event.paths.iter().any(|path| path == &config_path)
+        {
+            let _ = sender.send(());
+        }
+    })?;
+    watcher.watch(&directory, RecursiveMode::NonRecursive)?;
+
+    Ok(watcher)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// How long the watcher is given to notice a change.
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    fn config() -> (tempfile::TempDir, PathBuf) {
+        let directory = tempfile::tempdir().unwrap();
+        let path = directory.path().join("config.yaml");
+        std::fs::write(&path, "profiles: []").unwrap();
+
+        (directory, path)
+    }
+
+    #[tokio::test]
+    async fn test_write_reloads() {
+        let (_directory, path) = config();
+        let mut reloader = Reloader::new(&path);
+
+        std::fs::write(&path, "profiles: [{}]").unwrap();
+
+        assert!(
+            tokio::time::timeout(TIMEOUT, reloader.changed())
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_burst_of_writes_reloads_once() {
+        let (_directory, path) = config();
+        let mut reloader = Reloader::new(&path);
+
+        for index in 0..5 {
+            std::fs::write(&path, format!("# {index}")).unwrap();
+        }
+
+        assert!(
+            tokio::time::timeout(TIMEOUT, reloader.changed())
+                .await
+                .is_ok()
+        );
+        assert!(
+            tokio::time::timeout(DEBOUNCE * 4, reloader.changed())
+                .await
+                .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_other_files_are_ignored() {
+        let (directory, path) = config();
+        let mut reloader = Reloader::new(&path);
+
+        std::fs::write(directory.path().join("other.yaml"), "meow").unwrap();
+
+        assert!(
+            tokio::time::timeout(DEBOUNCE * 4, reloader.changed())
+                .await
+                .is_err()
+        );
+    }
+}
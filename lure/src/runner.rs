@@ -0,0 +1,352 @@
+use std::ops::ControlFlow;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use jiff::Zoned;
+use jiff::tz::TimeZone;
+use lure_config::filter::Filters;
+use lure_config::profile::Profile;
+use lure_config::schedule::Outside;
+use lure_history::{History, PlayId};
+use lure_types::{PlaybackStatus, TrackInfo, template};
+use tokio::time::sleep;
+
+use crate::metrics::ProfileMetrics;
+use crate::service::Service;
+use crate::sink::{Event, SinkOptions, Sinks};
+use crate::start::{RunError, ServiceError};
+use crate::state::{NowPlaying, ProfileState, unix_timestamp};
+use crate::stats::StatsProvider;
+
+/// Longest wait before checking the schedule again, so that changes of the
+/// system clock are caught up with.
+const MAX_SCHEDULE_CHECK_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Polls the listening service of a profile and mirrors the playback
+/// status to every sink of it.
+pub struct Runner {
+    profile: Profile,
+    http_client: reqwest::Client,
+    service: Service,
+    sinks: Sinks,
+    current_track: Option<TrackInfo>,
+    state: ProfileState,
+    metrics: ProfileMetrics,
+    history: Option<Arc<History>>,
+    /// The play of the current track in the listening history.
+    current_play: Option<PlayId>,
+    stats: StatsProvider,
+    filters: Filters,
+    time_zone: TimeZone,
+    /// Whether the profile is inside its schedule.
+    active: bool,
+}
+
+impl Runner {
+    pub async fn try_new(
+        profile: Profile,
+        http_client: reqwest::Client,
+        state: ProfileState,
+        metrics: ProfileMetrics,
+        history: Option<Arc<History>>,
+    ) -> Result<Self, RunError> {
+        let service = Service::try_from_options(profile.service.clone(), &http_client)?;
+        let filters = Filters::new(&profile.filters)?;
+        let time_zone = profile.schedule.time_zone()?;
+        let sinks = Sinks::connect(&profile.name, SinkOptions::of(&profile), &http_client).await?;
+
+        state.update(|snapshot| {
+            snapshot.active_service = Some(service.name());
+            snapshot.check_interval = Some(service.check_interval());
+            snapshot.started_at = Some(unix_timestamp());
+            snapshot.stoat_authenticated = true;
+        });
+
+        let mut runner = Self {
+            stats: StatsProvider::new(&profile),
+            filters,
+            time_zone,
+            active: true,
+            profile,
+            http_client,
+            service,
+            sinks,
+            current_track: None,
+            state,
+            metrics,
+            history,
+            current_play: None,
+        };
+        runner.apply_schedule().await?;
+
+        Ok(runner)
+    }
+
+    /// Waits for the check interval, then polls the listening service.
+    /// Never completes while the profile is outside its schedule.
+    pub async fn poll(&self) -> Result<PlaybackStatus, ServiceError> {
+        if !self.active {
+            return std::future::pending().await;
+        }
+
+        sleep(Duration::from_secs(self.service.check_interval())).await;
+
+        let started_at = Instant::now();
+        let result = self.service.fetch().await;
+        self.metrics.observe_poll(
+            self.service.name(),
+            started_at.elapsed(),
+            result.as_ref().err().map(ServiceError::kind),
+        );
+
+        result
+    }
+
+    /// Applies a poll result to every Stoat target. Breaks if the service
+    /// returned a fatal error.
+    pub async fn handle(
+        &mut self,
+        result: Result<PlaybackStatus, ServiceError>,
+    ) -> Result<ControlFlow<()>, RunError> {
+        let track = match result {
+            Ok(PlaybackStatus::Playing(track)) => Some(track),
+            Ok(PlaybackStatus::NotPlaying) => None,
+            Err(error) => {
+                self.state.record_error(&error);
+                self.sinks.error(&error.to_string()).await;
+
+                if self.service.is_fatal_error(&error) {
+                    eprintln!("[{}] Fatal error: {error}", self.profile.name);
+                    return Ok(ControlFlow::Break(()));
+                }
+
+                eprintln!("[{}] Non-fatal error, retrying: {error}", self.profile.name);
+                return Ok(ControlFlow::Continue(()));
+            }
+        };
+
+        self.state.record_poll();
+        self.metrics.observe_playback_status(if track.is_some() {
+            "playing"
+        } else {
+            "not_playing"
+        });
+
+        if track != self.current_track {
+            if track.is_some() {
+                self.metrics.observe_track_change();
+            }
+
+            self.record_play(track.as_ref());
+            self.current_track = track;
+        }
+
+        self.update_sinks().await;
+
+        Ok(ControlFlow::Continue(()))
+    }
+
+    /// Applies new options of the profile without restarting.
+    ///
+    /// The service is rebuilt only if its options changed. Sinks are matched
+    /// by their output, like the Stoat account: new outputs are connected,
+    /// removed ones get restored, and the rest keep their state and get the
+    /// current track re-rendered with the new options, so the status doesn't
+    /// flicker. Turning the dry run on or off reconnects every account.
+    pub async fn reload(&mut self, profile: Profile) -> Result<(), RunError> {
+        let filters = Filters::new(&profile.filters)?;
+        let time_zone = profile.schedule.time_zone()?;
+        let service = if profile.service == self.profile.service {
+            None
+        } else {
+            Some(Service::try_from_options(
+                profile.service.clone(),
+                &self.http_client,
+            )?)
+        };
+
+        // Connect the new outputs first, so that nothing is changed if any
+        // of them fails.
+        self.sinks
+            .reload(SinkOptions::of(&profile), &self.http_client)
+            .await?;
+
+        if let Some(service) = service {
+            self.state.update(|snapshot| {
+                snapshot.active_service = Some(service.name());
+                snapshot.check_interval = Some(service.check_interval());
+            });
+            self.service = service;
+            println!(
+                "[{}] Service options changed, switched to {}.",
+                profile.name,
+                self.service.name()
+            );
+        }
+
+        self.stats.reload(&profile);
+        self.filters = filters;
+        self.time_zone = time_zone;
+        self.profile = profile;
+        self.apply_schedule().await?;
+
+        if self.active {
+            self.update_sinks().await;
+        }
+
+        Ok(())
+    }
+
+    /// Waits until the profile gets inside or outside its schedule.
+    pub async fn schedule_changed(&self) {
+        let now = Zoned::now().with_time_zone(self.time_zone.clone());
+        let Some(next_change) = self.profile.schedule.next_change(&now) else {
+            return std::future::pending().await;
+        };
+
+        let delay = Duration::try_from(now.duration_until(&next_change)).unwrap_or_default();
+        sleep(delay.min(MAX_SCHEDULE_CHECK_INTERVAL)).await;
+    }
+
+    /// Pauses or resumes the profile according to its schedule. Outside of
+    /// it, the current play ends and the statuses show what the schedule
+    /// says, until the profile gets inside of it again.
+    pub async fn apply_schedule(&mut self) -> Result<(), RunError> {
+        let now = Zoned::now().with_time_zone(self.time_zone.clone());
+        let active = self.profile.schedule.is_active(&now);
+
+        if active != self.active {
+            self.active = active;
+
+            if active {
+                println!("[{}] Inside the schedule, resuming.", self.profile.name);
+            } else {
+                println!("[{}] Outside the schedule, pausing.", self.profile.name);
+                self.record_play(None);
+                self.current_track = None;
+            }
+
+            self.state.update(|snapshot| {
+                snapshot.paused = !active;
+                if active {
+                    snapshot.resumed_at = Some(unix_timestamp());
+                } else {
+                    snapshot.now_playing = None;
+                }
+            });
+        }
+
+        if active {
+            return Ok(());
+        }
+
+        match self.profile.schedule.outside {
+            Outside::Idle => self.update_sinks().await,
+            Outside::Restore => self.sinks.reset().await,
+        }
+
+        Ok(())
+    }
+
+    /// Restores every output to the state it had before lure started.
+    pub async fn restore(mut self) -> Result<(), RunError> {
+        self.record_play(None);
+
+        self.sinks.restore().await?;
+
+        Ok(())
+    }
+
+    /// Ends the current play in the listening history and starts one for
+    /// `track`. Tracks hidden by a privacy filter are recorded too, since
+    /// the listening service has them anyway, but the statistics skip them.
+    /// Errors are only logged, so that the history can't stop the status
+    /// from being updated.
+    fn record_play(&mut self, track: Option<&TrackInfo>) {
+        let Some(history) = &self.history else {
+            return;
+        };
+        let now = i64::try_from(unix_timestamp()).unwrap_or(i64::MAX);
+
+        let result = self
+            .current_play
+            .take()
+            .map_or(Ok(()), |play| history.end_play(play, now))
+            .and_then(|()| {
+                track
+                    .map(|track| {
+                        history.start_play(&self.profile.name, self.service.name(), track, now)
+                    })
+                    .transpose()
+            });
+
+        match result {
+            Ok(play) => self.current_play = play,
+            Err(error) => eprintln!(
+                "[{}] Failed to record the listening history: {error}",
+                self.profile.name
+            ),
+        }
+    }
+
+    /// Sends the current track to every sink. Tracks matching a privacy
+    /// filter are shown with its replacement template, or as if nothing was
+    /// playing.
+    async fn update_sinks(&mut self) {
+        let event = match self.current_track.as_ref().map(|track| {
+            (
+                track,
+                self.filters
+                    .find(track)
+                    .map(|filter| filter.replacement.as_deref()),
+            )
+        }) {
+            Some((track, None)) => Event::Playing {
+                track,
+                replacement: None,
+            },
+            Some((track, Some(Some(replacement)))) => Event::Playing {
+                track,
+                replacement: Some(replacement),
+            },
+            Some((_, Some(None))) | None => Event::Idle,
+        };
+        let track = match event {
+            Event::Playing { track, .. } => Some(track),
+            Event::Idle => None,
+        };
+        let stats = self
+            .stats
+            .stats(
+                &self.profile.name,
+                &self.service,
+                self.history.as_deref(),
+                &self.filters,
+                track,
+            )
+            .await;
+
+        let now_playing = match event {
+            Event::Playing {
+                track,
+                replacement: None,
+            } => NowPlaying::Playing {
+                track: track.clone(),
+                stats: stats.clone(),
+            },
+            Event::Playing {
+                track,
+                replacement: Some(replacement),
+            } => NowPlaying::Hidden {
+                replacement: template::render(replacement, Some(track), &stats),
+            },
+            Event::Idle => NowPlaying::Idle {
+                stats: stats.clone(),
+            },
+        };
+        self.state
+            .update(|snapshot| snapshot.now_playing = Some(now_playing));
+
+        self.sinks.update(&event, &stats, &self.metrics).await;
+    }
+}
@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use axum::{
+    Json, Router,
+    extract::State as AxumState,
+    http::{StatusCode, header},
+    response::{IntoResponse as _, Response},
+    routing::get,
+};
+
+use tokio::{net::TcpListener, task::JoinHandle};
+
+use crate::metrics::Metrics;
+use crate::state::State;
+
+#[derive(Clone)]
+struct ServerState {
+    state: Arc<State>,
+    metrics: Arc<Metrics>,
+    max_missed_polls: u32,
+}
+
+/// Binds the HTTP server and spawns it in the background.
+pub async fn spawn(
+    options: &lure_config::server::Options,
+    state: Arc<State>,
+    metrics: Arc<Metrics>,
+) -> Result<JoinHandle<()>, std::io::Error> {
+    let mut router = Router::new()
+        .route("/livez", get(livez))
+        .route("/readyz", get(readyz))
+        .route("/state", get(current_state));
+
+    if options.metrics {
+        router = router.route("/metrics", get(prometheus_metrics));
+    }
+
+    let router = router.with_state(ServerState {
+        state,
+        metrics,
+        max_missed_polls: options.max_missed_polls,
+    });
+
+    let listener = TcpListener::bind(options.address).await?;
+    println!("HTTP server listening on {}", listener.local_addr()?);
+
+    Ok(tokio::spawn(async move {
+        if let Err(error) = axum::serve(listener, router).await {
+            eprintln!("HTTP server error: {error}");
+        }
+    }))
+}
+
+async fn livez(AxumState(server): AxumState<ServerState>) -> Response {
+    health_response(server.state.snapshot().is_live(server.max_missed_polls))
+}
+
+async fn readyz(AxumState(server): AxumState<ServerState>) -> Response {
+    health_response(server.state.snapshot().is_ready(server.max_missed_polls))
+}
+
+async fn current_state(AxumState(server): AxumState<ServerState>) -> Response {
+    Json(server.state.snapshot()).into_response()
+}
+
+async fn prometheus_metrics(AxumState(server): AxumState<ServerState>) -> Response {
+    server.metrics.encode().map_or_else(
+        |_| StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+        |body| {
+            (
+                [(
+                    header::CONTENT_TYPE,
+                    "application/openmetrics-text; version=1.0.0; charset=utf-8",
+                )],
+                body,
+            )
+                .into_response()
+        },
+    )
+}
+
+fn health_response(healthy: bool) -> Response {
+    if healthy {
+        (StatusCode::OK, "ok").into_response()
+    } else {
+        (StatusCode::SERVICE_UNAVAILABLE, "unavailable").into_response()
+    }
+}
@@ -1,17 +1,135 @@
-use lure_types::PlaybackStatus;
+use std::time::Duration;
 
-use crate::start::ServiceError;
+use lure_config::ServiceOptions;
+use lure_types::{HistoryPage, PlaybackStatus, TrackInfo};
+
+use crate::start::{RunError, ServiceError};
 
 pub enum Service {
     LastFm(lure_lastfm_service::Service),
     ListenBrainz(lure_listenbrainz_service::Service),
+    Exec(lure_exec_service::Service),
 }
 
 impl Service {
-    pub async fn poll(&self) -> Result<PlaybackStatus, ServiceError> {
+    pub fn try_from_options(
+        options: ServiceOptions,
+        http_client: &reqwest::Client,
+    ) -> Result<Self, RunError> {
+        let enabled = options.enabled();
+        if enabled.len() > 1 {
+            return Err(RunError::MoreThanOneServiceEnabled(enabled.join(" and ")));
+        }
+
+        match options {
+            ServiceOptions {
+                lastfm: Some(config),
+                ..
+            } => Ok(Self::LastFm(
+                lure_lastfm_service::Service::with_http_client(config, http_client.clone()),
+            )),
+            ServiceOptions {
+                listenbrainz: Some(config),
+                ..
+            } => Ok(Self::ListenBrainz(
+                lure_listenbrainz_service::Service::with_http_client(config, http_client.clone()),
+            )),
+            ServiceOptions {
+                exec: Some(config), ..
+            } => Ok(Self::Exec(lure_exec_service::Service::new(config))),
+            ServiceOptions { .. } => Err(RunError::NoServicesEnabled),
+        }
+    }
+
+    pub async fn fetch(&self) -> Result<PlaybackStatus, ServiceError> {
+        match self {
+            Self::LastFm(s) => s.fetch().await.map_err(ServiceError::LastFm),
+            Self::ListenBrainz(s) => s.fetch().await.map_err(ServiceError::ListenBrainz),
+            Self::Exec(s) => s.fetch().await.map_err(ServiceError::Exec),
+        }
+    }
+
+    /// Fetches a page of the listening history between the `from` and `to`
+    /// Unix timestamps. `cursor` is the `next` cursor of the previous page,
+    /// or `None` for the first page.
+    pub async fn history_page(
+        &self,
+        from: Option<i64>,
+        to: Option<i64>,
+        cursor: Option<i64>,
+    ) -> Result<HistoryPage, ServiceError> {
         match self {
-            Self::LastFm(s) => s.poll().await.map_err(ServiceError::LastFm),
-            Self::ListenBrainz(s) => s.poll().await.map_err(ServiceError::ListenBrainz),
+            Self::LastFm(s) => s
+                .recent_tracks(from, to, cursor.unwrap_or(1))
+                .await
+                .map_err(ServiceError::LastFm),
+            Self::ListenBrainz(s) => s
+                .listens(from, cursor.or(to))
+                .await
+                .map_err(ServiceError::ListenBrainz),
+            Self::Exec(_) => Err(ServiceError::NoHistory(self.name())),
+        }
+    }
+
+    /// Number of times `track` was played, if the service keeps count.
+    pub async fn play_count(&self, track: &TrackInfo) -> Result<Option<i64>, ServiceError> {
+        match self {
+            Self::LastFm(s) => s
+                .track_play_count(track)
+                .await
+                .map_err(ServiceError::LastFm),
+            Self::ListenBrainz(_) | Self::Exec(_) => Ok(None),
+        }
+    }
+
+    /// Number of times `artist` was played, if the service keeps count.
+    pub async fn artist_play_count(&self, artist: &str) -> Result<Option<i64>, ServiceError> {
+        match self {
+            Self::LastFm(s) => s
+                .artist_play_count(artist)
+                .await
+                .map_err(ServiceError::LastFm),
+            Self::ListenBrainz(_) | Self::Exec(_) => Ok(None),
+        }
+    }
+
+    /// The most played artist of the last week.
+    pub async fn top_artist_week(&self) -> Result<Option<String>, ServiceError> {
+        match self {
+            Self::LastFm(s) => s.top_artist("7day").await.map_err(ServiceError::LastFm),
+            Self::ListenBrainz(s) => s
+                .top_artist("this_week")
+                .await
+                .map_err(ServiceError::ListenBrainz),
+            Self::Exec(_) => Ok(None),
+        }
+    }
+
+    /// The most played track of the last week.
+    pub async fn top_track_week(&self) -> Result<Option<TrackInfo>, ServiceError> {
+        match self {
+            Self::LastFm(s) => s.top_track("7day").await.map_err(ServiceError::LastFm),
+            Self::ListenBrainz(s) => s
+                .top_track("this_week")
+                .await
+                .map_err(ServiceError::ListenBrainz),
+            Self::Exec(_) => Ok(None),
+        }
+    }
+
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::LastFm(_) => "lastfm",
+            Self::ListenBrainz(_) => "listenbrainz",
+            Self::Exec(_) => "exec",
+        }
+    }
+
+    pub const fn check_interval(&self) -> u64 {
+        match self {
+            Self::LastFm(s) => s.options().check_interval,
+            Self::ListenBrainz(s) => s.options().check_interval,
+            Self::Exec(s) => s.options().check_interval,
         }
     }
 
@@ -19,7 +137,18 @@ impl Service {
         match (self, error) {
             (Self::LastFm(_), ServiceError::LastFm(e)) => e.is_fatal(),
             (Self::ListenBrainz(_), ServiceError::ListenBrainz(e)) => e.is_fatal(),
+            (Self::Exec(_), ServiceError::Exec(e)) => e.is_fatal(),
             _ => true,
         }
     }
+
+    /// How long the service asked to wait before retrying, if it did.
+    pub const fn retry_after(error: &ServiceError) -> Option<Duration> {
+        match error {
+            ServiceError::ListenBrainz(lure_listenbrainz_service::ServiceError::Api(
+                lure_listenbrainz_service::APIError::RateLimitExceeded(seconds),
+            )) => Some(Duration::from_secs(*seconds)),
+            _ => None,
+        }
+    }
 }
@@ -0,0 +1,685 @@
+use std::time::{Duration, Instant};
+
+use lure_config::profile::Profile;
+use lure_types::{TrackInfo, stats::Stats};
+
+use crate::channel::ChannelSink;
+use crate::discord::DiscordSink;
+use crate::fediverse::FediverseSink;
+use crate::file::FileSink;
+use crate::hook::HookSink;
+use crate::matrix::MatrixSink;
+use crate::metrics::ProfileMetrics;
+use crate::mqtt::MqttSink;
+use crate::stoat::StoatTarget;
+use crate::stoat_profile::StoatProfileSink;
+use crate::webhook::WebhookSink;
+
+/// Delay before retrying a sink that failed, doubled after every failure.
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(5);
+/// Longest delay before retrying a sink that failed.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(5 * 60);
+/// Longest time a call to a sink may take before it's given up on, so that a
+/// hanging output doesn't stall the profile.
+const CALL_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// What a profile is listening to, as sent to the sinks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event<'a> {
+    Playing {
+        track: &'a TrackInfo,
+        /// The template of the privacy filter matching the track, to use
+        /// instead of the configured one.
+        replacement: Option<&'a str>,
+    },
+    /// Nothing is playing, or the track is hidden by a privacy filter.
+    Idle,
+}
+
+/// The options of a sink, used to build it and to match it on reload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SinkOptions {
+    Stoat {
+        options: lure_config::stoat::Options,
+        dry_run: bool,
+    },
+    /// The profile of a Stoat account, for the accounts with
+    /// `profile` options.
+    StoatProfile {
+        options: lure_config::stoat::Options,
+        dry_run: bool,
+    },
+    Channel {
+        options: lure_config::channel::Options,
+        dry_run: bool,
+    },
+    File {
+        options: lure_config::file::Options,
+        dry_run: bool,
+    },
+    Webhook {
+        options: lure_config::webhook::Options,
+        /// Name of the profile and of its service, sent along the events.
+        profile: String,
+        service: &'static str,
+        dry_run: bool,
+    },
+    Hook {
+        options: lure_config::hook::Options,
+        /// Name of the profile and of its service, passed to the command.
+        profile: String,
+        service: &'static str,
+        dry_run: bool,
+    },
+    Mqtt {
+        options: lure_config::mqtt::Options,
+        /// Name of the profile, used in the default topics.
+        profile: String,
+        dry_run: bool,
+    },
+    Discord {
+        options: lure_config::discord::Options,
+        dry_run: bool,
+    },
+    Matrix {
+        options: lure_config::matrix::Options,
+        dry_run: bool,
+    },
+    Fediverse {
+        options: lure_config::fediverse::Options,
+        dry_run: bool,
+    },
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SinkError {
+    #[error(transparent)]
+    Stoat(#[from] lure_stoat_api::Error),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Mqtt(#[from] rumqttc::ClientError),
+    #[error(transparent)]
+    Discord(#[from] lure_discord_ipc::Error),
+    #[error(transparent)]
+    Http(#[from] reqwest::Error),
+    /// The output rejected an update that can't succeed later, like one
+    /// going over a limit.
+    #[error("{0}")]
+    Rejected(String),
+    #[error("timed out after {}s", CALL_TIMEOUT.as_secs())]
+    TimedOut,
+}
+
+/// An output the listening activity of a profile is mirrored to.
+#[async_trait::async_trait]
+pub trait Sink: Send + Sync {
+    /// Name of the sink, used in logs.
+    fn name(&self) -> String;
+
+    /// Whether `options` are for the same output as this sink, so that they
+    /// can be applied with [`Sink::reconfigure`] instead of building a new
+    /// sink.
+    fn accepts(&self, options: &SinkOptions) -> bool;
+
+    /// Applies new options accepted by [`Sink::accepts`].
+    fn reconfigure(&mut self, options: SinkOptions);
+
+    /// Sends the current event. Called after every poll, so unchanged events
+    /// should be skipped.
+    async fn update(
+        &mut self,
+        event: &Event<'_>,
+        stats: &Stats,
+        metrics: &ProfileMetrics,
+    ) -> Result<(), SinkError>;
+
+    /// Reports a failed poll of the listening service. Ignored by default.
+    async fn error(&mut self, _error: &str) -> Result<(), SinkError> {
+        Ok(())
+    }
+
+    /// Checks that the output can be used, for `lure check --online`.
+    /// Building the sink already checks the credentials of most outputs.
+    async fn verify(&self) -> Result<(), SinkError> {
+        Ok(())
+    }
+
+    /// Puts the output back in the state it had before lure started, while
+    /// keeping the sink usable.
+    async fn reset(&mut self) -> Result<(), SinkError>;
+
+    /// Puts the output back in the state it had before lure started, when
+    /// the sink is removed or lure stops.
+    async fn restore(&mut self) -> Result<(), SinkError> {
+        self.reset().await
+    }
+}
+
+impl SinkOptions {
+    /// Returns the options of every sink of `profile`.
+    pub fn of(profile: &Profile) -> Vec<Self> {
+        let stoat = profile.stoat.0.iter().map(|options| Self::Stoat {
+            options: options.clone(),
+            dry_run: profile.dry_run,
+        });
+        let stoat_profiles = profile
+            .stoat
+            .0
+            .iter()
+            .filter(|options| options.profile.is_enabled())
+            .map(|options| Self::StoatProfile {
+                options: options.clone(),
+                dry_run: profile.dry_run,
+            });
+        let channels = profile.channels.iter().map(|options| Self::Channel {
+            options: options.clone(),
+            dry_run: profile.dry_run,
+        });
+        let files = profile.files.iter().map(|options| Self::File {
+            options: options.clone(),
+            dry_run: profile.dry_run,
+        });
+
+        let webhooks = profile.webhooks.iter().map(|options| Self::Webhook {
+            options: options.clone(),
+            profile: profile.name.clone(),
+            service: profile.service.name().unwrap_or_default(),
+            dry_run: profile.dry_run,
+        });
+
+        let hooks = profile.hooks.iter().map(|options| Self::Hook {
+            options: options.clone(),
+            profile: profile.name.clone(),
+            service: profile.service.name().unwrap_or_default(),
+            dry_run: profile.dry_run,
+        });
+
+        let mqtt = profile.mqtt.iter().map(|options| Self::Mqtt {
+            options: options.clone(),
+            profile: profile.name.clone(),
+            dry_run: profile.dry_run,
+        });
+
+        let discord = profile.discord.iter().map(|options| Self::Discord {
+            options: options.clone(),
+            dry_run: profile.dry_run,
+        });
+
+        let matrix = profile.matrix.iter().map(|options| Self::Matrix {
+            options: options.clone(),
+            dry_run: profile.dry_run,
+        });
+
+        let fediverse = profile.fediverse.iter().map(|options| Self::Fediverse {
+            options: options.clone(),
+            dry_run: profile.dry_run,
+        });
+
+        stoat
+            .chain(stoat_profiles)
+            .chain(channels)
+            .chain(files)
+            .chain(webhooks)
+            .chain(hooks)
+            .chain(mqtt)
+            .chain(discord)
+            .chain(matrix)
+            .chain(fediverse)
+            .collect()
+    }
+
+    /// What kind of output the options are for, used in logs before the
+    /// sink is built.
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::Stoat { .. } => "stoat",
+            Self::StoatProfile { .. } => "stoat profile",
+            Self::Channel { .. } => "channel",
+            Self::File { .. } => "file",
+            Self::Webhook { .. } => "webhook",
+            Self::Hook { .. } => "hook",
+            Self::Mqtt { .. } => "mqtt",
+            Self::Discord { .. } => "discord",
+            Self::Matrix { .. } => "matrix",
+            Self::Fediverse { .. } => "fediverse",
+        }
+    }
+
+    pub async fn build(self, http_client: &reqwest::Client) -> Result<Box<dyn Sink>, SinkError> {
+        Ok(match self {
+            Self::Stoat { options, dry_run } => {
+                Box::new(StoatTarget::connect(options, http_client, dry_run).await?)
+            }
+            Self::StoatProfile { options, dry_run } => {
+                Box::new(StoatProfileSink::connect(options, http_client, dry_run).await?)
+            }
+            Self::Channel { options, dry_run } => {
+                Box::new(ChannelSink::new(options, http_client, dry_run)?)
+            }
+            Self::File { options, dry_run } => Box::new(FileSink::new(options, dry_run)),
+            Self::Webhook {
+                options,
+                profile,
+                service,
+                dry_run,
+            } => Box::new(WebhookSink::new(
+                options,
+                profile,
+                service,
+                http_client,
+                dry_run,
+            )),
+            Self::Hook {
+                options,
+                profile,
+                service,
+                dry_run,
+            } => Box::new(HookSink::new(options, profile, service, dry_run)),
+            Self::Mqtt {
+                options,
+                profile,
+                dry_run,
+            } => Box::new(MqttSink::connect(options, profile, dry_run)),
+            Self::Discord { options, dry_run } => Box::new(DiscordSink::new(options, dry_run)),
+            Self::Matrix { options, dry_run } => {
+                Box::new(MatrixSink::connect(options, http_client, dry_run).await?)
+            }
+            Self::Fediverse { options, dry_run } => {
+                Box::new(FediverseSink::connect(options, http_client, dry_run).await?)
+            }
+        })
+    }
+}
+
+impl SinkError {
+    /// Whether retrying can't help, like when the credentials are wrong.
+    pub fn is_fatal(&self) -> bool {
+        match self {
+            Self::Stoat(lure_stoat_api::Error::ApiError(
+                lure_stoat_api::APIError::AuthenticationFailed,
+            )) => true,
+            Self::Http(error) => error.status() == Some(reqwest::StatusCode::UNAUTHORIZED),
+            Self::Rejected(_) => true,
+            _ => false,
+        }
+    }
+}
+
+/// The sinks of a profile. Every sink is updated on its own: a failing sink
+/// is retried with an exponential backoff without holding the others back,
+/// and a sink failing with a fatal error is disabled until its options
+/// change.
+pub struct Sinks {
+    profile: String,
+    slots: Vec<Slot>,
+}
+
+struct Slot {
+    sink: Box<dyn Sink>,
+    failures: u32,
+    retry_at: Option<Instant>,
+    disabled: bool,
+}
+
+impl Sinks {
+    /// Builds every sink. Fails if any of them can't be built, since that's
+    /// likely a configuration problem.
+    pub async fn connect(
+        profile: &str,
+        options: Vec<SinkOptions>,
+        http_client: &reqwest::Client,
+    ) -> Result<Self, SinkError> {
+        let sinks = futures::future::try_join_all(
+            options
+                .into_iter()
+                .map(|options| options.build(http_client)),
+        )
+        .await?;
+
+        Ok(Self {
+            profile: profile.to_string(),
+            slots: sinks.into_iter().map(Slot::new).collect(),
+        })
+    }
+
+    /// Applies new options. Sinks accepting them are reconfigured in place,
+    /// new ones are built, and removed ones are restored, logging any error.
+    /// Nothing changes if a new sink can't be built.
+    pub async fn reload(
+        &mut self,
+        options: Vec<SinkOptions>,
+        http_client: &reqwest::Client,
+    ) -> Result<(), SinkError> {
+        let (kept, added): (Vec<_>, Vec<_>) = options.into_iter().partition(|options| {
+            self.slots
+                .iter()
+                .any(|slot| !slot.disabled && slot.sink.accepts(options))
+        });
+
+        let built = futures::future::try_join_all(
+            added.into_iter().map(|options| options.build(http_client)),
+        )
+        .await?;
+
+        let mut previous = std::mem::take(&mut self.slots);
+        for options in kept {
+            if let Some(index) = previous
+                .iter()
+                .position(|slot| !slot.disabled && slot.sink.accepts(&options))
+            {
+                let mut slot = previous.swap_remove(index);
+                slot.sink.reconfigure(options);
+                self.slots.push(slot);
+            }
+        }
+        for sink in built {
+            println!("[{}] Added {}.", self.profile, sink.name());
+            self.slots.push(Slot::new(sink));
+        }
+
+        for mut slot in previous {
+            println!("[{}] Removed {}.", self.profile, slot.sink.name());
+            if !slot.disabled
+                && let Err(error) = slot.sink.restore().await
+            {
+                eprintln!(
+                    "[{}] Failed to restore {}: {error}",
+                    self.profile,
+                    slot.sink.name()
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Sends `event` to every sink that isn't waiting to be retried.
+    pub async fn update(&mut self, event: &Event<'_>, stats: &Stats, metrics: &ProfileMetrics) {
+        let profile = &self.profile;
+
+        futures::future::join_all(self.slots.iter_mut().filter(|slot| slot.is_ready()).map(
+            |slot| async move {
+                let result = with_timeout(slot.sink.update(event, stats, metrics)).await;
+                slot.record(profile, result);
+            },
+        ))
+        .await;
+    }
+
+    /// Reports a failed poll to every sink that isn't waiting to be retried.
+    pub async fn error(&mut self, error: &str) {
+        let profile = &self.profile;
+
+        futures::future::join_all(self.slots.iter_mut().filter(|slot| slot.is_ready()).map(
+            |slot| async move {
+                let result = with_timeout(slot.sink.error(error)).await;
+                slot.record(profile, result);
+            },
+        ))
+        .await;
+    }
+
+    /// Resets every sink. Errors are only logged.
+    pub async fn reset(&mut self) {
+        let profile = &self.profile;
+
+        futures::future::join_all(self.slots.iter_mut().filter(|slot| !slot.disabled).map(
+            |slot| async move {
+                let result = with_timeout(slot.sink.reset()).await;
+                slot.record(profile, result);
+            },
+        ))
+        .await;
+    }
+
+    /// Restores every sink, returning the first error after trying all of
+    /// them.
+    pub async fn restore(self) -> Result<(), SinkError> {
+        let results = futures::future::join_all(
+            self.slots
+                .into_iter()
+                .filter(|slot| !slot.disabled)
+                .map(|mut slot| async move { with_timeout(slot.sink.restore()).await }),
+        )
+        .await;
+
+        results.into_iter().collect()
+    }
+}
+
+/// Gives up on a call to a sink taking longer than [`CALL_TIMEOUT`].
+async fn with_timeout(call: impl Future<Output = Result<(), SinkError>>) -> Result<(), SinkError> {
+    tokio::time::timeout(CALL_TIMEOUT, call)
+        .await
+        .unwrap_or(Err(SinkError::TimedOut))
+}
+
+impl Slot {
+    fn new(sink: Box<dyn Sink>) -> Self {
+        Self {
+            sink,
+            failures: 0,
+            retry_at: None,
+            disabled: false,
+        }
+    }
+
+    fn is_ready(&self) -> bool {
+        !self.disabled
+            && self
+                .retry_at
+                .is_none_or(|retry_at| Instant::now() >= retry_at)
+    }
+
+    /// Records the result of a call to the sink, scheduling a retry or
+    /// disabling it if it failed.
+    fn record(&mut self, profile: &str, result: Result<(), SinkError>) {
+        let Err(error) = result else {
+            self.failures = 0;
+            self.retry_at = None;
+            return;
+        };
+
+        if error.is_fatal() {
+            self.disabled = true;
+            eprintln!(
+                "[{profile}] Disabled {} because of a fatal error: {error}",
+                self.sink.name()
+            );
+            return;
+        }
+
+        let delay = INITIAL_RETRY_DELAY
+            .saturating_mul(2_u32.saturating_pow(self.failures))
+            .min(MAX_RETRY_DELAY);
+        self.failures = self.failures.saturating_add(1);
+        self.retry_at = Some(Instant::now() + delay);
+        eprintln!(
+            "[{profile}] {} failed, retrying in {}s: {error}",
+            self.sink.name(),
+            delay.as_secs()
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::metrics::Metrics;
+
+    #[derive(Debug, Clone, Copy)]
+    enum Outcome {
+        Ok,
+        Failed,
+        Fatal,
+        Hang,
+    }
+
+    /// A sink returning `outcome` and counting the calls to it.
+    struct FakeSink {
+        outcome: Outcome,
+        calls: Arc<AtomicUsize>,
+    }
+
+    impl FakeSink {
+        async fn result(&self) -> Result<(), SinkError> {
+            self.calls.fetch_add(1, Ordering::Relaxed);
+
+            match self.outcome {
+                Outcome::Ok => Ok(()),
+                Outcome::Failed => Err(std::io::Error::other("unreachable").into()),
+                Outcome::Fatal => Err(lure_stoat_api::Error::ApiError(
+                    lure_stoat_api::APIError::AuthenticationFailed,
+                )
+                .into()),
+                Outcome::Hang => std::future::pending().await,
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Sink for FakeSink {
+        fn name(&self) -> String {
+            String::from("fake")
+        }
+
+        fn accepts(&self, _options: &SinkOptions) -> bool {
+            false
+        }
+
+        fn reconfigure(&mut self, _options: SinkOptions) {}
+
+        async fn update(
+            &mut self,
+            _event: &Event<'_>,
+            _stats: &Stats,
+            _metrics: &ProfileMetrics,
+        ) -> Result<(), SinkError> {
+            self.result().await
+        }
+
+        async fn error(&mut self, _error: &str) -> Result<(), SinkError> {
+            self.result().await
+        }
+
+        async fn reset(&mut self) -> Result<(), SinkError> {
+            self.result().await
+        }
+    }
+
+    fn sinks(outcomes: &[Outcome]) -> (Sinks, Vec<Arc<AtomicUsize>>) {
+        let calls: Vec<_> = outcomes
+            .iter()
+            .map(|_| Arc::new(AtomicUsize::new(0)))
+            .collect();
+        let slots = outcomes
+            .iter()
+            .zip(&calls)
+            .map(|(&outcome, calls)| {
+                Slot::new(Box::new(FakeSink {
+                    outcome,
+                    calls: Arc::clone(calls),
+                }))
+            })
+            .collect();
+
+        (
+            Sinks {
+                profile: String::from("kitty"),
+                slots,
+            },
+            calls,
+        )
+    }
+
+    fn failed() -> Result<(), SinkError> {
+        Err(std::io::Error::other("unreachable").into())
+    }
+
+    #[test]
+    fn test_retry_delay_doubles_up_to_the_maximum() {
+        let (mut sinks, _) = sinks(&[Outcome::Failed]);
+        let slot = &mut sinks.slots[0];
+
+        for seconds in [5, 10, 20, 40, 80, 160, 300, 300] {
+            let before = Instant::now();
+            slot.record("kitty", failed());
+            let delay = slot.retry_at.unwrap() - before;
+
+            assert!(delay >= Duration::from_secs(seconds), "{delay:?}");
+            assert!(delay < Duration::from_secs(seconds + 1), "{delay:?}");
+            assert!(!slot.is_ready());
+        }
+    }
+
+    #[test]
+    fn test_success_resets_the_retry_delay() {
+        let (mut sinks, _) = sinks(&[Outcome::Failed]);
+        let slot = &mut sinks.slots[0];
+
+        slot.record("kitty", failed());
+        slot.record("kitty", failed());
+        slot.record("kitty", Ok(()));
+
+        assert_eq!(slot.failures, 0);
+        assert!(slot.is_ready());
+
+        let before = Instant::now();
+        slot.record("kitty", failed());
+        assert!(slot.retry_at.unwrap() - before < Duration::from_secs(6));
+    }
+
+    #[tokio::test]
+    async fn test_failing_sink_waits_without_holding_back_the_others() {
+        let (mut sinks, calls) = sinks(&[Outcome::Failed, Outcome::Ok]);
+        let metrics = Arc::new(Metrics::new()).profile("kitty");
+
+        sinks
+            .update(&Event::Idle, &Stats::default(), &metrics)
+            .await;
+        sinks
+            .update(&Event::Idle, &Stats::default(), &metrics)
+            .await;
+        sinks.error("unreachable").await;
+
+        assert_eq!(calls[0].load(Ordering::Relaxed), 1);
+        assert_eq!(calls[1].load(Ordering::Relaxed), 3);
+        assert!(!sinks.slots[0].disabled);
+    }
+
+    #[tokio::test]
+    async fn test_fatal_error_disables_the_sink() {
+        let (mut sinks, calls) = sinks(&[Outcome::Fatal, Outcome::Ok]);
+
+        sinks.error("unreachable").await;
+        assert!(sinks.slots[0].disabled);
+        assert!(!sinks.slots[0].is_ready());
+
+        sinks.error("unreachable").await;
+        sinks.reset().await;
+        sinks.restore().await.unwrap();
+
+        assert_eq!(calls[0].load(Ordering::Relaxed), 1);
+        assert_eq!(calls[1].load(Ordering::Relaxed), 4);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_hanging_sink_times_out_and_is_retried() {
+        let (mut sinks, calls) = sinks(&[Outcome::Hang, Outcome::Ok]);
+
+        sinks.error("unreachable").await;
+
+        let slot = &sinks.slots[0];
+        assert_eq!(slot.failures, 1);
+        assert!(!slot.disabled);
+        assert!(!slot.is_ready());
+        assert!(!SinkError::TimedOut.is_fatal());
+        assert_eq!(calls[1].load(Ordering::Relaxed), 1);
+    }
+}
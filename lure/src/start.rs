@@ -1,66 +1,44 @@
-use std::path::{Path, PathBuf};
-use std::time::Duration;
-
-use figment::{
-    Figment,
-    providers::{Env, Format as _, Yaml},
-};
-use figment_file_provider_adapter::FileAdapter;
+use std::path::Path;
+use std::sync::Arc;
+
 use futures::FutureExt as _;
-use lure_config::ServiceOptions;
-use lure_types::{PlaybackStatus, TrackInfo};
-use tokio::time::sleep;
-
-use crate::service::Service;
-
-pub async fn run(config_path: Option<PathBuf>) -> Result<(), RunError> {
-    const SECURE_CONFIG_KEYS: &[&str; 2] = &["session_token", "api_key"];
-
-    let config_path = config_path
-        .as_ref()
-        .map_or_else(|| Path::new("config.yaml"), PathBuf::as_path);
-
-    let config: lure_config::Config = Figment::new()
-        .merge(Yaml::file(config_path))
-        .merge(Env::prefixed("LURE_").split("__"))
-        .merge(FileAdapter::wrap(Yaml::file(config_path)).only(SECURE_CONFIG_KEYS))
-        .merge(FileAdapter::wrap(Env::prefixed("LURE_").split("__")).only(SECURE_CONFIG_KEYS))
-        .extract()?;
-
-    let service = match config.service {
-        ServiceOptions {
-            lastfm: Some(config),
-            listenbrainz: None,
-        } => Service::LastFm(
-            lure_lastfm_service::Service::try_new(config).map_err(ServiceError::from)?,
-        ),
-        ServiceOptions {
-            lastfm: None,
-            listenbrainz: Some(config),
-        } => Service::ListenBrainz(
-            lure_listenbrainz_service::Service::try_new(config).map_err(ServiceError::from)?,
-        ),
-        ServiceOptions {
-            lastfm: Some(_),
-            listenbrainz: Some(_),
-        } => {
-            return Err(RunError::MoreThanOneServiceEnabled(
-                "lastfm and listenbrainz".to_string(),
-            ));
+use lure_history::History;
+use tokio::task::JoinHandle;
+
+use crate::metrics::Metrics;
+use crate::reload::Reloader;
+use crate::state::State;
+use crate::supervisor::Supervisor;
+
+pub async fn run(config_path: &Path, dry_run: bool) -> Result<(), RunError> {
+    let load = || {
+        let figment = lure_config::loader::figment(config_path);
+
+        if dry_run {
+            lure_config::loader::extract(&figment.merge(("dry_run", true)))
+        } else {
+            lure_config::loader::extract(&figment)
         }
-        ServiceOptions {
-            lastfm: None,
-            listenbrainz: None,
-        } => return Err(RunError::NoServicesEnabled),
     };
+    let mut config = load()?;
+    if config.dry_run {
+        println!("Dry run, the statuses are only logged.");
+    }
 
-    let stoat_client = lure_stoat_api::Client::try_new(
-        config.stoat.api_url,
-        &lure_stoat_models::Authentication::SessionToken(config.stoat.session_token),
-    )?;
+    let state = Arc::new(State::default());
+    state.update(|snapshot| snapshot.config_loaded = true);
+    let metrics = Arc::new(Metrics::new());
 
-    let first_status = stoat_client.get_status_text().await?;
-    let mut previous_track: Option<TrackInfo> = None;
+    let mut server = spawn_server(&config.server, &state, &metrics).await?;
+    let mut bot = spawn_bot(&config, &state)?;
+    let history = if config.history.enable {
+        Some(Arc::new(History::open(&config.history.path())?))
+    } else {
+        None
+    };
+    let mut supervisor = Supervisor::new(Arc::clone(&state), Arc::clone(&metrics), history);
+    supervisor.apply(config.profiles());
+    let mut reloader = Reloader::new(config_path);
 
     let mut ctrl_c = Box::pin(tokio::signal::ctrl_c().fuse());
 
@@ -70,55 +48,85 @@ pub async fn run(config_path: Option<PathBuf>) -> Result<(), RunError> {
                 println!("Received Ctrl+C, exiting...");
                 break;
             }
-            result = service.poll() => match result {
-                Ok(PlaybackStatus::Playing(track)) => {
-                    if previous_track.as_ref().is_some_and(|prev| prev == &track) {
+            () = reloader.changed() => {
+                let new_config = match load() {
+                    Ok(new_config) if new_config == config => continue,
+                    Ok(new_config) => new_config,
+                    Err(diagnostics) => {
+                        eprintln!("Failed to reload the configuration, keeping the current one. {diagnostics}");
                         continue;
                     }
+                };
 
-                    let status_text = config
-                        .stoat
-                        .status
-                        .template
-                        .replace("%ARTIST%", &track.artist)
-                        .replace("%NAME%", &track.title);
-
-                    match stoat_client.set_status_text(Some(status_text)).await {
-                        Ok(()) => previous_track = Some(track),
-                        Err(lure_stoat_api::Error::ApiError(
-                            lure_stoat_api::APIError::RateLimitExceeded(remaining)
-                        )) => sleep(Duration::from_millis(remaining)).await,
-                        Err(error) => return Err(error.into()),
+                if new_config.server != config.server {
+                    if let Some(server) = server.take() {
+                        server.abort();
                     }
+                    server = spawn_server(&new_config.server, &state, &metrics).await?;
                 }
-                Ok(PlaybackStatus::NotPlaying) => {
-                    if previous_track.is_none() {
-                        continue;
-                    }
 
-                    match stoat_client.set_status_text(first_status.clone()).await {
-                        Ok(()) => previous_track = None,
-                        Err(lure_stoat_api::Error::ApiError(
-                            lure_stoat_api::APIError::RateLimitExceeded(remaining)
-                        )) => sleep(Duration::from_millis(remaining)).await,
-                        Err(error) => return Err(error.into()),
+                if new_config.bot != config.bot || new_config.dry_run != config.dry_run {
+                    if let Some(bot) = bot.take() {
+                        bot.abort();
                     }
+                    bot = spawn_bot(&new_config, &state)?;
                 }
-                Err(error) => {
-                    if service.is_fatal_error(&error) {
-                        eprintln!("Fatal error: {error}");
-                        break;
-                    }
 
-                    eprintln!("Non-fatal error, retrying: {error}");
+                if new_config.history != config.history {
+                    println!("History options changed, restart lure to apply them.");
+                }
+
+                supervisor.apply(new_config.profiles());
+
+                config = new_config;
+                println!("Reloaded the configuration.");
+            }
+            () = supervisor.stopped() => {
+                if supervisor.is_idle() {
+                    println!("Every profile has stopped, exiting...");
+                    break;
                 }
             }
         }
     }
 
-    stoat_client.set_status_text(first_status).await?;
+    if let Some(server) = server {
+        server.abort();
+    }
+    if let Some(bot) = bot {
+        bot.abort();
+    }
+
+    supervisor.stop().await
+}
+
+async fn spawn_server(
+    options: &lure_config::server::Options,
+    state: &Arc<State>,
+    metrics: &Arc<Metrics>,
+) -> Result<Option<JoinHandle<()>>, RunError> {
+    if !options.enable {
+        return Ok(None);
+    }
+
+    Ok(Some(
+        crate::server::spawn(options, Arc::clone(state), Arc::clone(metrics)).await?,
+    ))
+}
 
-    Ok(())
+fn spawn_bot(
+    config: &lure_config::Config,
+    state: &Arc<State>,
+) -> Result<Option<JoinHandle<()>>, RunError> {
+    let Some(options) = &config.bot else {
+        return Ok(None);
+    };
+
+    Ok(Some(crate::bot::spawn(
+        options.clone(),
+        Arc::clone(state),
+        config.dry_run,
+    )?))
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -127,6 +135,21 @@ pub enum ServiceError {
     LastFm(#[from] lure_lastfm_service::ServiceError),
     #[error(transparent)]
     ListenBrainz(#[from] lure_listenbrainz_service::ServiceError),
+    #[error(transparent)]
+    Exec(#[from] lure_exec_service::ServiceError),
+    #[error("The {0} service has no listening history.")]
+    NoHistory(&'static str),
+}
+
+impl ServiceError {
+    pub const fn kind(&self) -> &'static str {
+        match self {
+            Self::LastFm(error) => error.kind(),
+            Self::ListenBrainz(error) => error.kind(),
+            Self::Exec(error) => error.kind(),
+            Self::NoHistory(_) => "no_history",
+        }
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -138,9 +161,21 @@ pub enum RunError {
     #[error(transparent)]
     StoatApi(#[from] lure_stoat_api::Error),
     #[error(transparent)]
-    Figment(#[from] figment::Error),
+    Sink(#[from] crate::sink::SinkError),
+    #[error(transparent)]
+    Config(#[from] lure_config::Diagnostics),
     #[error(transparent)]
     Anyhow(#[from] anyhow::Error),
     #[error(transparent)]
     Service(#[from] ServiceError),
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    History(#[from] lure_history::Error),
+    #[error("Invalid time zone: {0}")]
+    TimeZone(#[from] jiff::Error),
+    #[error("Invalid privacy filter: {0}")]
+    Filter(#[from] lure_config::filter::Error),
+    #[error("{0} profile(s) stopped because of an error.")]
+    ProfilesFailed(usize),
 }
@@ -0,0 +1,298 @@
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use lure_types::{TrackInfo, stats::Stats};
+
+/// Runtime state of lure, shared with the HTTP server.
+#[derive(Default)]
+pub struct State {
+    inner: RwLock<Snapshot>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct Snapshot {
+    pub config_loaded: bool,
+    pub profiles: BTreeMap<String, ProfileSnapshot>,
+}
+
+#[derive(Debug, Clone, Default, serde::Serialize)]
+pub struct ProfileSnapshot {
+    pub running: bool,
+    /// Whether the profile is outside of its schedule, and doesn't poll.
+    pub paused: bool,
+    /// When the profile last got back inside its schedule.
+    pub resumed_at: Option<u64>,
+    pub stoat_authenticated: bool,
+    pub active_service: Option<&'static str>,
+    pub check_interval: Option<u64>,
+    pub started_at: Option<u64>,
+    pub last_poll: Option<u64>,
+    pub last_successful_poll: Option<u64>,
+    pub last_error: Option<LastError>,
+    /// What the sinks show, with the privacy filters applied. The track
+    /// itself isn't served, so that a filtered track never shows up there.
+    #[serde(skip)]
+    pub now_playing: Option<NowPlaying>,
+}
+
+/// What the sinks of a profile show. Unset while the profile is paused.
+#[derive(Debug, Clone)]
+pub enum NowPlaying {
+    Playing {
+        track: TrackInfo,
+        stats: Stats,
+    },
+    /// The track is hidden by a privacy filter, and the rendered replacement
+    /// template of it is shown instead.
+    Hidden {
+        replacement: String,
+    },
+    /// Nothing is playing, or the track is hidden by a privacy filter
+    /// without a replacement.
+    Idle {
+        stats: Stats,
+    },
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct LastError {
+    pub message: String,
+    pub timestamp: u64,
+}
+
+/// The part of [`State`] that belongs to a single profile.
+pub struct ProfileState {
+    state: Arc<State>,
+    name: String,
+}
+
+impl State {
+    pub fn snapshot(&self) -> Snapshot {
+        self.inner
+            .read()
+            .map(|snapshot| snapshot.clone())
+            .unwrap_or_default()
+    }
+
+    pub fn update(&self, f: impl FnOnce(&mut Snapshot)) {
+        if let Ok(mut snapshot) = self.inner.write() {
+            f(&mut snapshot);
+        }
+    }
+
+    /// Starts tracking the state of the profile `name`, replacing any
+    /// previous state it had.
+    pub fn profile(self: &Arc<Self>, name: &str) -> ProfileState {
+        self.update(|snapshot| {
+            snapshot.profiles.insert(
+                name.to_string(),
+                ProfileSnapshot {
+                    running: true,
+                    ..ProfileSnapshot::default()
+                },
+            );
+        });
+
+        ProfileState {
+            state: Arc::clone(self),
+            name: name.to_string(),
+        }
+    }
+
+    pub fn remove_profile(&self, name: &str) {
+        self.update(|snapshot| {
+            snapshot.profiles.remove(name);
+        });
+    }
+}
+
+impl ProfileState {
+    pub fn update(&self, f: impl FnOnce(&mut ProfileSnapshot)) {
+        self.state.update(|snapshot| {
+            if let Some(profile) = snapshot.profiles.get_mut(&self.name) {
+                f(profile);
+            }
+        });
+    }
+
+    pub fn record_poll(&self) {
+        self.update(|snapshot| {
+            let now = unix_timestamp();
+            snapshot.last_poll = Some(now);
+            snapshot.last_successful_poll = Some(now);
+        });
+    }
+
+    pub fn record_error(&self, error: &impl std::fmt::Display) {
+        self.update(|snapshot| {
+            let now = unix_timestamp();
+            snapshot.last_poll = Some(now);
+            snapshot.last_error = Some(LastError {
+                message: error.to_string(),
+                timestamp: now,
+            });
+        });
+    }
+}
+
+impl Snapshot {
+    /// Whether every running profile is live. Profiles that stopped
+    /// because of an error are left out, so that they don't affect the
+    /// others.
+    pub fn is_live(&self, max_missed_polls: u32) -> bool {
+        self.running_profiles()
+            .all(|profile| profile.is_live(max_missed_polls))
+    }
+
+    /// Whether the configuration is loaded, at least one profile is
+    /// running, and every running profile is ready.
+    pub fn is_ready(&self, max_missed_polls: u32) -> bool {
+        self.config_loaded
+            && self.running_profiles().next().is_some()
+            && self
+                .running_profiles()
+                .all(|profile| profile.is_ready(max_missed_polls))
+    }
+
+    fn running_profiles(&self) -> impl Iterator<Item = &ProfileSnapshot> {
+        self.profiles.values().filter(|profile| profile.running)
+    }
+}
+
+impl ProfileSnapshot {
+    /// Whether the last poll attempt, successful or not, happened within
+    /// `max_missed_polls` check intervals. Paused profiles are always live.
+    pub fn is_live(&self, max_missed_polls: u32) -> bool {
+        self.paused
+            || self.within_missed_polls(
+                self.last_poll.or(self.started_at).max(self.resumed_at),
+                max_missed_polls,
+            )
+    }
+
+    /// Whether Stoat is authenticated and the last successful poll
+    /// happened within `max_missed_polls` check intervals. Paused profiles
+    /// only need Stoat to be authenticated.
+    pub fn is_ready(&self, max_missed_polls: u32) -> bool {
+        self.stoat_authenticated
+            && (self.paused
+                || self.within_missed_polls(self.last_successful_poll, max_missed_polls))
+    }
+
+    fn within_missed_polls(&self, timestamp: Option<u64>, max_missed_polls: u32) -> bool {
+        let (Some(check_interval), Some(timestamp)) = (self.check_interval, timestamp) else {
+            return false;
+        };
+
+        unix_timestamp().saturating_sub(timestamp)
+            <= check_interval.saturating_mul(u64::from(max_missed_polls))
+    }
+}
+
+pub fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A running profile polling every 60 seconds, which last polled
+    /// `seconds_ago`.
+    fn profile(seconds_ago: u64) -> ProfileSnapshot {
+        let last_poll = unix_timestamp() - seconds_ago;
+
+        ProfileSnapshot {
+            running: true,
+            stoat_authenticated: true,
+            check_interval: Some(60),
+            started_at: Some(last_poll),
+            last_poll: Some(last_poll),
+            last_successful_poll: Some(last_poll),
+            ..ProfileSnapshot::default()
+        }
+    }
+
+    fn snapshot(profiles: impl IntoIterator<Item = (&'static str, ProfileSnapshot)>) -> Snapshot {
+        Snapshot {
+            config_loaded: true,
+            profiles: profiles
+                .into_iter()
+                .map(|(name, profile)| (name.to_string(), profile))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_within_missed_polls() {
+        assert!(profile(0).is_live(3));
+        assert!(profile(0).is_ready(3));
+        assert!(profile(170).is_live(3));
+        assert!(profile(170).is_ready(3));
+
+        assert!(!profile(190).is_live(3));
+        assert!(!profile(190).is_ready(3));
+    }
+
+    #[test]
+    fn test_failing_polls_are_live_but_not_ready() {
+        let mut profile = profile(0);
+        profile.last_successful_poll = Some(unix_timestamp() - 600);
+
+        assert!(profile.is_live(3));
+        assert!(!profile.is_ready(3));
+    }
+
+    #[test]
+    fn test_paused_profile() {
+        let mut profile = profile(600);
+        profile.paused = true;
+
+        assert!(profile.is_live(3));
+        assert!(profile.is_ready(3));
+
+        profile.stoat_authenticated = false;
+        assert!(!profile.is_ready(3));
+    }
+
+    #[test]
+    fn test_no_running_profiles() {
+        let mut stopped = profile(600);
+        stopped.running = false;
+
+        assert!(snapshot([]).is_live(3));
+        assert!(!snapshot([]).is_ready(3));
+        assert!(snapshot([("kitty", stopped.clone())]).is_live(3));
+        assert!(!snapshot([("kitty", stopped)]).is_ready(3));
+    }
+
+    #[test]
+    fn test_stopped_profile_is_left_out() {
+        let mut stopped = profile(600);
+        stopped.running = false;
+        let snapshot = snapshot([("kitty", profile(0)), ("puppy", stopped)]);
+
+        assert!(snapshot.is_live(3));
+        assert!(snapshot.is_ready(3));
+    }
+
+    #[test]
+    fn test_late_profile_holds_back_the_others() {
+        let snapshot = snapshot([("kitty", profile(0)), ("puppy", profile(600))]);
+
+        assert!(!snapshot.is_live(3));
+        assert!(!snapshot.is_ready(3));
+    }
+
+    #[test]
+    fn test_config_not_loaded() {
+        let mut snapshot = snapshot([("kitty", profile(0))]);
+        snapshot.config_loaded = false;
+
+        assert!(snapshot.is_live(3));
+        assert!(!snapshot.is_ready(3));
+    }
+}
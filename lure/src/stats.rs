@@ -0,0 +1,217 @@
+use std::time::{Duration, Instant};
+
+use lure_config::{filter::Filters, profile::Profile};
+use lure_history::{History, Query};
+use lure_types::{TrackInfo, stats::Stats, template};
+
+use crate::service::Service;
+use crate::start::ServiceError;
+use crate::state::unix_timestamp;
+
+/// How long computed statistics are reused before they're computed again.
+const MAX_AGE: Duration = Duration::from_secs(5 * 60);
+const WEEK: i64 = 7 * 24 * 60 * 60;
+
+#[derive(Debug, thiserror::Error)]
+pub enum StatsError {
+    #[error(transparent)]
+    Service(#[from] ServiceError),
+    #[error(transparent)]
+    History(#[from] lure_history::Error),
+}
+
+/// The statistics used by the status templates of a profile.
+#[derive(Debug, Clone, Copy, Default)]
+struct Needed {
+    play_count: bool,
+    artist_play_count: bool,
+    top_artist_week: bool,
+    top_track_week: bool,
+    streak: bool,
+}
+
+/// Computes the statistics for the status templates of a profile, from the
+/// local listening history if it's enabled, or from the listening service
+/// otherwise.
+///
+/// Only the statistics the templates use are computed, and they're cached
+/// for a while, so that the service isn't queried on every poll. Failures
+/// are cached too, and the affected placeholders render as empty. The top
+/// artist and track of the week skip the ones hidden by a privacy filter.
+pub struct StatsProvider {
+    needed: Needed,
+    /// Statistics that don't depend on the current track.
+    general: Option<(Instant, Stats)>,
+    /// Statistics of the current track.
+    track: Option<(Instant, TrackInfo, Stats)>,
+}
+
+impl Needed {
+    fn of(profile: &Profile) -> Self {
+        let uses = |placeholder| {
+            profile
+                .templates()
+                .any(|template| template::uses(template, placeholder))
+        };
+
+        Self {
+            play_count: uses("%PLAYCOUNT%"),
+            artist_play_count: uses("%ARTIST_PLAYCOUNT%"),
+            top_artist_week: uses("%TOP_ARTIST_WEEK%"),
+            top_track_week: uses("%TOP_TRACK_WEEK%"),
+            streak: uses("%STREAK%"),
+        }
+    }
+}
+
+impl StatsProvider {
+    pub fn new(profile: &Profile) -> Self {
+        Self {
+            needed: Needed::of(profile),
+            general: None,
+            track: None,
+        }
+    }
+
+    /// Applies new options of the profile, dropping the cached statistics.
+    pub fn reload(&mut self, profile: &Profile) {
+        *self = Self::new(profile);
+    }
+
+    /// Returns the statistics for `track`, computing the ones that are
+    /// missing or too old. Errors are only logged.
+    pub async fn stats(
+        &mut self,
+        profile: &str,
+        service: &Service,
+        history: Option<&History>,
+        filters: &Filters,
+        track: Option<&TrackInfo>,
+    ) -> Stats {
+        if self
+            .general
+            .as_ref()
+            .is_none_or(|(computed_at, _)| computed_at.elapsed() >= MAX_AGE)
+        {
+            let stats = self
+                .general_stats(profile, service, history, filters)
+                .await
+                .unwrap_or_else(|error| {
+                    eprintln!("[{profile}] Failed to compute the statistics: {error}");
+                    Stats::default()
+                });
+            self.general = Some((Instant::now(), stats));
+        }
+
+        let mut stats = self
+            .general
+            .as_ref()
+            .map(|(_, stats)| stats.clone())
+            .unwrap_or_default();
+
+        let Some(track) = track else {
+            return stats;
+        };
+
+        if self.track.as_ref().is_none_or(|(computed_at, cached, _)| {
+            cached != track || computed_at.elapsed() >= MAX_AGE
+        }) {
+            let track_stats = self
+                .track_stats(profile, service, history, track)
+                .await
+                .unwrap_or_else(|error| {
+                    eprintln!("[{profile}] Failed to compute the statistics: {error}");
+                    Stats::default()
+                });
+            self.track = Some((Instant::now(), track.clone(), track_stats));
+        }
+
+        if let Some((_, _, track_stats)) = &self.track {
+            stats.play_count = track_stats.play_count;
+            stats.artist_play_count = track_stats.artist_play_count;
+        }
+
+        stats
+    }
+
+    async fn general_stats(
+        &self,
+        profile: &str,
+        service: &Service,
+        history: Option<&History>,
+        filters: &Filters,
+    ) -> Result<Stats, StatsError> {
+        let mut stats = Stats::default();
+        let now = i64::try_from(unix_timestamp()).unwrap_or(i64::MAX);
+        // Every artist or track, since the top ones may be hidden.
+        let week = Query {
+            profile: Some(profile.to_string()),
+            from: Some(now - WEEK),
+            to: None,
+            limit: u32::MAX,
+        };
+
+        if self.needed.top_artist_week {
+            stats.top_artist_week = match history {
+                Some(history) => history
+                    .top_artists(&week)?
+                    .into_iter()
+                    .map(|count| count.artist)
+                    .find(|artist| filters.find_artist(artist).is_none()),
+                None => service
+                    .top_artist_week()
+                    .await?
+                    .filter(|artist| filters.find_artist(artist).is_none()),
+            };
+        }
+
+        if self.needed.top_track_week {
+            stats.top_track_week = match history {
+                Some(history) => history
+                    .top_tracks(&week)?
+                    .into_iter()
+                    .map(|count| count.track)
+                    .find(|track| filters.find(track).is_none()),
+                None => service
+                    .top_track_week()
+                    .await?
+                    .filter(|track| filters.find(track).is_none()),
+            };
+        }
+
+        // Only the local history has the day of every play.
+        if self.needed.streak
+            && let Some(history) = history
+        {
+            stats.streak = Some(history.streak(profile, now)?);
+        }
+
+        Ok(stats)
+    }
+
+    async fn track_stats(
+        &self,
+        profile: &str,
+        service: &Service,
+        history: Option<&History>,
+        track: &TrackInfo,
+    ) -> Result<Stats, StatsError> {
+        let mut stats = Stats::default();
+
+        if self.needed.play_count {
+            stats.play_count = match history {
+                Some(history) => Some(history.play_count(profile, track)?),
+                None => service.play_count(track).await?,
+            };
+        }
+
+        if self.needed.artist_play_count {
+            stats.artist_play_count = match history {
+                Some(history) => Some(history.artist_play_count(profile, &track.artist)?),
+                None => service.artist_play_count(&track.artist).await?,
+            };
+        }
+
+        Ok(stats)
+    }
+}
@@ -0,0 +1,189 @@
+use std::time::{Duration, Instant};
+
+use lure_config::stoat::Options;
+use lure_types::{stats::Stats, template};
+
+use crate::metrics::ProfileMetrics;
+use crate::sink::{Event, Sink, SinkError, SinkOptions};
+
+/// A Stoat account whose status is kept in sync with the listening
+/// activity.
+pub struct StoatTarget {
+    options: Options,
+    /// The API client, or `None` in dry run, where the statuses are only
+    /// logged.
+    client: Option<lure_stoat_api::Client>,
+    /// The status the account had before lure started.
+    first_status: Option<String>,
+    /// The status last set by lure.
+    applied_status: Option<String>,
+    rate_limited_until: Option<Instant>,
+}
+
+impl StoatTarget {
+    /// Connects to the account, and fetches its current status. In dry run,
+    /// nothing is fetched, and the account is assumed to have no status.
+    pub async fn connect(
+        options: Options,
+        http_client: &reqwest::Client,
+        dry_run: bool,
+    ) -> Result<Self, lure_stoat_api::Error> {
+        if dry_run {
+            return Ok(Self {
+                options,
+                client: None,
+                first_status: None,
+                applied_status: None,
+                rate_limited_until: None,
+            });
+        }
+
+        let client = lure_stoat_api::Client::with_http_client(
+            http_client.clone(),
+            options.api_url.clone(),
+            &lure_stoat_models::Authentication::SessionToken(options.session_token.clone()),
+        )?;
+        let first_status = client.get_status_text().await?;
+
+        Ok(Self {
+            options,
+            client: Some(client),
+            applied_status: first_status.clone(),
+            first_status,
+            rate_limited_until: None,
+        })
+    }
+
+    fn account_name(&self) -> &str {
+        self.options
+            .name
+            .as_deref()
+            .unwrap_or(&self.options.api_url)
+    }
+
+    /// Renders the status for `event`, with the replacement template of the
+    /// privacy filter instead of the configured one if set, or the idle
+    /// status if nothing is playing. Falls back to the status the account
+    /// had before lure started when no idle status is configured.
+    pub fn status_text(&self, event: &Event<'_>, stats: &Stats) -> Option<String> {
+        match *event {
+            Event::Playing { track, replacement } => Some(template::render(
+                replacement.unwrap_or(&self.options.status.template),
+                Some(track),
+                stats,
+            )),
+            Event::Idle => self
+                .options
+                .status
+                .idle
+                .as_deref()
+                .map(|idle| template::render(idle, None, stats))
+                .or_else(|| self.first_status.clone()),
+        }
+    }
+
+    fn log_dry_run(&self, status_text: Option<&str>) {
+        match status_text {
+            Some(status_text) => println!(
+                "Stoat ({}) dry run, status set to: {status_text}",
+                self.account_name()
+            ),
+            None => println!("Stoat ({}) dry run, status cleared.", self.account_name()),
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Sink for StoatTarget {
+    fn name(&self) -> String {
+        format!("Stoat target {}", self.account_name())
+    }
+
+    /// Accepts the options of the same account, in the same dry run mode.
+    fn accepts(&self, options: &SinkOptions) -> bool {
+        let SinkOptions::Stoat { options, dry_run } = options else {
+            return false;
+        };
+
+        *dry_run == self.client.is_none()
+            && self.options.api_url == options.api_url
+            && self.options.session_token == options.session_token
+    }
+
+    fn reconfigure(&mut self, options: SinkOptions) {
+        debug_assert!(self.accepts(&options));
+
+        if let SinkOptions::Stoat { options, .. } = options {
+            self.options = options;
+        }
+    }
+
+    /// Updates the status if it changed. While rate limited, the update is
+    /// skipped and retried on the next call.
+    async fn update(
+        &mut self,
+        event: &Event<'_>,
+        stats: &Stats,
+        metrics: &ProfileMetrics,
+    ) -> Result<(), SinkError> {
+        let status_text = self.status_text(event, stats);
+
+        if status_text == self.applied_status
+            || self
+                .rate_limited_until
+                .is_some_and(|until| Instant::now() < until)
+        {
+            return Ok(());
+        }
+
+        let Some(client) = &self.client else {
+            self.log_dry_run(status_text.as_deref());
+            self.applied_status = status_text;
+
+            return Ok(());
+        };
+
+        let result = client.set_status_text(status_text.clone()).await;
+        metrics.observe_stoat_status_update(result.as_ref().err().map(lure_stoat_api::Error::kind));
+
+        match result {
+            Ok(()) => {
+                self.applied_status = status_text;
+                self.rate_limited_until = None;
+
+                Ok(())
+            }
+            Err(lure_stoat_api::Error::ApiError(lure_stoat_api::APIError::RateLimitExceeded(
+                remaining,
+            ))) => {
+                let duration = Duration::from_millis(remaining);
+                metrics.observe_rate_limit_sleep(duration);
+                self.rate_limited_until = Some(Instant::now() + duration);
+                println!(
+                    "Stoat ({}) rate limit exceeded, retrying in {}ms.",
+                    self.account_name(),
+                    remaining
+                );
+
+                Ok(())
+            }
+            Err(error) => Err(error.into()),
+        }
+    }
+
+    /// Sets the status back to the one the account had before lure
+    /// started.
+    async fn reset(&mut self) -> Result<(), SinkError> {
+        if self.applied_status == self.first_status {
+            return Ok(());
+        }
+
+        match &self.client {
+            Some(client) => client.set_status_text(self.first_status.clone()).await?,
+            None => self.log_dry_run(self.first_status.as_deref()),
+        }
+        self.applied_status.clone_from(&self.first_status);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,651 @@
+use std::ops::Range;
+use std::time::{Duration, Instant};
+
+use lure_config::stoat::{BioOptions, Options};
+use lure_stoat_models::schemas::user::{DataEditUser, DataUserProfile, FieldsUser};
+use lure_types::{stats::Stats, template};
+
+use crate::metrics::ProfileMetrics;
+use crate::sink::{Event, Sink, SinkError, SinkOptions};
+
+/// Bucket of the file server the profile backgrounds are uploaded to.
+const BACKGROUNDS_TAG: &str = "backgrounds";
+
+/// The profile of a Stoat account, with a section of its bio and its
+/// background kept up to date with the current track.
+pub struct StoatProfileSink {
+    options: Options,
+    /// The API client, or `None` in dry run, where the changes are only
+    /// logged.
+    client: Option<Client>,
+    /// The bio section before lure started, trimmed. Empty if the bio
+    /// didn't have the markers.
+    first_section: String,
+    /// The bio section last set by lure, or `None` if unknown.
+    applied_section: Option<String>,
+    /// The background the profile had before lure started.
+    first_background: Option<Background>,
+    /// URL of the cover art last set as the background, or `None` for the
+    /// first background.
+    applied_cover: Option<String>,
+    rate_limited_until: Option<Instant>,
+}
+
+struct Client {
+    api: lure_stoat_api::Client,
+    http_client: reqwest::Client,
+    user_id: String,
+    /// URL of the file server, only fetched if backgrounds are enabled.
+    autumn_url: String,
+}
+
+/// A background image. The original one is kept to upload it again on
+/// restore, since an uploaded file can only be attached once.
+#[derive(Clone)]
+struct Background {
+    filename: String,
+    content_type: String,
+    bytes: Vec<u8>,
+}
+
+impl StoatProfileSink {
+    /// Connects to the account, and fetches its current bio section and
+    /// background. In dry run, nothing is fetched, and the profile is
+    /// assumed to be empty.
+    pub async fn connect(
+        options: Options,
+        http_client: &reqwest::Client,
+        dry_run: bool,
+    ) -> Result<Self, lure_stoat_api::Error> {
+        if dry_run {
+            return Ok(Self {
+                options,
+                client: None,
+                first_section: String::new(),
+                applied_section: None,
+                first_background: None,
+                applied_cover: None,
+                rate_limited_until: None,
+            });
+        }
+
+        let api = lure_stoat_api::Client::with_http_client(
+            http_client.clone(),
+            options.api_url.clone(),
+            &lure_stoat_models::Authentication::SessionToken(options.session_token.clone()),
+        )?;
+        let user = api.get_self().await?;
+        let profile = api.get_profile(&user.id).await?;
+
+        let autumn_url = if options.profile.background {
+            let autumn = api.get_config().await?.features.autumn;
+            if !autumn.enabled {
+                return Err(lure_stoat_api::APIError::Unknown(
+                    "The instance has no file server for the profile background.".to_string(),
+                )
+                .into());
+            }
+
+            autumn.url
+        } else {
+            String::new()
+        };
+
+        let first_background = match profile.background {
+            Some(file) if options.profile.background => Some(Background {
+                bytes: api.download_file(&autumn_url, &file.tag, &file.id).await?,
+                filename: file.filename,
+                content_type: if file.content_type.is_empty() {
+                    String::from("application/octet-stream")
+                } else {
+                    file.content_type
+                },
+            }),
+            _ => None,
+        };
+
+        let first_section = options.profile.bio.as_ref().and_then(|bio| {
+            let content = profile.content.as_deref().unwrap_or_default();
+            section_range(content, bio).map(|range| content[range].trim().to_string())
+        });
+
+        Ok(Self {
+            client: Some(Client {
+                api,
+                http_client: http_client.clone(),
+                user_id: user.id,
+                autumn_url,
+            }),
+            applied_section: first_section.clone(),
+            first_section: first_section.unwrap_or_default(),
+            first_background,
+            applied_cover: None,
+            options,
+            rate_limited_until: None,
+        })
+    }
+
+    fn account_name(&self) -> &str {
+        self.options
+            .name
+            .as_deref()
+            .unwrap_or(&self.options.api_url)
+    }
+
+    /// Renders the bio section for `event`, with the replacement template
+    /// of the privacy filter instead of the configured one if set, or the
+    /// idle section if nothing is playing. Falls back to the section the bio
+    /// had before lure started when no idle section is configured.
+    fn section(&self, bio: &BioOptions, event: &Event<'_>, stats: &Stats) -> String {
+        match *event {
+            Event::Playing { track, replacement } => {
+                template::render(replacement.unwrap_or(&bio.template), Some(track), stats)
+            }
+            Event::Idle => bio.idle.as_deref().map_or_else(
+                || self.first_section.clone(),
+                |idle| template::render(idle, None, stats),
+            ),
+        }
+    }
+
+    /// Replaces the bio section, if it changed. The bio is fetched again
+    /// first, so that changes made to the rest of it are kept.
+    async fn set_section(&mut self, section: String) -> Result<(), SinkError> {
+        let Some(bio) = &self.options.profile.bio else {
+            return Ok(());
+        };
+        if self.applied_section.as_ref() == Some(&section) {
+            return Ok(());
+        }
+
+        let Some(client) = &self.client else {
+            println!(
+                "Stoat profile ({}) dry run, bio section set to: {section}",
+                self.account_name()
+            );
+            self.applied_section = Some(section);
+
+            return Ok(());
+        };
+
+        let content = client
+            .api
+            .get_profile(&client.user_id)
+            .await?
+            .content
+            .unwrap_or_default();
+        let Some(content) = replace_section(&content, bio, &section) else {
+            eprintln!(
+                "Stoat profile ({}) bio has no `{}` and `{}` markers, leaving it untouched.",
+                self.account_name(),
+                bio.start_marker,
+                bio.end_marker
+            );
+            // Not retried until the section changes, so that the warning
+            // isn't logged after every poll.
+            self.applied_section = Some(section);
+
+            return Ok(());
+        };
+
+        client
+            .api
+            .edit_user(&DataEditUser {
+                profile: Some(DataUserProfile {
+                    content: Some(content),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            })
+            .await?;
+        self.applied_section = Some(section);
+
+        Ok(())
+    }
+
+    /// Sets the cover art at `cover` as the background, or the first
+    /// background if `None`, if it changed.
+    async fn set_background(&mut self, cover: Option<String>) -> Result<(), SinkError> {
+        if !self.options.profile.background || cover == self.applied_cover {
+            return Ok(());
+        }
+
+        let Some(client) = &self.client else {
+            match &cover {
+                Some(cover) => println!(
+                    "Stoat profile ({}) dry run, background set to: {cover}",
+                    self.account_name()
+                ),
+                None => println!(
+                    "Stoat profile ({}) dry run, background restored.",
+                    self.account_name()
+                ),
+            }
+            self.applied_cover = cover;
+
+            return Ok(());
+        };
+
+        let background = match &cover {
+            Some(cover) => Some(download(&client.http_client, cover).await?),
+            None => self.first_background.clone(),
+        };
+
+        let data = match background {
+            Some(background) => {
+                let file_id = client
+                    .api
+                    .upload_file(
+                        &client.autumn_url,
+                        BACKGROUNDS_TAG,
+                        background.filename,
+                        &background.content_type,
+                        background.bytes,
+                    )
+                    .await?;
+
+                DataEditUser {
+                    profile: Some(DataUserProfile {
+                        background: Some(file_id),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                }
+            }
+            None => DataEditUser {
+                remove: Some(vec![FieldsUser::ProfileBackground]),
+                ..Default::default()
+            },
+        };
+        client.api.edit_user(&data).await?;
+        self.applied_cover = cover;
+
+        Ok(())
+    }
+
+    /// Skips the update if rate limited, so that it's retried on the next
+    /// call.
+    fn handle_rate_limit(&mut self, result: Result<(), SinkError>) -> Result<(), SinkError> {
+        match result {
+            Err(SinkError::Stoat(lure_stoat_api::Error::ApiError(
+                lure_stoat_api::APIError::RateLimitExceeded(remaining),
+            ))) => {
+                self.rate_limited_until = Some(Instant::now() + Duration::from_millis(remaining));
+                println!(
+                    "Stoat profile ({}) rate limit exceeded, retrying in {remaining}ms.",
+                    self.account_name()
+                );
+
+                Ok(())
+            }
+            result => result,
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Sink for StoatProfileSink {
+    fn name(&self) -> String {
+        format!("Stoat profile {}", self.account_name())
+    }
+
+    /// Accepts the options of the same account, in the same dry run mode,
+    /// with the same bio markers and background option, since the first
+    /// profile depends on them.
+    fn accepts(&self, options: &SinkOptions) -> bool {
+        let SinkOptions::StoatProfile { options, dry_run } = options else {
+            return false;
+        };
+        let markers = |options: &Options| {
+            options
+                .profile
+                .bio
+                .as_ref()
+                .map(|bio| (bio.start_marker.clone(), bio.end_marker.clone()))
+        };
+
+        *dry_run == self.client.is_none()
+            && self.options.api_url == options.api_url
+            && self.options.session_token == options.session_token
+            && self.options.profile.background == options.profile.background
+            && markers(&self.options) == markers(options)
+    }
+
+    fn reconfigure(&mut self, options: SinkOptions) {
+        debug_assert!(self.accepts(&options));
+
+        if let SinkOptions::StoatProfile { options, .. } = options {
+            self.options = options;
+        }
+    }
+
+    /// Updates the bio section and the background if they changed. Tracks
+    /// hidden by a privacy filter don't show their cover art. While rate
+    /// limited, the update is skipped and retried on the next call.
+    async fn update(
+        &mut self,
+        event: &Event<'_>,
+        stats: &Stats,
+        _metrics: &ProfileMetrics,
+    ) -> Result<(), SinkError> {
+        if self
+            .rate_limited_until
+            .is_some_and(|until| Instant::now() < until)
+        {
+            return Ok(());
+        }
+        self.rate_limited_until = None;
+
+        if let Some(bio) = &self.options.profile.bio {
+            let section = self.section(bio, event, stats);
+            let result = self.set_section(section).await;
+            self.handle_rate_limit(result)?;
+
+            // The background would be rate limited too.
+            if self.rate_limited_until.is_some() {
+                return Ok(());
+            }
+        }
+
+        let cover = match *event {
+            Event::Playing {
+                track,
+                replacement: None,
+            } => track.cover_url.clone(),
+            _ => None,
+        };
+        let result = self.set_background(cover).await;
+        self.handle_rate_limit(result)
+    }
+
+    /// Puts the first bio section and background back.
+    async fn reset(&mut self) -> Result<(), SinkError> {
+        if self.options.profile.bio.is_some() {
+            self.set_section(self.first_section.clone()).await?;
+        }
+        self.set_background(None).await
+    }
+}
+
+/// Returns the byte range of the section of `bio` between the markers.
+fn section_range(bio: &str, options: &BioOptions) -> Option<Range<usize>> {
+    let start = bio.find(&options.start_marker)? + options.start_marker.len();
+    let end = start + bio[start..].find(&options.end_marker)?;
+
+    Some(start..end)
+}
+
+/// Replaces the section of `bio` between the markers with `section`,
+/// keeping the whitespace around it, like the line breaks after and before
+/// the markers.
+fn replace_section(bio: &str, options: &BioOptions, section: &str) -> Option<String> {
+    let range = section_range(bio, options)?;
+    let current = &bio[range.clone()];
+    let (leading, trailing) = if current.trim().is_empty() {
+        (current, "")
+    } else {
+        (
+            &current[..current.len() - current.trim_start().len()],
+            &current[current.trim_end().len()..],
+        )
+    };
+
+    Some(format!(
+        "{}{leading}{}{trailing}{}",
+        &bio[..range.start],
+        section.trim(),
+        &bio[range.end..]
+    ))
+}
+
+/// Downloads the cover art at `url`.
+async fn download(http_client: &reqwest::Client, url: &str) -> Result<Background, reqwest::Error> {
+    let response = http_client.get(url).send().await?.error_for_status()?;
+    let content_type = response
+        .headers()
+        .get(reqwest::header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or("application/octet-stream")
+        .to_string();
+    let filename = response
+        .url()
+        .path_segments()
+        .and_then(|mut segments| segments.next_back())
+        .filter(|segment| !segment.is_empty())
+        .unwrap_or("cover")
+        .to_string();
+
+    Ok(Background {
+        filename,
+        content_type,
+        bytes: response.bytes().await?.to_vec(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use axum::extract::State;
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse as _;
+    use axum::routing::{get, patch};
+    use lure_types::TrackInfo;
+
+    use super::*;
+    use crate::metrics::Metrics;
+
+    fn options() -> BioOptions {
+        BioOptions {
+            template: String::from("🎵 %NAME%"),
+            idle: None,
+            start_marker: String::from("[np]"),
+            end_marker: String::from("[/np]"),
+        }
+    }
+
+    #[test]
+    fn test_replace_section() {
+        assert_eq!(
+            replace_section("Meow\n[np]Old[/np]\nPurr", &options(), "New").as_deref(),
+            Some("Meow\n[np]New[/np]\nPurr")
+        );
+    }
+
+    #[test]
+    fn test_replace_section_keeps_whitespace() {
+        assert_eq!(
+            replace_section("[np]\n  Old\n[/np]", &options(), " New \n").as_deref(),
+            Some("[np]\n  New\n[/np]")
+        );
+        assert_eq!(
+            replace_section("[np]\r\nOld\r\n[/np]", &options(), "New").as_deref(),
+            Some("[np]\r\nNew\r\n[/np]")
+        );
+    }
+
+    #[test]
+    fn test_replace_empty_section() {
+        assert_eq!(
+            replace_section("[np][/np]", &options(), "New").as_deref(),
+            Some("[np]New[/np]")
+        );
+        assert_eq!(
+            replace_section("[np]\n[/np]", &options(), "New").as_deref(),
+            Some("[np]\nNew[/np]")
+        );
+        assert_eq!(
+            replace_section("[np]Old[/np]", &options(), "").as_deref(),
+            Some("[np][/np]")
+        );
+    }
+
+    #[test]
+    fn test_missing_markers() {
+        assert_eq!(section_range("Meow", &options()), None);
+        assert_eq!(section_range("[np]Meow", &options()), None);
+        assert_eq!(section_range("Meow[/np]", &options()), None);
+        assert_eq!(replace_section("[np]Meow", &options(), "New"), None);
+    }
+
+    #[test]
+    fn test_end_marker_before_start_marker() {
+        assert_eq!(section_range("[/np]Meow[np]", &options()), None);
+        assert_eq!(
+            section_range("[/np]Meow[np]Purr[/np]", &options()),
+            Some(13..17)
+        );
+    }
+
+    fn track() -> TrackInfo {
+        TrackInfo {
+            artist: String::from("Cat"),
+            title: String::from("Meow"),
+            album: None,
+            cover_url: Some(String::from("https://example.com/cover.png")),
+            url: None,
+        }
+    }
+
+    /// A sink in dry run, with `Purr` as the first bio section.
+    async fn sink(yaml: &str) -> StoatProfileSink {
+        let mut sink = StoatProfileSink::connect(
+            serde_yaml::from_str(yaml).unwrap(),
+            &reqwest::Client::new(),
+            true,
+        )
+        .await
+        .unwrap();
+        sink.first_section = String::from("Purr");
+        sink.applied_section = Some(String::from("Purr"));
+
+        sink
+    }
+
+    async fn update(sink: &mut StoatProfileSink, event: &Event<'_>) {
+        let metrics = Arc::new(Metrics::new()).profile("kitty");
+
+        sink.update(event, &Stats::default(), &metrics)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_update_and_reset() {
+        let mut sink = sink("profile: {bio: {template: '%NAME%'}, background: true}").await;
+        let track = track();
+
+        update(
+            &mut sink,
+            &Event::Playing {
+                track: &track,
+                replacement: None,
+            },
+        )
+        .await;
+        assert_eq!(sink.applied_section.as_deref(), Some("Meow"));
+        assert_eq!(sink.applied_cover, track.cover_url);
+
+        sink.reset().await.unwrap();
+        assert_eq!(sink.applied_section.as_deref(), Some("Purr"));
+        assert_eq!(sink.applied_cover, None);
+    }
+
+    #[tokio::test]
+    async fn test_hidden_track_has_no_background() {
+        let mut sink = sink("profile: {bio: {}, background: true}").await;
+        let track = track();
+
+        update(
+            &mut sink,
+            &Event::Playing {
+                track: &track,
+                replacement: Some("Something"),
+            },
+        )
+        .await;
+
+        assert_eq!(sink.applied_section.as_deref(), Some("Something"));
+        assert_eq!(sink.applied_cover, None);
+    }
+
+    #[tokio::test]
+    async fn test_idle_section() {
+        let mut sink = sink("profile: {bio: {idle: Nothing}}").await;
+
+        update(&mut sink, &Event::Idle).await;
+        assert_eq!(sink.applied_section.as_deref(), Some("Nothing"));
+
+        sink.reset().await.unwrap();
+        assert_eq!(sink.applied_section.as_deref(), Some("Purr"));
+    }
+
+    #[tokio::test]
+    async fn test_rate_limited_bio_skips_the_background() {
+        type Requests = Arc<Mutex<Vec<String>>>;
+
+        // A Stoat API rate limiting the profile edits, recording the
+        // requests.
+        let requests = Requests::default();
+        let router = axum::Router::new()
+            .route(
+                "/users/kitty/profile",
+                get(|State(requests): State<Requests>| async move {
+                    requests.lock().unwrap().push(String::from("GET profile"));
+                    axum::Json(serde_json::json!({ "content": "[np][/np]" }))
+                }),
+            )
+            .route(
+                "/users/@me",
+                patch(|State(requests): State<Requests>| async move {
+                    requests.lock().unwrap().push(String::from("PATCH user"));
+                    (
+                        StatusCode::TOO_MANY_REQUESTS,
+                        [("X-Ratelimit-Reset-After", "60000")],
+                    )
+                        .into_response()
+                }),
+            )
+            .fallback(
+                |State(requests): State<Requests>, uri: axum::http::Uri| async move {
+                    requests.lock().unwrap().push(format!("unexpected {uri}"));
+                    StatusCode::NOT_FOUND
+                },
+            )
+            .with_state(Arc::clone(&requests));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+
+        let http_client = reqwest::Client::new();
+        let mut sink =
+            sink("profile: {bio: {start_marker: '[np]', end_marker: '[/np]'}, background: true}")
+                .await;
+        sink.client = Some(Client {
+            api: lure_stoat_api::Client::with_http_client(
+                http_client.clone(),
+                format!("http://{address}"),
+                &lure_stoat_models::Authentication::SessionToken(String::from("meow")),
+            )
+            .unwrap(),
+            http_client,
+            user_id: String::from("kitty"),
+            autumn_url: format!("http://{address}/autumn"),
+        });
+        let mut track = track();
+        track.cover_url = Some(format!("http://{address}/cover.png"));
+
+        update(
+            &mut sink,
+            &Event::Playing {
+                track: &track,
+                replacement: None,
+            },
+        )
+        .await;
+
+        assert_eq!(*requests.lock().unwrap(), ["GET profile", "PATCH user"]);
+        assert!(sink.rate_limited_until.is_some());
+        assert_eq!(sink.applied_cover, None);
+    }
+}
@@ -0,0 +1,280 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use lure_config::profile::Profile;
+use lure_history::History;
+use tokio::sync::{mpsc, watch};
+use tokio::task::{self, JoinSet};
+
+use crate::metrics::{Metrics, ProfileMetrics};
+use crate::runner::Runner;
+use crate::start::RunError;
+use crate::state::{LastError, ProfileState, State, unix_timestamp};
+
+/// Runs every profile in its own task, so that a profile stopping because
+/// of an error doesn't affect the others. All tasks share one HTTP client,
+/// and with it, one connection pool.
+pub struct Supervisor {
+    http_client: reqwest::Client,
+    state: Arc<State>,
+    metrics: Arc<Metrics>,
+    history: Option<Arc<History>>,
+    profiles: HashMap<String, Task>,
+    tasks: JoinSet<Result<(), RunError>>,
+    failed: usize,
+}
+
+struct Task {
+    /// The profile as last applied by the task. A reload that fails leaves
+    /// it unchanged, so that applying the same profile again retries it.
+    profile: watch::Receiver<Profile>,
+    /// The ID of the task, or `None` if it has stopped.
+    id: Option<task::Id>,
+    commands: mpsc::UnboundedSender<Command>,
+}
+
+enum Command {
+    Reload(Box<Profile>),
+    Stop,
+}
+
+impl Supervisor {
+    pub fn new(state: Arc<State>, metrics: Arc<Metrics>, history: Option<Arc<History>>) -> Self {
+        Self {
+            http_client: reqwest::Client::new(),
+            state,
+            metrics,
+            history,
+            profiles: HashMap::new(),
+            tasks: JoinSet::new(),
+            failed: 0,
+        }
+    }
+
+    /// Starts, reloads and stops tasks so that they match `profiles`.
+    ///
+    /// Profiles are matched by their name: removed profiles are stopped,
+    /// added ones are started, and changed ones are reloaded, or started
+    /// again if they had stopped. Unchanged profiles are left alone.
+    pub fn apply(&mut self, profiles: Vec<Profile>) {
+        let removed: Vec<String> = self
+            .profiles
+            .keys()
+            .filter(|name| !profiles.iter().any(|profile| &profile.name == *name))
+            .cloned()
+            .collect();
+
+        for name in removed {
+            if let Some(task) = self.profiles.remove(&name) {
+                let _ = task.commands.send(Command::Stop);
+                self.state.remove_profile(&name);
+                println!("[{name}] Removed the profile.");
+            }
+        }
+
+        for profile in profiles {
+            match self.profiles.get_mut(&profile.name) {
+                Some(task) if *task.profile.borrow() == profile => {}
+                Some(task) if task.id.is_some() => {
+                    let _ = task.commands.send(Command::Reload(Box::new(profile)));
+                }
+                _ => self.spawn(profile),
+            }
+        }
+    }
+
+    /// Waits until the task of a profile stops.
+    pub async fn stopped(&mut self) {
+        loop {
+            let Some(joined) = self.tasks.join_next_with_id().await else {
+                return std::future::pending().await;
+            };
+
+            if self.finish(joined) {
+                return;
+            }
+        }
+    }
+
+    /// Whether no profile is running anymore.
+    pub fn is_idle(&self) -> bool {
+        self.profiles.values().all(|task| task.id.is_none())
+    }
+
+    /// Stops every task and waits until they restore the statuses.
+    pub async fn stop(mut self) -> Result<(), RunError> {
+        for task in self.profiles.values() {
+            let _ = task.commands.send(Command::Stop);
+        }
+
+        while let Some(joined) = self.tasks.join_next_with_id().await {
+            self.finish(joined);
+        }
+
+        if self.failed > 0 {
+            return Err(RunError::ProfilesFailed(self.failed));
+        }
+
+        Ok(())
+    }
+
+    /// Records that a task stopped. Returns `false` if the task belonged to
+    /// a removed profile, which is no longer tracked.
+    fn finish(
+        &mut self,
+        joined: Result<(task::Id, Result<(), RunError>), task::JoinError>,
+    ) -> bool {
+        let (id, result) = match joined {
+            Ok((id, result)) => (id, result.map_err(|error| error.to_string())),
+            Err(error) => (error.id(), Err(error.to_string())),
+        };
+
+        let Some((name, task)) = self
+            .profiles
+            .iter_mut()
+            .find(|(_, task)| task.id == Some(id))
+        else {
+            return false;
+        };
+
+        task.id = None;
+
+        match &result {
+            Ok(()) => println!("[{name}] Stopped."),
+            Err(error) => {
+                eprintln!("[{name}] Stopped because of an error: {error}");
+                self.failed += 1;
+            }
+        }
+
+        self.state.update(|snapshot| {
+            if let Some(profile) = snapshot.profiles.get_mut(name) {
+                profile.running = false;
+
+                if let Err(message) = result {
+                    profile.last_error = Some(LastError {
+                        message,
+                        timestamp: unix_timestamp(),
+                    });
+                }
+            }
+        });
+
+        true
+    }
+
+    fn spawn(&mut self, profile: Profile) {
+        let (commands, receiver) = mpsc::unbounded_channel();
+        let state = self.state.profile(&profile.name);
+        let metrics = self.metrics.profile(&profile.name);
+        let name = profile.name.clone();
+        let (applied, applied_profile) = watch::channel(profile.clone());
+
+        let id = self
+            .tasks
+            .spawn(run(
+                profile,
+                self.http_client.clone(),
+                state,
+                metrics,
+                self.history.clone(),
+                receiver,
+                applied,
+            ))
+            .id();
+
+        self.profiles.insert(
+            name,
+            Task {
+                profile: applied_profile,
+                id: Some(id),
+                commands,
+            },
+        );
+    }
+}
+
+async fn run(
+    profile: Profile,
+    http_client: reqwest::Client,
+    state: ProfileState,
+    metrics: ProfileMetrics,
+    history: Option<Arc<History>>,
+    mut commands: mpsc::UnboundedReceiver<Command>,
+    applied: watch::Sender<Profile>,
+) -> Result<(), RunError> {
+    let name = profile.name.clone();
+    let mut runner = Runner::try_new(profile, http_client, state, metrics, history).await?;
+
+    loop {
+        tokio::select! {
+            command = commands.recv() => match command {
+                Some(Command::Reload(profile)) => match runner.reload((*profile).clone()).await {
+                    Ok(()) => {
+                        applied.send_replace(*profile);
+                    }
+                    Err(error) => {
+                        eprintln!("[{name}] Failed to apply the reloaded configuration: {error}");
+                    }
+                },
+                Some(Command::Stop) | None => break,
+            },
+            result = runner.poll() => {
+                if runner.handle(result).await?.is_break() {
+                    break;
+                }
+            }
+            () = runner.schedule_changed() => runner.apply_schedule().await?,
+        }
+    }
+
+    runner.restore().await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    /// A profile polling a command that never plays anything, with
+    /// `filters`.
+    fn profile(filters: &str) -> Profile {
+        serde_yaml::from_str(&format!(
+            "
+            name: kitty
+            service:
+                exec:
+                    command: [sh, -c, 'echo {{}}']
+            stoat: []
+            filters: {filters}
+            dry_run: true
+            "
+        ))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_apply_keeps_the_profile_until_it_is_reloaded() {
+        let mut supervisor = Supervisor::new(Arc::default(), Arc::new(Metrics::new()), None);
+        let initial = profile("[]");
+        let invalid = profile("[{ field: artist, match: regex, pattern: '(' }]");
+        let changed = profile("[{ field: artist, pattern: Kitty }]");
+
+        supervisor.apply(vec![initial.clone()]);
+        supervisor.apply(vec![invalid]);
+        assert_eq!(*supervisor.profiles["kitty"].profile.borrow(), initial);
+
+        supervisor.apply(vec![changed.clone()]);
+        let mut applied = supervisor.profiles["kitty"].profile.clone();
+        tokio::time::timeout(
+            Duration::from_secs(5),
+            applied.wait_for(|profile| *profile == changed),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        supervisor.stop().await.unwrap();
+    }
+}
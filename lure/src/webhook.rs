@@ -0,0 +1,489 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+use hmac::{Hmac, KeyInit as _, Mac as _};
+use lure_config::webhook::Options;
+use lure_types::{TrackInfo, stats::Stats};
+use secrecy::{ExposeSecret as _, SecretString};
+use sha2::Sha256;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+
+use crate::metrics::ProfileMetrics;
+use crate::sink::{Event, Sink, SinkError, SinkOptions};
+
+/// Delay before retrying a failed delivery, doubled after every failure.
+const INITIAL_RETRY_DELAY: Duration = Duration::from_secs(1);
+/// Longest delay before retrying a failed delivery.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(2 * 60);
+/// How long a delivery can take before it's considered failed.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+/// How long the queued events are given to be delivered when the webhook
+/// is removed or lure stops.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Header carrying the hex encoded HMAC-SHA256 of the timestamp header, a
+/// `.` and the body, prefixed with `sha256=`.
+const SIGNATURE_HEADER: &str = "X-Lure-Signature";
+/// Header carrying the Unix timestamp of the delivery, which is signed
+/// along the body so that endpoints can reject replayed deliveries.
+const TIMESTAMP_HEADER: &str = "X-Lure-Timestamp";
+
+/// A URL the playback events are posted to as JSON.
+///
+/// Events are queued and delivered in order by a background task, which
+/// retries failed deliveries with an exponential backoff, so a slow or
+/// unreachable endpoint never holds back the other sinks.
+pub struct WebhookSink {
+    options: Options,
+    profile: String,
+    service: &'static str,
+    /// What's playing as last sent, or `None` if nothing is.
+    playing: Option<Playing>,
+    queue: Arc<Queue>,
+    /// The delivering task, or `None` in dry run, where the events are only
+    /// logged.
+    worker: Option<JoinHandle<()>>,
+    next_id: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Playing {
+    /// The current track, or `None` if it's hidden by a privacy filter.
+    track: Option<TrackInfo>,
+    /// Unix timestamp of when the track started.
+    started_at: i64,
+}
+
+#[derive(Debug, Clone, Copy, serde::Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Change {
+    Started,
+    Changed,
+    Stopped,
+}
+
+#[derive(serde::Serialize)]
+struct Payload<'a> {
+    event: Change,
+    profile: &'a str,
+    service: &'a str,
+    /// The current track, or `None` if nothing is playing or the track is
+    /// hidden by a privacy filter.
+    track: Option<&'a TrackInfo>,
+    started_at: Option<i64>,
+    /// Unix timestamp of the event.
+    timestamp: i64,
+}
+
+/// Events waiting to be delivered, oldest first. An event stays at the
+/// front of the queue until it's delivered.
+struct Queue {
+    capacity: usize,
+    entries: Mutex<VecDeque<(u64, String)>>,
+    /// Notified when an event is pushed.
+    pushed: Notify,
+    /// Notified when the queue becomes empty.
+    drained: Notify,
+}
+
+#[derive(Debug, thiserror::Error)]
+enum DeliveryError {
+    #[error(transparent)]
+    Request(#[from] reqwest::Error),
+    #[error("the endpoint responded with {0}")]
+    Status(reqwest::StatusCode),
+}
+
+impl WebhookSink {
+    pub fn new(
+        options: Options,
+        profile: String,
+        service: &'static str,
+        http_client: &reqwest::Client,
+        dry_run: bool,
+    ) -> Self {
+        let queue = Arc::new(Queue::new(options.queue_size));
+        let worker = (!dry_run).then(|| {
+            tokio::spawn(deliver(
+                http_client.clone(),
+                options.url.clone(),
+                options.secret.clone(),
+                Arc::clone(&queue),
+            ))
+        });
+
+        Self {
+            options,
+            profile,
+            service,
+            playing: None,
+            queue,
+            worker,
+            next_id: 0,
+        }
+    }
+
+    /// Queues an event for the current track if it changed.
+    fn change(&mut self, track: Option<Option<&TrackInfo>>) -> Result<(), SinkError> {
+        let change = match (&self.playing, track) {
+            (None, None) => return Ok(()),
+            (Some(playing), Some(track)) if playing.track.as_ref() == track => return Ok(()),
+            (None, Some(_)) => Change::Started,
+            (Some(_), Some(_)) => Change::Changed,
+            (Some(_), None) => Change::Stopped,
+        };
+
+        let timestamp = jiff::Timestamp::now().as_second();
+        self.playing = track.map(|track| Playing {
+            track: track.cloned(),
+            started_at: timestamp,
+        });
+
+        let body = serde_json::to_string(&Payload {
+            event: change,
+            profile: &self.profile,
+            service: self.service,
+            track: self
+                .playing
+                .as_ref()
+                .and_then(|playing| playing.track.as_ref()),
+            started_at: self.playing.as_ref().map(|playing| playing.started_at),
+            timestamp,
+        })?;
+
+        if self.worker.is_none() {
+            println!("Webhook ({}) dry run, sending: {body}", self.options.url);
+            return Ok(());
+        }
+
+        self.next_id += 1;
+        if self.queue.push(self.next_id, body) {
+            eprintln!(
+                "Webhook ({}) queue is full, dropped the oldest event.",
+                self.options.url
+            );
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for WebhookSink {
+    fn drop(&mut self) {
+        if let Some(worker) = &self.worker {
+            worker.abort();
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl Sink for WebhookSink {
+    fn name(&self) -> String {
+        format!("webhook {}", self.options.url)
+    }
+
+    /// Accepts the same options in the same dry run mode, since the
+    /// delivering task is bound to them.
+    fn accepts(&self, options: &SinkOptions) -> bool {
+        let SinkOptions::Webhook {
+            options, dry_run, ..
+        } = options
+        else {
+            return false;
+        };
+
+        *dry_run == self.worker.is_none() && self.options == *options
+    }
+
+    fn reconfigure(&mut self, options: SinkOptions) {
+        debug_assert!(self.accepts(&options));
+
+        if let SinkOptions::Webhook {
+            profile, service, ..
+        } = options
+        {
+            self.profile = profile;
+            self.service = service;
+        }
+    }
+
+    async fn update(
+        &mut self,
+        event: &Event<'_>,
+        _stats: &Stats,
+        _metrics: &ProfileMetrics,
+    ) -> Result<(), SinkError> {
+        self.change(match *event {
+            Event::Playing { track, replacement } => Some(replacement.is_none().then_some(track)),
+            Event::Idle => None,
+        })
+    }
+
+    /// Sends a stop event if something was playing.
+    async fn reset(&mut self) -> Result<(), SinkError> {
+        self.change(None)
+    }
+
+    /// Sends a stop event if something was playing, and gives the queued
+    /// events a few seconds to be delivered.
+    async fn restore(&mut self) -> Result<(), SinkError> {
+        self.change(None)?;
+
+        if tokio::time::timeout(FLUSH_TIMEOUT, self.queue.drained())
+            .await
+            .is_err()
+        {
+            eprintln!(
+                "Webhook ({}) couldn't deliver every queued event in time, dropping them.",
+                self.options.url
+            );
+        }
+
+        Ok(())
+    }
+}
+
+impl Queue {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            entries: Mutex::new(VecDeque::new()),
+            pushed: Notify::new(),
+            drained: Notify::new(),
+        }
+    }
+
+    /// Pushes an event, dropping the oldest one if the queue is full.
+    /// Returns whether an event was dropped.
+    fn push(&self, id: u64, body: String) -> bool {
+        let mut entries = self.entries();
+        let dropped = entries.len() >= self.capacity && entries.pop_front().is_some();
+        entries.push_back((id, body));
+        drop(entries);
+
+        self.pushed.notify_one();
+
+        dropped
+    }
+
+    /// Waits for an event, and returns the oldest one without removing it.
+    async fn front(&self) -> (u64, String) {
+        loop {
+            let entry = self.entries().front().cloned();
+            if let Some(entry) = entry {
+                return entry;
+            }
+
+            self.pushed.notified().await;
+        }
+    }
+
+    /// Removes a delivered event, unless it was already dropped.
+    fn remove(&self, id: u64) {
+        let mut entries = self.entries();
+        if entries.front().is_some_and(|(front, _)| *front == id) {
+            entries.pop_front();
+        }
+        let empty = entries.is_empty();
+        drop(entries);
+
+        if empty {
+            self.drained.notify_one();
+        }
+    }
+
+    /// Waits until every event is delivered.
+    async fn drained(&self) {
+        while !self.entries().is_empty() {
+            self.drained.notified().await;
+        }
+    }
+
+    /// Locks the events. Nothing can panic while they're locked, so they're
+    /// still usable if the lock is poisoned.
+    fn entries(&self) -> MutexGuard<'_, VecDeque<(u64, String)>> {
+        self.entries.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Delivers the queued events in order, retrying each one until it's
+/// delivered, or rejected by the endpoint.
+async fn deliver(
+    http_client: reqwest::Client,
+    url: String,
+    secret: Option<SecretString>,
+    queue: Arc<Queue>,
+) {
+    loop {
+        let (id, body) = queue.front().await;
+        let mut delay = INITIAL_RETRY_DELAY;
+
+        loop {
+            match post(&http_client, &url, secret.as_ref(), &body).await {
+                Ok(()) => break,
+                Err(DeliveryError::Status(status))
+                    if status.is_client_error()
+                        && status != reqwest::StatusCode::REQUEST_TIMEOUT
+                        && status != reqwest::StatusCode::TOO_MANY_REQUESTS =>
+                {
+                    eprintln!("Webhook ({url}) rejected an event with {status}, dropping it.");
+                    break;
+                }
+                Err(error) => {
+                    eprintln!(
+                        "Webhook ({url}) failed, retrying in {}s: {error}",
+                        delay.as_secs()
+                    );
+                    tokio::time::sleep(delay).await;
+                    delay = delay.saturating_mul(2).min(MAX_RETRY_DELAY);
+                }
+            }
+        }
+
+        queue.remove(id);
+    }
+}
+
+async fn post(
+    http_client: &reqwest::Client,
+    url: &str,
+    secret: Option<&SecretString>,
+    body: &str,
+) -> Result<(), DeliveryError> {
+    let mut request = http_client
+        .post(url)
+        .timeout(REQUEST_TIMEOUT)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(body.to_string());
+    if let Some(secret) = secret {
+        let timestamp = jiff::Timestamp::now().as_second();
+        request = request.header(TIMESTAMP_HEADER, timestamp).header(
+            SIGNATURE_HEADER,
+            signature(secret, &format!("{timestamp}.{body}")),
+        );
+    }
+
+    let status = request.send().await?.status();
+    if status.is_success() {
+        Ok(())
+    } else {
+        Err(DeliveryError::Status(status))
+    }
+}
+
+/// Signs `message` with HMAC-SHA256, so that the endpoint can check it
+/// comes from lure.
+fn signature(secret: &SecretString, message: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.expose_secret().as_bytes())
+        .expect("HMAC takes keys of any size");
+    mac.update(message.as_bytes());
+
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::Metrics;
+
+    #[test]
+    fn test_signature() {
+        // Test case 2 of RFC 4231.
+        assert_eq!(
+            signature(&SecretString::from("Jefe"), "what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_queue_drops_the_oldest_event() {
+        let queue = Queue::new(2);
+
+        assert!(!queue.push(1, String::from("first")));
+        assert!(!queue.push(2, String::from("second")));
+        assert!(queue.push(3, String::from("third")));
+        assert_eq!(queue.front().await, (2, String::from("second")));
+
+        // An event dropped while it was being delivered.
+        queue.remove(1);
+        assert_eq!(queue.front().await, (2, String::from("second")));
+
+        queue.remove(2);
+        queue.remove(3);
+        tokio::time::timeout(Duration::from_secs(1), queue.drained())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_queues_the_changes() {
+        // A worker that never delivers, so that the events stay queued.
+        let mut sink = WebhookSink {
+            options: serde_yaml::from_str("url: https://example.com/hook").unwrap(),
+            profile: String::from("kitty"),
+            service: "lastfm",
+            playing: None,
+            queue: Arc::new(Queue::new(100)),
+            worker: Some(tokio::spawn(std::future::pending())),
+            next_id: 0,
+        };
+        let metrics = Arc::new(Metrics::new()).profile("kitty");
+        let track = |title: &str| TrackInfo {
+            artist: String::from("Cat"),
+            title: title.to_string(),
+            album: None,
+            cover_url: None,
+            url: None,
+        };
+        let (meow, purr) = (track("Meow"), track("Purr"));
+
+        for event in [
+            Event::Playing {
+                track: &meow,
+                replacement: None,
+            },
+            Event::Playing {
+                track: &meow,
+                replacement: None,
+            },
+            Event::Playing {
+                track: &purr,
+                replacement: None,
+            },
+            Event::Playing {
+                track: &purr,
+                replacement: Some("Something"),
+            },
+            Event::Idle,
+        ] {
+            sink.update(&event, &Stats::default(), &metrics)
+                .await
+                .unwrap();
+        }
+        sink.reset().await.unwrap();
+
+        let events: Vec<(String, serde_json::Value)> = sink
+            .queue
+            .entries()
+            .iter()
+            .map(|(_, body)| {
+                let payload: serde_json::Value = serde_json::from_str(body).unwrap();
+                (
+                    payload["event"].as_str().unwrap().to_string(),
+                    payload["track"]["title"].clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            events,
+            [
+                (String::from("started"), serde_json::json!("Meow")),
+                (String::from("changed"), serde_json::json!("Purr")),
+                (String::from("changed"), serde_json::Value::Null),
+                (String::from("stopped"), serde_json::Value::Null),
+            ]
+        );
+    }
+}